@@ -0,0 +1,212 @@
+// alerts.rs — arbitrage opportunities → Telegram
+//
+// The engine re-sends an Open event on every book update while a group stays
+// profitable, which can be dozens a second. This turns that stream into at
+// most one message per opportunity:
+//   - the first Open of an episode is alerted with legs, sizes and profit
+//   - further Opens for the same group are folded into it (deduplicated)
+//   - when it closes, a follow-up says how long it lasted
+//   - a group that was alerted stays quiet for the cooldown after that alert,
+//     so a market flickering around the threshold doesn't spam the chat
+//
+// Episodes that open during a cooldown are tracked but never alerted, and
+// neither is their close. If the task falls behind the engine and events are
+// lost, the open set is forgotten — a lost Closed would otherwise keep that
+// group silent for good.
+
+use crate::arb::{ArbEvent, ArbOpportunity};
+use crate::fetch::Config;
+use crate::telegram::TgQueue;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AlertSettings {
+    pub cooldown: Duration, // min gap between two alerts for the same group
+}
+
+impl AlertSettings {
+    pub fn from_config(config: &Config) -> AlertSettings {
+        AlertSettings { cooldown: Duration::from_secs(config.arb_alert_cooldown_secs) }
+    }
+}
+
+pub struct ArbAlerter {
+    settings: AlertSettings,
+    open: HashMap<String, bool>,                // group_id → alerted this episode?
+    last_alert: HashMap<String, DateTime<Utc>>, // group_id → when we last alerted it
+}
+
+impl ArbAlerter {
+    pub fn new(settings: AlertSettings) -> ArbAlerter {
+        ArbAlerter { settings, open: HashMap::new(), last_alert: HashMap::new() }
+    }
+
+    // Drops every open episode after events were lost. The next Open of each
+    // group starts a new episode (still subject to the cooldown).
+    pub fn forget_open(&mut self) {
+        self.open.clear();
+    }
+
+    // Feeds one engine event in; returns the message to send, if any
+    pub fn on_event(&mut self, event: &ArbEvent) -> Option<String> {
+        match event {
+            ArbEvent::Open(opp) => {
+                if self.open.contains_key(&opp.group_id) {
+                    return None; // same opportunity, already handled
+                }
+
+                let cooling = self.last_alert.get(&opp.group_id).is_some_and(|at| {
+                    (opp.detected_at - *at).to_std().is_ok_and(|gap| gap < self.settings.cooldown)
+                });
+                self.open.insert(opp.group_id.clone(), !cooling);
+                if cooling { return None; }
+
+                self.last_alert.insert(opp.group_id.clone(), opp.detected_at);
+                Some(format_open(opp))
+            }
+            ArbEvent::Closed(rec) => {
+                if !self.open.remove(&rec.group_id)? { return None; }
+                Some(format!(
+                    "⌛ Closed after {:.1}s — {} [{}]\nBest expected profit while open: ${:.2}",
+                    rec.duration_secs(), rec.title, rec.kind.strategy(), rec.peak_profit
+                ))
+            }
+        }
+    }
+}
+
+// ── Message formatting ────────────────────────────────────────────────────────
+fn format_open(opp: &ArbOpportunity) -> String {
+    let legs: Vec<String> = opp.legs.iter()
+        .map(|l| format!(
+            "• {}\n   {:.0} @ avg {:.3} (best {:.3} x{:.0}, limit {:.3})",
+            l.outcome, opp.fill_size, l.avg_price, l.price, l.size, l.worst_price
+        ))
+        .collect();
+    format!(
+        "💰 ARB — {}\n{}\n\n{}\n\nSize: {:.0} sets · cost ${:.2} · fees ${:.2} · gas ${:.2}\nExpected profit: ${:.2}",
+        opp.title,
+        opp.explain(),
+        legs.join("\n"),
+        opp.fill_size,
+        opp.notional,
+        opp.fees,
+        opp.gas,
+        opp.expected_profit,
+    )
+}
+
+// ── Engine → Telegram ─────────────────────────────────────────────────────────
+// Broadcasts alerts to every active subscriber.
+pub fn forward_arbs_to_telegram(mut events: broadcast::Receiver<ArbEvent>, tg: TgQueue, settings: AlertSettings) {
+    tokio::spawn(async move {
+        let mut alerter = ArbAlerter::new(settings);
+        loop {
+            let event = match events.recv().await {
+                Ok(e) => e,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(skipped = n, "fell behind the arb engine, forgetting open episodes");
+                    alerter.forget_open();
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            if let Some(text) = alerter.on_event(&event) {
+                tg.broadcast(text);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arb::{ArbRecord, GroupKind};
+
+    const T0: i64 = 1_700_000_000_000;
+
+    fn open(group_id: &str, at_secs: i64) -> ArbEvent {
+        ArbEvent::Open(ArbOpportunity {
+            group_id: group_id.to_string(),
+            title: "A vs B".to_string(),
+            kind: GroupKind::Binary,
+            legs: vec![],
+            cost: 0.95,
+            payout: 1.0,
+            edge: 0.05,
+            fill_size: 100.0,
+            notional: 95.0,
+            fees: 0.0,
+            gas: 0.0,
+            expected_profit: 5.0,
+            detected_at: DateTime::from_timestamp_millis(T0 + at_secs * 1000).unwrap_or_default(),
+        })
+    }
+
+    fn closed(group_id: &str) -> ArbEvent {
+        ArbEvent::Closed(ArbRecord {
+            group_id: group_id.to_string(),
+            title: "A vs B".to_string(),
+            league: "nba".to_string(),
+            kind: GroupKind::Binary,
+            opened_at_ms: T0,
+            closed_at_ms: T0 + 2500,
+            peak_edge: 0.05,
+            peak_size: 100.0,
+            peak_profit: 5.0,
+            updates: 3,
+        })
+    }
+
+    fn alerter(cooldown_secs: u64) -> ArbAlerter {
+        ArbAlerter::new(AlertSettings { cooldown: Duration::from_secs(cooldown_secs) })
+    }
+
+    #[test]
+    fn one_alert_per_episode() {
+        let mut a = alerter(0);
+        let first = a.on_event(&open("g", 0)).expect("first open alerted");
+        assert!(first.contains("A vs B") && first.contains("Expected profit: $5.00"), "{first}");
+        assert_eq!(a.on_event(&open("g", 1)), None);
+        assert_eq!(a.on_event(&open("g", 2)), None);
+
+        let close = a.on_event(&closed("g")).expect("close of an alerted episode");
+        assert!(close.starts_with("⌛ Closed after 2.5s"), "{close}");
+        assert_eq!(a.on_event(&closed("g")), None, "closed twice");
+        assert_eq!(a.on_event(&closed("never-opened")), None);
+    }
+
+    #[test]
+    fn cooldown_silences_a_whole_episode() {
+        let mut a = alerter(60);
+        assert!(a.on_event(&open("g", 0)).is_some());
+        assert!(a.on_event(&closed("g")).is_some());
+
+        // Reopens 30s after the alert: neither the open nor its close is sent
+        assert_eq!(a.on_event(&open("g", 30)), None);
+        assert_eq!(a.on_event(&open("g", 61)), None, "still the same episode");
+        assert_eq!(a.on_event(&closed("g")), None);
+
+        // Other groups have their own cooldown
+        assert!(a.on_event(&open("h", 30)).is_some());
+
+        // Past the cooldown the next episode is alerted again
+        assert!(a.on_event(&open("g", 61)).is_some());
+    }
+
+    #[test]
+    fn forgetting_lost_episodes() {
+        let mut a = alerter(60);
+        assert!(a.on_event(&open("g", 0)).is_some());
+        a.forget_open();
+        // The Closed was lost: a new episode still respects the cooldown...
+        assert_eq!(a.on_event(&open("g", 10)), None);
+        a.forget_open();
+        // ...and is alerted once it has passed
+        assert!(a.on_event(&open("g", 120)).is_some());
+    }
+}
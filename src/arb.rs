@@ -0,0 +1,765 @@
+// arb.rs — N-outcome arbitrage detector
+//
+// Tokens are grouped into sets whose combined payout is fixed no matter how
+// the event resolves. If the best asks across a whole group cost less than
+// that payout (minus a margin set by the threshold), buying one share of every
+// leg locks in the difference.
+//
+// Groups come from the events fetch.rs already pulls:
+//   - every moneyline market on its own (2-way: Team A / Team B, or Yes / No)
+//     — one side pays $1
+//   - soccer-style events with 3+ Yes/No moneyline markets (Home / Draw / Away)
+//     are logically linked — exactly one of the Yes tokens can win, so:
+//       · Yes on every outcome pays $1
+//       · No on every outcome pays $(N − 1), since all but one No wins
+//     Either is mispriced when the separate markets don't add up.
+//
+// The engine keeps its own copy of every book from MarketUpdate::Book events
+// and re-evaluates only the groups that contain the asset that changed. It
+// also tracks each open group from first detection until it stops being
+// profitable, and reports the finished lifecycle as an ArbRecord
+// (ArbEvent::Closed) — see stats.rs for where those end up.
+//
+// Top-of-book below the threshold is only the first filter. The engine then
+// walks the ask ladder of every leg together, buying full sets while one more
+// set still costs less than its payout after fees (see fees.rs), and reports the
+// expected USDC profit at that size. Only opportunities above the configured
+// minimum profit are emitted.
+
+use crate::book::OrderBook;
+use crate::fees::FeeConfig;
+use crate::fetch::{event_league, extract_moneyline_markets, Config};
+use crate::metrics::metrics;
+use crate::ws::MarketUpdate;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio::sync::{broadcast, mpsc};
+use tracing::warn;
+
+// ================================================================================
+// GROUPS
+// ================================================================================
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupKind {
+    Binary, // both tokens of one market
+    AllYes, // the "Yes" token of each market in an N-way event
+    AllNo,  // the "No" token of each market in an N-way event
+}
+
+impl GroupKind {
+    // Short name of the trade
+    pub fn strategy(self) -> &'static str {
+        match self {
+            GroupKind::Binary => "both sides",
+            GroupKind::AllYes => "Yes on all",
+            GroupKind::AllNo  => "No on all",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GroupLeg {
+    pub asset_id: String,
+    pub outcome: String,
+    pub market: String, // condition id of the market the token belongs to
+}
+
+#[derive(Debug, Clone)]
+pub struct ArbGroup {
+    pub id: String,    // "<event_id>:<question>", "<event_id>:N-way" or "<event_id>:N-way-no"
+    pub title: String, // event title, for display
+    pub league: String, // see fetch::event_league
+    pub kind: GroupKind,
+    pub legs: Vec<GroupLeg>,
+}
+
+impl ArbGroup {
+    // How many legs pay $1 whichever way the event resolves
+    pub fn winning_legs(&self) -> usize {
+        match self.kind {
+            GroupKind::Binary | GroupKind::AllYes => 1,
+            GroupKind::AllNo => self.legs.len().saturating_sub(1),
+        }
+    }
+
+    // USDC returned by one full set
+    pub fn payout(&self) -> f64 {
+        self.winning_legs() as f64
+    }
+}
+
+// Builds every mutually exclusive group for one Gamma event
+pub fn groups_from_event(event: &Value) -> Vec<ArbGroup> {
+    let event_id = event.get("id").and_then(Value::as_str).unwrap_or("");
+    let title    = event.get("title").and_then(Value::as_str).unwrap_or("").to_string();
+    let league   = event_league(event);
+    let markets  = extract_moneyline_markets(event);
+    let conditions = condition_ids(event);
+    let condition = |question: &str| conditions.get(question).cloned().unwrap_or_default();
+
+    let mut groups: Vec<ArbGroup> = markets
+        .iter()
+        .filter(|(_, tokens, outcomes)| tokens.len() >= 2 && tokens.len() == outcomes.len())
+        .map(|(question, tokens, outcomes)| ArbGroup {
+            id: format!("{}:{}", event_id, question),
+            title: title.clone(),
+            league: league.clone(),
+            kind: GroupKind::Binary,
+            legs: tokens.iter().zip(outcomes)
+                .map(|(t, o)| GroupLeg { asset_id: t.clone(), outcome: format!("{} — {}", question, o), market: condition(question) })
+                .collect(),
+        })
+        .collect();
+
+    // N-way: one Yes/No market per outcome, outcomes ordered ["Yes", "No"]
+    let side_legs = |side: &str| -> Vec<GroupLeg> {
+        markets
+            .iter()
+            .filter_map(|(question, tokens, outcomes)| {
+                let i = outcomes.iter().position(|o| o == side)?;
+                Some(GroupLeg {
+                    asset_id: tokens.get(i)?.clone(),
+                    outcome: format!("{} — {}", question, side),
+                    market: condition(question),
+                })
+            })
+            .collect()
+    };
+    let yes_legs = side_legs("Yes");
+    let no_legs  = side_legs("No");
+    let n = markets.len();
+    if n >= 3 && yes_legs.len() == n {
+        groups.push(ArbGroup {
+            id: format!("{}:{}-way", event_id, n),
+            title: title.clone(),
+            league: league.clone(),
+            kind: GroupKind::AllYes,
+            legs: yes_legs,
+        });
+    }
+    if n >= 3 && no_legs.len() == n {
+        groups.push(ArbGroup {
+            id: format!("{}:{}-way-no", event_id, n),
+            title,
+            league,
+            kind: GroupKind::AllNo,
+            legs: no_legs,
+        });
+    }
+
+    groups
+}
+
+// Question → condition id for every market of the event, so positions know
+// which market_resolved settles them from the moment they're bought
+fn condition_ids(event: &Value) -> HashMap<String, String> {
+    event.get("markets").and_then(Value::as_array).into_iter().flatten()
+        .filter_map(|m| Some((
+            m.get("question")?.as_str()?.to_string(),
+            m.get("conditionId")?.as_str()?.to_string(),
+        )))
+        .collect()
+}
+
+// ================================================================================
+// OPPORTUNITY
+// ================================================================================
+#[derive(Debug, Clone)]
+pub struct ArbLeg {
+    pub asset_id: String,
+    pub outcome: String,
+    pub market: String,   // condition id, empty if Gamma didn't send one
+    pub price: f64,       // best ask
+    pub size: f64,        // shares available at that ask
+    pub avg_price: f64,   // average price paid across the walked levels
+    pub worst_price: f64, // deepest level touched — the limit price for this leg
+}
+
+#[derive(Debug, Clone)]
+pub struct ArbOpportunity {
+    pub group_id: String,
+    pub title: String,
+    pub kind: GroupKind,
+    pub legs: Vec<ArbLeg>,
+    pub cost: f64,            // sum of best asks for one share of every leg
+    pub payout: f64,          // USDC one full set returns
+    pub edge: f64,            // payout - cost, per set, before fees
+    pub fill_size: f64,       // full sets buyable while each set stays profitable
+    pub notional: f64,        // USDC spent on shares at fill_size
+    pub fees: f64,            // taker + winner fees at fill_size
+    pub gas: f64,             // per-order costs for every leg
+    pub expected_profit: f64, // fill_size × payout − notional − fees − gas
+    pub detected_at: DateTime<Utc>,
+}
+
+impl ArbOpportunity {
+    // One-line terminal summary
+    pub fn summary(&self) -> String {
+        let legs: Vec<String> = self.legs.iter()
+            .map(|l| format!("{} @ {:.3} (x{:.0})", l.outcome, l.price, l.size))
+            .collect();
+        format!(
+            "ARB {} [{}] | cost={:.3} edge={:.3} | size={:.0} profit=${:.2} | {}",
+            self.title, self.kind.strategy(), self.cost, self.edge, self.fill_size, self.expected_profit, legs.join("  +  ")
+        )
+    }
+
+    // Why the combination can't lose, in plain words — for alerts
+    pub fn explain(&self) -> String {
+        let n = self.legs.len();
+        let why = match self.kind {
+            GroupKind::Binary => "Buy both sides of one market: exactly one of them pays $1".to_string(),
+            GroupKind::AllYes => format!(
+                "Buy Yes on all {n} outcomes: the markets are mutually exclusive, so exactly one Yes pays $1"
+            ),
+            GroupKind::AllNo => format!(
+                "Buy No on all {n} outcomes: only one outcome can happen, so {} of the No legs pay $1",
+                n.saturating_sub(1)
+            ),
+        };
+        format!(
+            "{why}. One set costs ${:.3} at the top of book and returns ${:.2} whatever the result.",
+            self.cost, self.payout
+        )
+    }
+}
+
+// ── Lifecycle record ──────────────────────────────────────────────────────────
+// One opportunity from first detection to close. Timestamps are ms since epoch
+// so records serialize as plain numbers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArbRecord {
+    pub group_id: String,
+    pub title: String,
+    pub league: String,
+    pub kind: GroupKind,
+    pub opened_at_ms: i64,
+    pub closed_at_ms: i64,
+    pub peak_edge: f64,   // best top-of-book edge per set seen while open
+    pub peak_size: f64,   // largest fillable size seen while open
+    pub peak_profit: f64, // best expected profit seen while open
+    pub updates: u32,     // book updates that re-confirmed it
+}
+
+impl ArbRecord {
+    pub fn duration_secs(&self) -> f64 {
+        (self.closed_at_ms - self.opened_at_ms).max(0) as f64 / 1000.0
+    }
+}
+
+// ── Engine output ─────────────────────────────────────────────────────────────
+// Open is re-sent on every book update while the group stays profitable;
+// Closed is sent once, the first time it no longer is.
+#[derive(Debug, Clone)]
+pub enum ArbEvent {
+    Open(ArbOpportunity),
+    Closed(ArbRecord),
+}
+
+// ================================================================================
+// ENGINE
+// ================================================================================
+pub struct ArbEngine {
+    groups: Vec<ArbGroup>,
+    by_asset: HashMap<String, Vec<usize>>, // asset_id → indexes into groups
+    books: HashMap<String, OrderBook>,
+    threshold: f64,                        // top-of-book prefilter: edge > 1 − threshold per set
+    min_profit: f64,                       // USDC, after fees and gas
+    fees: FeeConfig,
+    open: HashMap<String, ArbRecord>,      // group_id → lifecycle so far
+}
+
+impl ArbEngine {
+    pub fn new(threshold: f64, min_profit: f64, fees: FeeConfig) -> ArbEngine {
+        ArbEngine {
+            groups: Vec::new(),
+            by_asset: HashMap::new(),
+            books: HashMap::new(),
+            threshold,
+            min_profit,
+            fees,
+            open: HashMap::new(),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> ArbEngine {
+        ArbEngine::new(config.arb_threshold, config.arb_min_profit_usdc, config.fees.clone())
+    }
+
+    pub fn add_group(&mut self, group: ArbGroup) {
+        if self.groups.iter().any(|g| g.id == group.id) { return; }
+        let idx = self.groups.len();
+        for leg in &group.legs {
+            self.by_asset.entry(leg.asset_id.clone()).or_default().push(idx);
+        }
+        self.groups.push(group);
+    }
+
+    // Drops every group of the event, and the books no remaining group
+    // needs. A window still open on one of them is closed so downstream
+    // consumers don't keep it marked.
+    pub fn remove_event(&mut self, event_id: &str) -> Vec<ArbEvent> {
+        let prefix = format!("{}:", event_id);
+        let (removed, kept): (Vec<ArbGroup>, Vec<ArbGroup>) =
+            std::mem::take(&mut self.groups).into_iter().partition(|g| g.id.starts_with(&prefix));
+        self.by_asset.clear();
+        for group in kept {
+            self.add_group(group);
+        }
+        self.books.retain(|asset_id, _| self.by_asset.contains_key(asset_id));
+
+        let mut events = Vec::new();
+        for group in removed {
+            if let Some(mut rec) = self.open.remove(&group.id) {
+                rec.closed_at_ms = Utc::now().timestamp_millis();
+                events.push(ArbEvent::Closed(rec));
+            }
+        }
+        events
+    }
+
+    pub fn groups(&self) -> &[ArbGroup] {
+        &self.groups
+    }
+
+    // Feeds one update in; returns an Open for every group containing the
+    // changed asset that is profitable now, and a Closed for each one that
+    // was open and no longer is.
+    pub fn on_update(&mut self, update: &MarketUpdate) -> Vec<ArbEvent> {
+        let MarketUpdate::Book { asset_id, book } = update else { return vec![] };
+        self.books.insert(asset_id.clone(), book.clone());
+
+        let Some(idxs) = self.by_asset.get(asset_id) else { return vec![] };
+        let mut events = Vec::new();
+        for &i in idxs {
+            let group = &self.groups[i];
+            match self.evaluate(group) {
+                Some(opp) => {
+                    if !self.open.contains_key(&group.id) {
+                        metrics().arb_opportunities.inc();
+                    }
+                    let rec = self.open.entry(group.id.clone()).or_insert_with(|| ArbRecord {
+                        group_id: group.id.clone(),
+                        title: group.title.clone(),
+                        league: group.league.clone(),
+                        kind: group.kind,
+                        opened_at_ms: opp.detected_at.timestamp_millis(),
+                        closed_at_ms: 0,
+                        peak_edge: opp.edge,
+                        peak_size: opp.fill_size,
+                        peak_profit: opp.expected_profit,
+                        updates: 0,
+                    });
+                    rec.peak_edge   = rec.peak_edge.max(opp.edge);
+                    rec.peak_size   = rec.peak_size.max(opp.fill_size);
+                    rec.peak_profit = rec.peak_profit.max(opp.expected_profit);
+                    rec.updates += 1;
+                    events.push(ArbEvent::Open(opp));
+                }
+                None => {
+                    if let Some(mut rec) = self.open.remove(&group.id) {
+                        rec.closed_at_ms = Utc::now().timestamp_millis();
+                        events.push(ArbEvent::Closed(rec));
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    // Prices the group: top-of-book prefilter, then a depth walk for size and
+    // profit. None if any leg has no ask / an out-of-sync book, or the result
+    // doesn't clear the minimum profit.
+    fn evaluate(&self, group: &ArbGroup) -> Option<ArbOpportunity> {
+        let books: Vec<&OrderBook> = group.legs.iter()
+            .map(|leg| self.books.get(&leg.asset_id).filter(|b| b.in_sync))
+            .collect::<Option<_>>()?;
+        let tops: Vec<(f64, f64)> = books.iter().map(|b| b.best_ask()).collect::<Option<_>>()?;
+
+        let cost: f64 = tops.iter().map(|(p, _)| p).sum();
+        let payout = group.payout();
+        if payout - cost <= 1.0 - self.threshold { return None; }
+
+        let fees = self.fees.for_group(&group.id, group.legs.iter().map(|l| l.market.as_str()));
+        let winners = group.winning_legs();
+        let ladders: Vec<Vec<(f64, f64)>> = books.iter().map(|b| b.asks_ascending().collect()).collect();
+        let fill = walk_depth(&ladders, payout, |prices| fees.fees_per_set(prices, winners));
+        if fill.size <= 0.0 { return None; }
+
+        let notional: f64 = fill.leg_cost.iter().sum();
+        let gas = fees.gas(group.legs.len());
+        let expected_profit = fill.size * payout - notional - fill.fees - gas;
+        if expected_profit < self.min_profit { return None; }
+
+        let legs = group.legs.iter().zip(&tops).enumerate()
+            .map(|(i, (leg, (price, size)))| ArbLeg {
+                asset_id: leg.asset_id.clone(),
+                outcome: leg.outcome.clone(),
+                market: leg.market.clone(),
+                price: *price,
+                size: *size,
+                avg_price: fill.leg_cost[i] / fill.size,
+                worst_price: fill.worst_price[i],
+            })
+            .collect();
+
+        Some(ArbOpportunity {
+            group_id: group.id.clone(),
+            title: group.title.clone(),
+            kind: group.kind,
+            legs,
+            cost,
+            payout,
+            edge: payout - cost,
+            fill_size: fill.size,
+            notional,
+            fees: fill.fees,
+            gas,
+            expected_profit,
+            detected_at: Utc::now(),
+        })
+    }
+}
+
+// ── Depth walk ────────────────────────────────────────────────────────────────
+// Steps through every leg's ask ladder in lockstep. At each step the chunk is
+// the smallest size left at the current level of any leg; it's taken only if
+// one full set at those prices, plus per-set fees, still costs less than the
+// set pays out.
+pub struct DepthFill {
+    pub size: f64,             // full sets filled
+    pub leg_cost: Vec<f64>,    // USDC spent per leg
+    pub worst_price: Vec<f64>, // deepest price touched per leg
+    pub fees: f64,
+}
+
+pub fn walk_depth(ladders: &[Vec<(f64, f64)>], payout: f64, fees_per_set: impl Fn(&[f64]) -> f64) -> DepthFill {
+    let n = ladders.len();
+    let mut fill = DepthFill { size: 0.0, leg_cost: vec![0.0; n], worst_price: vec![0.0; n], fees: 0.0 };
+    let mut level = vec![0usize; n];
+    let mut left: Vec<f64> = ladders.iter().map(|l| l.first().map(|(_, s)| *s).unwrap_or(0.0)).collect();
+
+    // Stops as soon as any leg runs out of asks
+    while let Some(prices) = (0..n).map(|i| ladders[i].get(level[i]).map(|(p, _)| *p)).collect::<Option<Vec<f64>>>() {
+        let set_fees = fees_per_set(&prices);
+        if prices.iter().sum::<f64>() + set_fees >= payout { break; }
+
+        let chunk = left.iter().copied().fold(f64::INFINITY, f64::min);
+        fill.size += chunk;
+        fill.fees += set_fees * chunk;
+        for i in 0..n {
+            fill.leg_cost[i] += prices[i] * chunk;
+            fill.worst_price[i] = prices[i];
+            left[i] -= chunk;
+            if left[i] <= 1e-9 {
+                level[i] += 1;
+                left[i] = ladders[i].get(level[i]).map(|(_, s)| *s).unwrap_or(0.0);
+            }
+        }
+    }
+    fill
+}
+
+// ── Background task ───────────────────────────────────────────────────────────
+// Runs the engine over a MarketUpdate stream and republishes its events.
+// Call .subscribe() on the returned sender for a receiver.
+pub fn spawn(updates: broadcast::Receiver<MarketUpdate>, engine: ArbEngine) -> broadcast::Sender<ArbEvent> {
+    spawn_with_groups(updates, engine, mpsc::unbounded_channel().1)
+}
+
+// Changes to the engine's groups while it runs
+#[derive(Debug, Clone)]
+pub enum GroupChange {
+    Add(ArbGroup),
+    RemoveEvent(String), // event id: drops all of its groups
+}
+
+// Same, and applies every change sent on `groups` while running — main.rs
+// adds the groups of each fetch's events as they're subscribed, and removes
+// them once the event drops out of the fetch
+pub fn spawn_with_groups(
+    mut updates: broadcast::Receiver<MarketUpdate>,
+    mut engine: ArbEngine,
+    mut groups: mpsc::UnboundedReceiver<GroupChange>,
+) -> broadcast::Sender<ArbEvent> {
+    let (tx, _) = broadcast::channel(1024);
+    let out = tx.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                Some(change) = groups.recv() => match change {
+                    GroupChange::Add(group) => engine.add_group(group),
+                    GroupChange::RemoveEvent(event_id) => {
+                        for event in engine.remove_event(&event_id) {
+                            let _ = out.send(event);
+                        }
+                    }
+                },
+                msg = updates.recv() => match msg {
+                    Ok(update) => {
+                        for event in engine.on_update(&update) {
+                            let _ = out.send(event);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => warn!(skipped = n, "engine fell behind"),
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+            }
+        }
+    });
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::PRICE_SCALE;
+    use serde_json::json;
+
+    fn near(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn market(question: &str, condition_id: &str, tokens: [&str; 2], outcomes: [&str; 2]) -> Value {
+        json!({
+            "question": question,
+            "conditionId": condition_id,
+            "sportsMarketType": "moneyline",
+            "clobTokenIds": serde_json::to_string(&tokens).unwrap_or_default(),
+            "outcomes": serde_json::to_string(&outcomes).unwrap_or_default(),
+        })
+    }
+
+    fn binary_event() -> Value {
+        json!({
+            "id": "1001",
+            "title": "A vs B",
+            "seriesSlug": "nba",
+            "markets": [
+                market("Who wins?", "0xm", ["111", "222"], ["A", "B"]),
+                { "question": "Total points?", "sportsMarketType": "totals", "clobTokenIds": "[\"333\", \"444\"]", "outcomes": "[\"Over\", \"Under\"]" },
+            ],
+        })
+    }
+
+    // Home / Draw / Away as three Yes/No markets; Yes tokens 1x, No tokens 2x
+    fn three_way_event() -> Value {
+        json!({
+            "id": "2002",
+            "title": "Home vs Away",
+            "markets": [
+                market("Will Home win?", "0xh", ["11", "21"], ["Yes", "No"]),
+                market("Draw?", "0xd", ["12", "22"], ["Yes", "No"]),
+                market("Will Away win?", "0xa", ["13", "23"], ["Yes", "No"]),
+            ],
+        })
+    }
+
+    // In-sync book with only asks, (price, size) from best to worst
+    fn update(asset_id: &str, asks: &[(f64, f64)]) -> MarketUpdate {
+        let book = OrderBook {
+            asks: asks.iter().map(|(p, s)| ((p * PRICE_SCALE).round() as u32, *s)).collect(),
+            in_sync: true,
+            ..OrderBook::default()
+        };
+        MarketUpdate::Book { asset_id: asset_id.to_string(), book }
+    }
+
+    fn engine(event: &Value, threshold: f64) -> ArbEngine {
+        let mut engine = ArbEngine::new(threshold, 0.0, FeeConfig::default());
+        for group in groups_from_event(event) {
+            engine.add_group(group);
+        }
+        engine
+    }
+
+    #[test]
+    fn binary_groups_from_event() {
+        let groups = groups_from_event(&binary_event());
+        assert_eq!(groups.len(), 1, "{:?}", groups);
+        let g = &groups[0];
+        assert_eq!((g.id.as_str(), g.title.as_str(), g.league.as_str(), g.kind), ("1001:Who wins?", "A vs B", "nba", GroupKind::Binary));
+        let legs: Vec<(&str, &str, &str)> = g.legs.iter().map(|l| (l.asset_id.as_str(), l.outcome.as_str(), l.market.as_str())).collect();
+        assert_eq!(legs, [("111", "Who wins? — A", "0xm"), ("222", "Who wins? — B", "0xm")]);
+        assert_eq!(g.payout(), 1.0);
+    }
+
+    #[test]
+    fn engine_prices_groups_from_best_asks() {
+        let mut engine = engine(&binary_event(), 0.98);
+        assert!(engine.on_update(&update("111", &[(0.55, 100.0)])).is_empty(), "one leg has no book yet");
+        assert!(engine.on_update(&update("222", &[(0.60, 100.0)])).is_empty(), "cost 1.15");
+
+        let events = engine.on_update(&update("222", &[(0.40, 100.0)]));
+        let [ArbEvent::Open(opp)] = events.as_slice() else { panic!("{:?}", events) };
+        assert_eq!((opp.group_id.as_str(), opp.kind), ("1001:Who wins?", GroupKind::Binary));
+        assert!(near(opp.cost, 0.95) && near(opp.edge, 0.05) && near(opp.payout, 1.0), "{:?}", opp);
+        assert!(near(opp.fill_size, 100.0) && near(opp.expected_profit, 5.0), "{:?}", opp);
+        assert_eq!(opp.legs.iter().map(|l| l.price).collect::<Vec<_>>(), [0.55, 0.40]);
+
+        // Updates to assets outside every group are ignored
+        assert!(engine.on_update(&update("333", &[(0.01, 100.0)])).is_empty());
+    }
+
+    #[test]
+    fn threshold_and_unsynced_books_filter() {
+        // Edge 0.01 per set doesn't clear 1 − 0.98
+        let mut engine = engine(&binary_event(), 0.98);
+        engine.on_update(&update("111", &[(0.50, 100.0)]));
+        assert!(engine.on_update(&update("222", &[(0.49, 100.0)])).is_empty());
+
+        // A book waiting on a resync is never priced
+        let mut stale = update("222", &[(0.30, 100.0)]);
+        if let MarketUpdate::Book { book, .. } = &mut stale { book.in_sync = false; }
+        assert!(engine.on_update(&stale).is_empty());
+    }
+
+    #[test]
+    fn depth_walk_takes_sets_while_profitable() {
+        let a = vec![(0.45, 50.0), (0.50, 100.0)];
+        let b = vec![(0.50, 30.0), (0.52, 100.0)];
+        // 30 sets at 0.95, 20 at 0.97, then 0.50 + 0.52 no longer pays
+        let fill = walk_depth(&[a.clone(), b.clone()], 1.0, |_| 0.0);
+        assert!(near(fill.size, 50.0), "{}", fill.size);
+        assert!(near(fill.leg_cost[0], 22.5) && near(fill.leg_cost[1], 25.4), "{:?}", fill.leg_cost);
+        assert_eq!(fill.worst_price, [0.45, 0.52]);
+        assert_eq!(fill.fees, 0.0);
+
+        // 4c of fees per set makes the second step a loss
+        let fill = walk_depth(&[a, b], 1.0, |_| 0.04);
+        assert!(near(fill.size, 30.0) && near(fill.fees, 1.2), "{} {}", fill.size, fill.fees);
+    }
+
+    #[test]
+    fn depth_walk_stops_when_a_leg_runs_out() {
+        let fill = walk_depth(&[vec![(0.40, 10.0)], vec![(0.40, 100.0)]], 1.0, |_| 0.0);
+        assert!(near(fill.size, 10.0), "{}", fill.size);
+
+        let fill = walk_depth(&[vec![], vec![(0.40, 100.0)]], 1.0, |_| 0.0);
+        assert_eq!(fill.size, 0.0);
+    }
+
+    #[test]
+    fn profit_is_net_of_fees_and_gas() {
+        let fees: FeeConfig = serde_json::from_value(json!({ "taker_fee_bps": 100, "gas_usdc_per_order": 0.05 })).expect("fees");
+        let mut engine = ArbEngine::new(0.98, 0.0, fees.clone());
+        for group in groups_from_event(&binary_event()) {
+            engine.add_group(group);
+        }
+        engine.on_update(&update("111", &[(0.55, 60.0), (0.56, 40.0)]));
+        let events = engine.on_update(&update("222", &[(0.40, 100.0)]));
+        let [ArbEvent::Open(opp)] = events.as_slice() else { panic!("{:?}", events) };
+        // 60 sets at 0.95 + 0.0095 fees, 40 at 0.96 + 0.0096, two orders of gas
+        assert!(near(opp.fill_size, 100.0) && near(opp.notional, 95.4), "{:?}", opp);
+        assert!(near(opp.fees, 0.954) && near(opp.gas, 0.1), "{:?}", opp);
+        assert!(near(opp.expected_profit, 100.0 - 95.4 - 0.954 - 0.1), "{:?}", opp);
+        assert!(near(opp.legs[0].avg_price, 0.554) && opp.legs[0].worst_price == 0.56, "{:?}", opp.legs[0]);
+
+        // The same window below the minimum profit is not reported
+        let mut engine = ArbEngine::new(0.98, 4.0, fees);
+        for group in groups_from_event(&binary_event()) {
+            engine.add_group(group);
+        }
+        engine.on_update(&update("111", &[(0.55, 60.0), (0.56, 40.0)]));
+        assert!(engine.on_update(&update("222", &[(0.40, 100.0)])).is_empty());
+    }
+
+    #[test]
+    fn linked_markets_get_all_yes_and_all_no_groups() {
+        let groups = groups_from_event(&three_way_event());
+        let kinds: Vec<(&str, GroupKind)> = groups.iter().map(|g| (g.id.as_str(), g.kind)).collect();
+        assert_eq!(kinds, [
+            ("2002:Will Home win?", GroupKind::Binary),
+            ("2002:Draw?", GroupKind::Binary),
+            ("2002:Will Away win?", GroupKind::Binary),
+            ("2002:3-way", GroupKind::AllYes),
+            ("2002:3-way-no", GroupKind::AllNo),
+        ]);
+
+        let yes = &groups[3];
+        assert_eq!(yes.legs.iter().map(|l| l.asset_id.as_str()).collect::<Vec<_>>(), ["11", "12", "13"]);
+        assert_eq!((yes.winning_legs(), yes.payout()), (1, 1.0));
+
+        // Only one outcome happens, so every other No pays
+        let no = &groups[4];
+        assert_eq!(no.legs.iter().map(|l| (l.asset_id.as_str(), l.market.as_str())).collect::<Vec<_>>(), [("21", "0xh"), ("22", "0xd"), ("23", "0xa")]);
+        assert_eq!((no.winning_legs(), no.payout()), (2, 2.0));
+    }
+
+    #[test]
+    fn all_no_priced_against_n_minus_one_payout() {
+        let fees: FeeConfig = serde_json::from_value(json!({ "winner_fee_pct": 5.0 })).expect("fees");
+        let mut engine = ArbEngine::new(0.98, 0.0, fees);
+        for group in groups_from_event(&three_way_event()) {
+            engine.add_group(group);
+        }
+        // Yes on all costs 1.25 and every binary at least 1.02: only No on all is mispriced
+        let mut events = Vec::new();
+        for (asset, ask) in [("11", 0.45), ("12", 0.40), ("13", 0.40), ("21", 0.60), ("22", 0.75), ("23", 0.72)] {
+            events.extend(engine.on_update(&update(asset, &[(ask, 100.0)])));
+        }
+        assert!(events.is_empty(), "{:?}", events);
+
+        let events = engine.on_update(&update("22", &[(0.62, 100.0)]));
+        let [ArbEvent::Open(opp)] = events.as_slice() else { panic!("{:?}", events) };
+        assert_eq!((opp.group_id.as_str(), opp.kind), ("2002:3-way-no", GroupKind::AllNo));
+        assert!(near(opp.cost, 1.94) && near(opp.payout, 2.0) && near(opp.edge, 0.06), "{:?}", opp);
+        // Winner fee on the two biggest No profits (0.40 + 0.38), not just one
+        assert!(near(opp.fees, 100.0 * 0.039), "{:?}", opp);
+        assert!(near(opp.expected_profit, 200.0 - 194.0 - 3.9), "{:?}", opp);
+        assert!(opp.explain().contains("2 of the No legs pay $1"), "{}", opp.explain());
+    }
+
+    #[test]
+    fn lifecycle_from_open_to_close() {
+        let mut engine = engine(&binary_event(), 0.98);
+        engine.on_update(&update("111", &[(0.55, 100.0)]));
+        let opened = engine.on_update(&update("222", &[(0.40, 100.0)]));
+        assert!(matches!(opened.as_slice(), [ArbEvent::Open(_)]), "{:?}", opened);
+        // Re-priced: smaller edge but more size, still the same window
+        let repriced = engine.on_update(&update("222", &[(0.41, 300.0)]));
+        assert!(matches!(repriced.as_slice(), [ArbEvent::Open(_)]), "{:?}", repriced);
+
+        let events = engine.on_update(&update("111", &[(0.60, 100.0)]));
+        let [ArbEvent::Closed(rec)] = events.as_slice() else { panic!("{:?}", events) };
+        assert_eq!((rec.group_id.as_str(), rec.league.as_str(), rec.updates), ("1001:Who wins?", "nba", 2));
+        assert!(near(rec.peak_edge, 0.05) && near(rec.peak_size, 100.0) && near(rec.peak_profit, 5.0), "{:?}", rec);
+        assert!(rec.closed_at_ms >= rec.opened_at_ms);
+
+        // Closed is sent once; the next window starts a fresh record
+        assert!(engine.on_update(&update("111", &[(0.65, 100.0)])).is_empty());
+        engine.on_update(&update("111", &[(0.50, 100.0)]));
+        let events = engine.on_update(&update("111", &[(0.70, 100.0)]));
+        assert!(matches!(events.as_slice(), [ArbEvent::Closed(r)] if r.updates == 1), "{:?}", events);
+    }
+
+    #[test]
+    fn removing_an_event_drops_its_groups_and_closes_open_windows() {
+        let mut engine = engine(&binary_event(), 0.98);
+        for group in groups_from_event(&three_way_event()) {
+            engine.add_group(group);
+        }
+        engine.on_update(&update("111", &[(0.55, 100.0)]));
+        let opened = engine.on_update(&update("222", &[(0.40, 100.0)]));
+        assert!(matches!(opened.as_slice(), [ArbEvent::Open(_)]), "{:?}", opened);
+
+        let events = engine.remove_event("1001");
+        let [ArbEvent::Closed(rec)] = events.as_slice() else { panic!("{:?}", events) };
+        assert_eq!(rec.group_id, "1001:Who wins?");
+        assert_eq!(engine.groups().len(), 5);
+        assert!(engine.groups().iter().all(|g| g.id.starts_with("2002:")));
+
+        // Its assets are no longer priced; the other event's still are
+        assert!(engine.on_update(&update("222", &[(0.30, 100.0)])).is_empty());
+        for (asset, ask) in [("11", 0.30), ("12", 0.30)] {
+            engine.on_update(&update(asset, &[(ask, 100.0)]));
+        }
+        let events = engine.on_update(&update("13", &[(0.30, 100.0)]));
+        assert!(matches!(events.as_slice(), [ArbEvent::Open(o)] if o.group_id == "2002:3-way"), "{:?}", events);
+
+        assert!(engine.remove_event("1001").is_empty(), "already gone");
+    }
+}
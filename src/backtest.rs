@@ -0,0 +1,364 @@
+// backtest.rs — arb strategies replayed over recorded sessions
+//
+// Runs a grid of strategies (threshold × fee model × latency) over one or more
+// WebSocket recordings (see recording.rs). Every recording is stepped through
+// a ws::Player one record at a time, and each strategy sees the resulting
+// updates with the record's receive time, so latency is simulated in
+// recorded time and two runs over the same files give the same numbers.
+//
+// Per strategy:
+//   - its own ArbEngine with the strategy's threshold and fees
+//   - a PaperTrader (see paper.rs) that fills the first Open of each window
+//     with FOK buys against the books as they were `latency` after detection
+//   - market_resolved records settle positions, so realized P&L is known for
+//     markets that resolved inside the recordings
+//
+// Output is one OpportunityResult per strategy and window, and one
+// StrategySummary per strategy.
+
+use crate::arb::{groups_from_event, ArbEngine, ArbEvent, ArbGroup, ArbOpportunity};
+use crate::fees::FeeConfig;
+use crate::fetch::Config;
+use crate::paper::{LegResult, PaperSettings, PaperTrader};
+use crate::recording::{read_meta, read_recording};
+use crate::stats::Distribution;
+use crate::ws::{MarketUpdate, Player, ReplayStats};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct Strategy {
+    pub name: String,
+    pub threshold: f64,
+    pub min_profit: f64,
+    pub fee_model: String,
+    pub fees: FeeConfig,
+    pub latency_ms: u64,
+    pub max_notional: f64,
+}
+
+impl Strategy {
+    // Every combination of threshold, fee model and latency. Empty lists fall
+    // back to the live settings: arb_threshold, "fees", paper_latency_ms.
+    pub fn grid(config: &Config, thresholds: &[f64], latencies_ms: &[u64]) -> Vec<Strategy> {
+        let thresholds = if thresholds.is_empty() { vec![config.arb_threshold] } else { thresholds.to_vec() };
+        let latencies = if latencies_ms.is_empty() { vec![config.paper_latency_ms] } else { latencies_ms.to_vec() };
+        let fee_models: Vec<(String, FeeConfig)> = if config.backtest_fee_models.is_empty() {
+            vec![("config".to_string(), config.fees.clone())]
+        } else {
+            config.backtest_fee_models.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+        };
+
+        let mut grid = Vec::new();
+        for &threshold in &thresholds {
+            for (fee_model, fees) in &fee_models {
+                for &latency_ms in &latencies {
+                    grid.push(Strategy {
+                        name: format!("t={} fees={} lat={}ms", threshold, fee_model, latency_ms),
+                        threshold,
+                        min_profit: config.arb_min_profit_usdc,
+                        fee_model: fee_model.clone(),
+                        fees: fees.clone(),
+                        latency_ms,
+                        max_notional: config.paper_max_notional_usdc,
+                    });
+                }
+            }
+        }
+        grid
+    }
+}
+
+// ================================================================================
+// RESULTS
+// ================================================================================
+// One arb window as a strategy saw it: detection, the simulated trade, close
+#[derive(Debug, Clone, Serialize)]
+pub struct OpportunityResult {
+    pub strategy: String,
+    pub group_id: String,
+    pub title: String,
+    pub kind: String,
+    pub opened_ms: i64,           // recorded time of the first Open
+    pub closed_ms: Option<i64>,   // None = still open when the recording ended
+    pub window_secs: f64,
+    pub cost: f64,                // at detection
+    pub edge: f64,
+    pub fill_size: f64,
+    pub expected_profit: f64,
+    pub status: &'static str,     // filled | partial | missed | too_small
+    pub sets: f64,
+    pub legs_filled: usize,
+    pub legs_killed: usize,
+    pub spent: f64,               // shares + taker fees
+    pub locked_profit: Option<f64>, // fully filled: payout − spent − gas − worst-case winner fee
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategySummary {
+    pub strategy: String,
+    pub threshold: f64,
+    pub fee_model: String,
+    pub latency_ms: u64,
+    pub windows: usize,
+    pub median_window_secs: f64,
+    pub trades: usize,
+    pub filled: usize,
+    pub partial: usize,
+    pub missed: usize,
+    pub fill_rate: f64,       // filled / trades
+    pub expected_profit: f64, // engine estimates at detection, every window
+    pub locked_profit: f64,   // fully filled trades
+    pub realized_pnl: f64,    // positions settled by market_resolved
+    pub open_cost: f64,       // USDC still in unresolved positions
+}
+
+impl StrategySummary {
+    fn line(&self) -> String {
+        format!(
+            "{:<36} windows={:<4} p50={:>6.1}s | trades={:<4} filled={:<4} partial={:<3} missed={:<3} fill={:>5.1}% | expected=${:.2} locked=${:.2} realized=${:.2} open=${:.2}",
+            self.strategy, self.windows, self.median_window_secs, self.trades, self.filled, self.partial, self.missed,
+            self.fill_rate * 100.0, self.expected_profit, self.locked_profit, self.realized_pnl, self.open_cost
+        )
+    }
+}
+
+pub struct BacktestReport {
+    pub results: Vec<OpportunityResult>,
+    pub summaries: Vec<StrategySummary>,
+    pub records: u64,        // totals over every file
+    pub messages: u64,
+    pub recorded_secs: f64,
+}
+
+impl BacktestReport {
+    pub fn render(&self) -> String {
+        let mut out = vec![format!(
+            "{} record(s), {} message(s), {:.1}s of recorded data\n",
+            self.records, self.messages, self.recorded_secs
+        )];
+        out.extend(self.summaries.iter().map(StrategySummary::line));
+        out.join("\n")
+    }
+
+    pub fn results_csv(&self) -> Result<Vec<u8>, String> {
+        to_csv(&self.results)
+    }
+
+    pub fn summary_csv(&self) -> Result<Vec<u8>, String> {
+        to_csv(&self.summaries)
+    }
+}
+
+fn to_csv<T: Serialize>(rows: &[T]) -> Result<Vec<u8>, String> {
+    let mut w = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        w.serialize(row).map_err(|e| e.to_string())?;
+    }
+    w.into_inner().map_err(|e| e.to_string())
+}
+
+// ================================================================================
+// ONE STRATEGY
+// ================================================================================
+struct Run {
+    strategy: Strategy,
+    engine: ArbEngine,
+    trader: PaperTrader,
+    pending: VecDeque<(i64, usize, ArbOpportunity)>, // (due ms, result index, opportunity)
+    open: HashMap<String, usize>,                   // group_id → result index of its window
+    results: Vec<OpportunityResult>,
+}
+
+impl Run {
+    fn new(strategy: Strategy) -> Run {
+        let settings = PaperSettings {
+            latency: Duration::from_millis(strategy.latency_ms),
+            max_notional: strategy.max_notional,
+            summary_path: String::new(), // never written
+        };
+        let trader = PaperTrader::new(settings, strategy.fees.clone());
+        Run {
+            engine: ArbEngine::new(strategy.threshold, strategy.min_profit, strategy.fees.clone()),
+            trader,
+            pending: VecDeque::new(),
+            open: HashMap::new(),
+            results: Vec::new(),
+            strategy,
+        }
+    }
+
+    // Fresh engine for a new recording: books from the last session are gone
+    fn start_session(&mut self, groups: &[ArbGroup]) {
+        let s = &self.strategy;
+        self.engine = ArbEngine::new(s.threshold, s.min_profit, s.fees.clone());
+        for group in groups {
+            self.engine.add_group(group.clone());
+        }
+    }
+
+    fn on_update(&mut self, t: i64, update: &MarketUpdate) {
+        // Orders due by now hit the books as they were before this update
+        while self.pending.front().is_some_and(|(due, _, _)| *due <= t) {
+            let (_, idx, opp) = self.pending.pop_front().unwrap();
+            self.execute(idx, &opp);
+        }
+        self.trader.on_update(update);
+
+        for event in self.engine.on_update(update) {
+            match event {
+                ArbEvent::Open(opp) => {
+                    if self.open.contains_key(&opp.group_id) { continue; }
+                    let idx = self.results.len();
+                    self.results.push(OpportunityResult {
+                        strategy: self.strategy.name.clone(),
+                        group_id: opp.group_id.clone(),
+                        title: opp.title.clone(),
+                        kind: opp.kind.strategy().to_string(),
+                        opened_ms: t,
+                        closed_ms: None,
+                        window_secs: 0.0,
+                        cost: opp.cost,
+                        edge: opp.edge,
+                        fill_size: opp.fill_size,
+                        expected_profit: opp.expected_profit,
+                        status: "missed",
+                        sets: 0.0,
+                        legs_filled: 0,
+                        legs_killed: 0,
+                        spent: 0.0,
+                        locked_profit: None,
+                    });
+                    self.open.insert(opp.group_id.clone(), idx);
+                    self.pending.push_back((t + self.strategy.latency_ms as i64, idx, opp));
+                }
+                ArbEvent::Closed(rec) => {
+                    if let Some(idx) = self.open.remove(&rec.group_id) {
+                        let r = &mut self.results[idx];
+                        r.closed_ms = Some(t);
+                        r.window_secs = (t - r.opened_ms) as f64 / 1000.0;
+                    }
+                }
+            }
+        }
+    }
+
+    fn execute(&mut self, idx: usize, opp: &ArbOpportunity) {
+        let result = &mut self.results[idx];
+        let Some(trade) = self.trader.execute(opp) else {
+            result.status = "too_small";
+            return;
+        };
+        result.sets = trade.sets;
+        result.spent = trade.spent();
+        result.legs_filled = trade.legs.iter().filter(|l| matches!(l, LegResult::Filled { .. })).count();
+        result.legs_killed = trade.legs.len() - result.legs_filled;
+        result.status = match result.legs_filled {
+            0 => "missed",
+            n if n == trade.legs.len() => "filled",
+            _ => "partial",
+        };
+        if trade.fully_filled() {
+            let model = self.strategy.fees.for_group(&opp.group_id, opp.legs.iter().map(|l| l.market.as_str()));
+            let prices: Vec<f64> = trade.legs.iter()
+                .filter_map(|l| match l { LegResult::Filled { avg_price, .. } => Some(*avg_price), _ => None })
+                .collect();
+            let winner_fee = model.winner_fee_per_set(&prices, opp.payout as usize) * trade.sets;
+            result.locked_profit = Some(trade.sets * opp.payout - result.spent - model.gas(prices.len()) - winner_fee);
+        }
+    }
+
+    // End of a recording: trades still waiting go out against the final
+    // books, windows still open are cut off at the last record
+    fn end_session(&mut self, last_ms: i64) {
+        while let Some((_, idx, opp)) = self.pending.pop_front() {
+            self.execute(idx, &opp);
+        }
+        for (_, idx) in self.open.drain() {
+            let r = &mut self.results[idx];
+            r.window_secs = (last_ms - r.opened_ms).max(0) as f64 / 1000.0;
+        }
+    }
+
+    fn summary(&self) -> StrategySummary {
+        let traded: Vec<&OpportunityResult> = self.results.iter().filter(|r| r.status != "too_small").collect();
+        let count = |status: &str| traded.iter().filter(|r| r.status == status).count();
+        let filled = count("filled");
+        let paper = self.trader.summary();
+        StrategySummary {
+            strategy: self.strategy.name.clone(),
+            threshold: self.strategy.threshold,
+            fee_model: self.strategy.fee_model.clone(),
+            latency_ms: self.strategy.latency_ms,
+            windows: self.results.len(),
+            median_window_secs: Distribution::from_values(self.results.iter().map(|r| r.window_secs).collect()).p50,
+            trades: traded.len(),
+            filled,
+            partial: count("partial"),
+            missed: count("missed"),
+            fill_rate: if traded.is_empty() { 0.0 } else { filled as f64 / traded.len() as f64 },
+            expected_profit: self.results.iter().map(|r| r.expected_profit).sum(),
+            locked_profit: self.results.iter().filter_map(|r| r.locked_profit).sum(),
+            realized_pnl: paper.realized_pnl,
+            open_cost: paper.open_cost,
+        }
+    }
+}
+
+// ================================================================================
+// BACKTEST
+// ================================================================================
+pub struct Backtest {
+    runs: Vec<Run>,
+    records: u64,
+    messages: u64,
+    recorded_secs: f64,
+}
+
+impl Backtest {
+    pub fn new(strategies: Vec<Strategy>) -> Backtest {
+        Backtest { runs: strategies.into_iter().map(Run::new).collect(), records: 0, messages: 0, recorded_secs: 0.0 }
+    }
+
+    // Replays one recording through every strategy. Arb groups come from the
+    // Gamma events in its meta records.
+    pub fn run_file(&mut self, path: &str) -> std::io::Result<ReplayStats> {
+        let groups: Vec<ArbGroup> = read_meta(path)?.iter().flat_map(groups_from_event).collect();
+        for run in &mut self.runs {
+            run.start_session(&groups);
+        }
+
+        let mut player = Player::new();
+        let handle = player.handle();
+        let mut updates = handle.updates();
+        for record in read_recording(path)? {
+            let t = record.t;
+            player.feed(record);
+            while let Ok(update) = updates.try_recv() {
+                for run in &mut self.runs {
+                    run.on_update(t, &update);
+                }
+            }
+        }
+
+        let stats = player.stats().clone();
+        for run in &mut self.runs {
+            run.end_session(stats.last_ms);
+        }
+        self.records += stats.records;
+        self.messages += stats.messages;
+        self.recorded_secs += stats.recorded_secs();
+        Ok(stats)
+    }
+
+    pub fn finish(self) -> BacktestReport {
+        BacktestReport {
+            summaries: self.runs.iter().map(Run::summary).collect(),
+            results: self.runs.into_iter().flat_map(|r| r.results).collect(),
+            records: self.records,
+            messages: self.messages,
+            recorded_secs: self.recorded_secs,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// src/bin/arb_report.rs — prints arb window statistics from the history log
+//
+// Run with: cargo run --bin arb_report [path/to/arb_history.jsonl]
+// Defaults to config.arb_log_path.
+
+use latex_telegram_bot::fetch::Config;
+use latex_telegram_bot::stats::{load_records, StatsReport};
+
+fn main() {
+    let path = std::env::args().nth(1).unwrap_or_else(|| Config::load("config.json").arb_log_path);
+
+    let records = load_records(&path);
+    println!("Loaded {} closed opportunities from {}\n", records.len(), path);
+    if records.is_empty() { return; }
+
+    println!("{}", StatsReport::build(&records).render());
+}
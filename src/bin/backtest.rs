@@ -0,0 +1,92 @@
+// src/bin/backtest.rs — runs arb strategies over recorded WebSocket sessions
+//
+// Run with: cargo run --bin backtest [recording.ndjson.gz | dir]... [--threshold 0.97,0.98]
+//           [--latency 0,250] [--out results.csv]
+//
+// Defaults: every recording in config.ws_record_dir (or ./recordings), the
+// strategy grid from config.backtest_* and config.backtest_results_path.
+// Prints one summary line per strategy and writes the per-opportunity results
+// plus a summary CSV.
+
+use latex_telegram_bot::backtest::{Backtest, Strategy};
+use latex_telegram_bot::fetch::Config;
+use latex_telegram_bot::logging;
+use latex_telegram_bot::output::write_atomic;
+use std::path::{Path, PathBuf};
+
+fn parse_list<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Vec<T> {
+    let value = value.unwrap_or_default();
+    value.split(',').filter(|s| !s.trim().is_empty()).map(|s| s.trim().parse().unwrap_or_else(|_| {
+        eprintln!("Bad value for {}: {}", flag, s);
+        std::process::exit(2);
+    })).collect()
+}
+
+// Files as given; directories expand to their recordings, oldest first
+fn recordings(inputs: &[String]) -> Vec<String> {
+    let mut files = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut found: Vec<PathBuf> = std::fs::read_dir(path).into_iter().flatten()
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.to_string_lossy().ends_with(".ndjson.gz"))
+                .collect();
+            found.sort();
+            files.extend(found.into_iter().map(|p| p.display().to_string()));
+        } else {
+            files.push(input.clone());
+        }
+    }
+    files
+}
+
+fn main() {
+    let config = Config::load("config.json");
+    logging::init_from_config(&config);
+    let mut inputs = Vec::new();
+    let mut thresholds = config.backtest_thresholds.clone();
+    let mut latencies = config.backtest_latencies_ms.clone();
+    let mut out = config.backtest_results_path.clone();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threshold" => thresholds = parse_list("--threshold", args.next()),
+            "--latency" => latencies = parse_list("--latency", args.next()),
+            "--out" => out = args.next().unwrap_or(out),
+            _ => inputs.push(arg),
+        }
+    }
+    if inputs.is_empty() {
+        inputs.push(if config.ws_record_dir.is_empty() { "recordings".to_string() } else { config.ws_record_dir.clone() });
+    }
+    let files = recordings(&inputs);
+    if files.is_empty() {
+        eprintln!("No recordings found in {}", inputs.join(", "));
+        std::process::exit(2);
+    }
+
+    let strategies = Strategy::grid(&config, &thresholds, &latencies);
+    println!("Backtesting {} strateg{} over {} recording(s)\n", strategies.len(), if strategies.len() == 1 { "y" } else { "ies" }, files.len());
+    let mut backtest = Backtest::new(strategies);
+    for file in &files {
+        match backtest.run_file(file) {
+            Ok(stats) => println!("  {} — {} record(s), {:.1}s", file, stats.records, stats.recorded_secs()),
+            Err(e) => eprintln!("  {} — skipped: {}", file, e),
+        }
+    }
+
+    let report = backtest.finish();
+    println!("\n{}\n", report.render());
+
+    let results = Path::new(&out);
+    let stem = results.file_stem().and_then(|s| s.to_str()).unwrap_or("backtest_results");
+    let summary = results.with_file_name(format!("{}_summary.csv", stem));
+    for (path, bytes) in [(results.to_path_buf(), report.results_csv()), (summary, report.summary_csv())] {
+        match bytes.and_then(|b| write_atomic(&path, &b).map_err(|e| e.to_string())) {
+            Ok(()) => println!("Wrote {}", path.display()),
+            Err(e) => eprintln!("Failed to write {}: {}", path.display(), e),
+        }
+    }
+}
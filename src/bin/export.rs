@@ -0,0 +1,38 @@
+// src/bin/export.rs — flatten a saved fetch report to CSV and / or Parquet
+//
+// Run with: cargo run --bin export -- [report.json] [csv] [parquet]
+//
+// Defaults to the "latest" report in config.output_dir and both formats.
+// Output goes next to the report, with the same name and the format's extension.
+
+use latex_telegram_bot::export::{export_report, ExportFormat};
+use latex_telegram_bot::fetch::Config;
+use serde_json::Value;
+use std::path::PathBuf;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut formats: Vec<ExportFormat> = args.iter().filter_map(|a| ExportFormat::parse(a)).collect();
+    if formats.is_empty() {
+        formats = vec![ExportFormat::Csv, ExportFormat::Parquet];
+    }
+    let path = match args.iter().find(|a| ExportFormat::parse(a).is_none()) {
+        Some(p) => PathBuf::from(p),
+        None => {
+            let config = Config::load("config.json");
+            // Resolve the link so exports land next to the real snapshot
+            let latest = PathBuf::from(&config.output_dir).join(&config.output_latest_name);
+            std::fs::canonicalize(&latest).unwrap_or(latest)
+        }
+    };
+
+    let report: Vec<Value> = match std::fs::read_to_string(&path).map(|s| serde_json::from_str(&s)) {
+        Ok(Ok(r)) => r,
+        Ok(Err(e)) => { eprintln!("{} is not a fetch report: {}", path.display(), e); std::process::exit(1); }
+        Err(e) => { eprintln!("Could not read {}: {}", path.display(), e); std::process::exit(1); }
+    };
+    match export_report(&report, &formats, &path) {
+        Ok(paths) => for p in paths { println!("Wrote {}", p); },
+        Err(e) => { eprintln!("Export failed: {}", e); std::process::exit(1); }
+    }
+}
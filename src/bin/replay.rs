@@ -0,0 +1,90 @@
+// src/bin/replay.rs — replays a recorded WebSocket session through the arb engine
+//
+// Run with: cargo run --bin replay <recordings/ws_….ndjson.gz> [speed]
+// speed: 1 = as recorded, 10 = ten times faster, 0 (default) = as fast as possible.
+//
+// Arb groups are rebuilt from the Gamma events stored in the recording's meta
+// records (the bot and test_ws write them); thresholds and fees come from config.json.
+
+use latex_telegram_bot::{arb, logging, recording, ws};
+use latex_telegram_bot::fetch::Config;
+
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("Usage: replay <recording.ndjson.gz> [speed]");
+        std::process::exit(2);
+    };
+    let speed: f64 = args.next().and_then(|s| s.parse().ok()).unwrap_or(0.0);
+    let config = Config::load("config.json");
+    logging::init_from_config(&config);
+
+    let events = match recording::read_meta(&path) {
+        Ok(events) => events,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let mut engine = arb::ArbEngine::from_config(&config);
+    for event in &events {
+        for group in arb::groups_from_event(event) {
+            engine.add_group(group);
+        }
+    }
+    println!("Replaying {} at {} — {} arb group(s) from {} event(s)\n",
+        path,
+        if speed > 0.0 { format!("{}x", speed) } else { "full speed".to_string() },
+        engine.groups().len(),
+        events.len());
+
+    let replay = match ws::Replay::open(&path, speed) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Could not open {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let handle = replay.handle();
+    let arb_events = arb::spawn(handle.updates(), engine);
+    let mut opportunities = arb_events.subscribe();
+    drop(arb_events);
+    let printer = tokio::spawn(async move {
+        let (mut opened, mut closed) = (0, 0);
+        loop {
+            match opportunities.recv().await {
+                Ok(arb::ArbEvent::Open(opp)) => {
+                    opened += 1;
+                    println!("  ← {}\n    {}", opp.summary(), opp.explain());
+                }
+                Ok(arb::ArbEvent::Closed(rec)) => {
+                    closed += 1;
+                    println!("  ← ARB closed after {:.1}s — {} [{}]", rec.duration_secs(), rec.title, rec.kind.strategy());
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(_) => return (opened, closed),
+            }
+        }
+    });
+
+    let stats = match replay.start().await {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("Replay failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let by_type = handle.stats().by_type.lock().unwrap().clone();
+    drop(handle); // closes the update stream, which ends the arb engine and printer
+    let (opened, closed) = printer.await.unwrap_or_default();
+
+    println!("\nReplayed {} record(s) covering {:.1}s — {} message(s), {} REST resync(s)",
+        stats.records, stats.recorded_secs(), stats.messages, stats.resyncs);
+    let mut by_type: Vec<_> = by_type.into_iter().collect();
+    by_type.sort();
+    for (kind, n) in by_type {
+        println!("  {:<18} {}", kind, n);
+    }
+    println!("Arb opportunities: {} opened, {} closed", opened, closed);
+}
@@ -1,14 +1,18 @@
 // src/bin/test_ws.rs — tests ws.rs against a live token from a real active market
 //
 // Run with: cargo run --bin test_ws
+//
+// A smoke test: streams one random event and prints books and arbs. Alerts,
+// history, paper trading and execution run in the main bot (main.rs).
 
-use latex_telegram_bot::{fetch, ws};
+use latex_telegram_bot::{arb, fetch, logging, ws};
 use latex_telegram_bot::fetch::print_event;
 use serde_json::Value;
 
 #[tokio::main]
 async fn main() {
     let config  = fetch::Config::load("config.json");
+    logging::init_from_config(&config);
     let client  = fetch::build_client(&config);
     let tag_ids: Vec<&str> = config.tag_ids.iter().map(|s| s.as_str()).collect();
 
@@ -102,5 +106,28 @@ async fn main() {
         .collect();
 
     println!("Monitoring {} token(s) — streaming live prices (Ctrl+C to stop):\n", tokens.len());
-    ws::run(tokens).await;
+    let handle = ws::spawn(client, tokens, ws::WsSettings::from_config(&config));
+    handle.record_meta(event.clone()); // lets `replay` rebuild the arb groups
+    ws::spawn_printer(&handle);
+
+    // ── Arb engine over the same update stream, printed only ──────────────────
+    let mut engine = arb::ArbEngine::from_config(&config);
+    for group in arb::groups_from_event(event) {
+        engine.add_group(group);
+    }
+    let mut opportunities = arb::spawn(handle.updates(), engine).subscribe();
+    tokio::spawn(async move {
+        loop {
+            match opportunities.recv().await {
+                Ok(arb::ArbEvent::Open(opp)) => println!("  ← {}\n    {}", opp.summary(), opp.explain()),
+                Ok(arb::ArbEvent::Closed(rec)) => {
+                    println!("  ← ARB closed after {:.1}s — {} [{}]", rec.duration_secs(), rec.title, rec.kind.strategy());
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(_) => return,
+            }
+        }
+    });
+
+    tokio::signal::ctrl_c().await.unwrap();
 }
@@ -0,0 +1,317 @@
+// book.rs — local in-memory order books built from the CLOB WebSocket feed
+//
+// One OrderBook per asset (token_id). Seeded from the full `book` snapshot the
+// server sends on subscribe (or from REST /book on a resync), then kept up to
+// date by applying `price_change` deltas level by level.
+//
+// Every price_change entry also carries the exchange's own best_bid/best_ask
+// after the change. If our book disagrees with those, is crossed, or receives
+// a delta before it has ever seen a snapshot, it's marked out of sync and the
+// caller is told to fetch a fresh snapshot. Deltas older than the book are
+// dropped by timestamp. The exchange's hash is kept for reference only — it
+// covers fields the stream doesn't carry, so it can't be recomputed locally.
+//
+// Prices are stored as integer units of 1/10_000 so they can be BTreeMap
+// keys — Polymarket's finest tick size is 0.0001.
+
+use crate::messages::{parse_f64, parse_ts, BookMsg, Level, PriceChangeMsg};
+use std::collections::{BTreeMap, HashMap};
+use tracing::warn;
+
+// 1.0 in price units
+pub const PRICE_SCALE: f64 = 10_000.0;
+
+pub fn price_to_units(price: &str) -> Option<u32> {
+    let p: f64 = price.parse().ok()?;
+    if !(0.0..=1.0).contains(&p) { return None; }
+    Some((p * PRICE_SCALE).round() as u32)
+}
+
+pub fn units_to_price(units: u32) -> f64 {
+    units as f64 / PRICE_SCALE
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Bid, // BUY orders
+    Ask, // SELL orders
+}
+
+impl Side {
+    pub fn parse(s: &str) -> Option<Side> {
+        match s {
+            "BUY"  => Some(Side::Bid),
+            "SELL" => Some(Side::Ask),
+            _      => None,
+        }
+    }
+}
+
+// ================================================================================
+// ORDER BOOK
+// ================================================================================
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    pub bids: BTreeMap<u32, f64>, // price units → size
+    pub asks: BTreeMap<u32, f64>,
+    pub timestamp: u64,           // ms, from the last snapshot or delta applied
+    pub hash: Option<String>,     // exchange book hash after the last update (not verified)
+    pub in_sync: bool,            // false until seeded, and after any inconsistency
+    pub tick_size: Option<f64>,   // from REST /book or tick_size_change
+}
+
+impl OrderBook {
+    // Replaces the whole book from a `book` WS message or REST /book body.
+    // Snapshots older than what we already have are ignored.
+    pub fn apply_snapshot(&mut self, snap: &BookMsg) -> bool {
+        let ts = parse_ts(&snap.timestamp);
+        if self.in_sync && ts < self.timestamp { return false; }
+
+        self.bids = parse_levels(&snap.bids);
+        self.asks = parse_levels(&snap.asks);
+        self.timestamp = ts;
+        self.hash = snap.hash.clone();
+        if let Some(tick) = snap.tick_size.as_deref().and_then(parse_f64) {
+            self.tick_size = Some(tick);
+        }
+        self.in_sync = !self.is_crossed();
+        true
+    }
+
+    // Sets one price level to an absolute size (size 0 removes the level).
+    // Deltas older than the current book are dropped — they're already
+    // reflected in a newer snapshot.
+    pub fn apply_level(&mut self, side: Side, price: u32, size: f64, ts: u64, hash: Option<&str>) {
+        if ts < self.timestamp { return; }
+
+        let levels = match side {
+            Side::Bid => &mut self.bids,
+            Side::Ask => &mut self.asks,
+        };
+        if size <= 0.0 {
+            levels.remove(&price);
+        } else {
+            levels.insert(price, size);
+        }
+        self.timestamp = ts;
+        if let Some(h) = hash { self.hash = Some(h.to_string()); }
+    }
+
+    // Highest bid as (price, size)
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bids.iter().next_back().map(|(p, s)| (units_to_price(*p), *s))
+    }
+
+    // Lowest ask as (price, size)
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.asks.iter().next().map(|(p, s)| (units_to_price(*p), *s))
+    }
+
+    // Ask levels from best (lowest) to worst
+    pub fn asks_ascending(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.asks.iter().map(|(p, s)| (units_to_price(*p), *s))
+    }
+
+    // Bid levels from best (highest) to worst
+    pub fn bids_descending(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.bids.iter().rev().map(|(p, s)| (units_to_price(*p), *s))
+    }
+
+    pub fn is_crossed(&self) -> bool {
+        match (self.bids.keys().next_back(), self.asks.keys().next()) {
+            (Some(bid), Some(ask)) => bid >= ask,
+            _ => false,
+        }
+    }
+
+    // Compares our top of book with what the exchange says it should be.
+    // A missing expectation (field absent) is not treated as a mismatch;
+    // "0" / "1" mean the side is empty.
+    fn matches_top(&self, expected_bid: Option<u32>, expected_ask: Option<u32>) -> bool {
+        let our_bid = self.bids.keys().next_back().copied().unwrap_or(0);
+        let our_ask = self.asks.keys().next().copied().unwrap_or(PRICE_SCALE as u32);
+        expected_bid.is_none_or(|b| b == our_bid) && expected_ask.is_none_or(|a| a == our_ask)
+    }
+}
+
+// ================================================================================
+// BOOK SET
+// All books for one connection, keyed by asset_id.
+// ================================================================================
+#[derive(Debug, Default)]
+pub struct Books {
+    books: HashMap<String, OrderBook>,
+}
+
+impl Books {
+    pub fn get(&self, asset_id: &str) -> Option<&OrderBook> {
+        self.books.get(asset_id)
+    }
+
+    pub fn remove(&mut self, asset_id: &str) {
+        self.books.remove(asset_id);
+    }
+
+    // Records a tick_size_change against the asset (creating the entry if needed)
+    pub fn set_tick_size(&mut self, asset_id: &str, tick_size: f64) {
+        self.books.entry(asset_id.to_string()).or_default().tick_size = Some(tick_size);
+    }
+
+    // Seeds / replaces one book from a snapshot. Returns the asset_id if the
+    // snapshot itself was unusable (crossed) and another one is needed.
+    pub fn apply_snapshot(&mut self, snap: &BookMsg) -> Option<String> {
+        let book = self.books.entry(snap.asset_id.clone()).or_default();
+        book.apply_snapshot(snap);
+        (!book.in_sync).then(|| snap.asset_id.clone())
+    }
+
+    // Applies every entry of a `price_change` message. Returns the asset_ids
+    // whose books are now out of sync and need a fresh snapshot.
+    pub fn apply_price_change(&mut self, msg: &PriceChangeMsg) -> Vec<String> {
+        let ts = parse_ts(&msg.timestamp);
+        let mut resync = Vec::new();
+
+        for change in &msg.price_changes {
+            let id    = change.asset_id.as_str();
+            let side  = Side::parse(&change.side);
+            let price = price_to_units(&change.price);
+            let size  = parse_f64(&change.size);
+            let hash  = change.hash.as_deref();
+
+            let book = self.books.entry(id.to_string()).or_default();
+            if ts < book.timestamp { continue; } // already covered by a newer snapshot
+            if let (Some(side), Some(price), Some(size)) = (side, price, size) {
+                book.apply_level(side, price, size, ts, hash);
+            }
+
+            // Never seeded, or already waiting on a resync — nothing to verify
+            if !book.in_sync {
+                if !resync.iter().any(|r| r == id) { resync.push(id.to_string()); }
+                continue;
+            }
+
+            let expected_bid = change.best_bid.as_deref().and_then(price_to_units);
+            let expected_ask = change.best_ask.as_deref().and_then(price_to_units);
+            if book.is_crossed() || !book.matches_top(expected_bid, expected_ask) {
+                warn!(token_id = %id, ts = %ts, "book out of sync, resyncing");
+                book.in_sync = false;
+                resync.push(id.to_string());
+            }
+        }
+        resync
+    }
+}
+
+// ── Parsing helpers ───────────────────────────────────────────────────────────
+fn parse_levels(levels: &[Level]) -> BTreeMap<u32, f64> {
+    levels
+        .iter()
+        .filter_map(|l| {
+            let price = price_to_units(&l.price)?;
+            let size  = parse_f64(&l.size)?;
+            (size > 0.0).then_some((price, size))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(ts: u64, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> BookMsg {
+        let levels = |l: &[(&str, &str)]| l.iter().map(|(p, s)| json!({ "price": p, "size": s })).collect::<Vec<_>>();
+        serde_json::from_value(json!({
+            "asset_id": "111",
+            "timestamp": ts.to_string(),
+            "bids": levels(bids),
+            "asks": levels(asks),
+        })).expect("book message")
+    }
+
+    // One price_change entry for asset 111 with the exchange's top of book after it
+    fn change(ts: u64, side: &str, price: &str, size: &str, best_bid: &str, best_ask: &str) -> PriceChangeMsg {
+        serde_json::from_value(json!({
+            "timestamp": ts.to_string(),
+            "price_changes": [{
+                "asset_id": "111", "side": side, "price": price, "size": size,
+                "best_bid": best_bid, "best_ask": best_ask,
+            }],
+        })).expect("price_change message")
+    }
+
+    fn seeded() -> Books {
+        let mut books = Books::default();
+        let snap = snapshot(1000, &[("0.40", "10"), ("0.44", "5"), ("0.30", "0")], &[("0.50", "7"), ("0.46", "3")]);
+        assert_eq!(books.apply_snapshot(&snap), None);
+        books
+    }
+
+    #[test]
+    fn snapshot_seeds_the_book() {
+        let books = seeded();
+        let book = books.get("111").expect("book");
+        assert!(book.in_sync);
+        assert_eq!(book.best_bid(), Some((0.44, 5.0)));
+        assert_eq!(book.best_ask(), Some((0.46, 3.0)));
+        assert_eq!(book.bids.len(), 2, "zero-size level kept: {:?}", book.bids);
+        assert_eq!(book.asks_ascending().collect::<Vec<_>>(), [(0.46, 3.0), (0.50, 7.0)]);
+        assert_eq!(book.bids_descending().collect::<Vec<_>>(), [(0.44, 5.0), (0.40, 10.0)]);
+    }
+
+    #[test]
+    fn deltas_set_and_remove_levels() {
+        let mut books = seeded();
+        assert!(books.apply_price_change(&change(1001, "SELL", "0.46", "8", "0.44", "0.46")).is_empty());
+        assert!(books.apply_price_change(&change(1002, "BUY", "0.45", "2", "0.45", "0.46")).is_empty());
+        assert!(books.apply_price_change(&change(1003, "SELL", "0.46", "0", "0.45", "0.5")).is_empty());
+
+        let book = books.get("111").expect("book");
+        assert_eq!(book.best_bid(), Some((0.45, 2.0)));
+        assert_eq!(book.best_ask(), Some((0.50, 7.0)));
+        assert_eq!(book.timestamp, 1003);
+        assert!(book.in_sync);
+    }
+
+    #[test]
+    fn stale_updates_are_dropped() {
+        let mut books = seeded();
+        assert!(books.apply_price_change(&change(999, "SELL", "0.46", "0", "0.44", "0.5")).is_empty());
+        assert_eq!(books.get("111").and_then(OrderBook::best_ask), Some((0.46, 3.0)));
+
+        books.apply_snapshot(&snapshot(900, &[], &[("0.60", "1")]));
+        assert_eq!(books.get("111").and_then(OrderBook::best_ask), Some((0.46, 3.0)), "older snapshot applied");
+    }
+
+    #[test]
+    fn top_of_book_mismatch_triggers_resync() {
+        let mut books = seeded();
+        // We think the best ask is still 0.46; the exchange says 0.47
+        assert_eq!(books.apply_price_change(&change(1001, "BUY", "0.41", "1", "0.44", "0.47")), ["111"]);
+        assert!(!books.get("111").expect("book").in_sync);
+
+        // Deltas while waiting are applied but keep asking for a snapshot
+        assert_eq!(books.apply_price_change(&change(1002, "BUY", "0.41", "2", "0.44", "0.46")), ["111"]);
+
+        // A fresh snapshot puts it back in sync, even at an older timestamp
+        assert_eq!(books.apply_snapshot(&snapshot(1001, &[("0.44", "5")], &[("0.47", "3")])), None);
+        assert!(books.get("111").expect("book").in_sync);
+    }
+
+    #[test]
+    fn crossed_book_triggers_resync() {
+        let mut books = seeded();
+        // A bid at the best ask crosses the book, whatever the exchange says
+        assert_eq!(books.apply_price_change(&change(1001, "BUY", "0.46", "1", "0.46", "0.46")), ["111"]);
+
+        let mut books = Books::default();
+        assert_eq!(books.apply_snapshot(&snapshot(1000, &[("0.50", "1")], &[("0.45", "1")])), Some("111".to_string()));
+    }
+
+    #[test]
+    fn delta_before_snapshot_triggers_resync() {
+        let mut books = Books::default();
+        assert_eq!(books.apply_price_change(&change(1000, "BUY", "0.40", "1", "0.40", "0.5")), ["111"]);
+        assert!(!books.get("111").expect("book").in_sync);
+    }
+}
@@ -0,0 +1,546 @@
+// db.rs — embedded SQLite store for everything the bot sees
+//
+// One file (config.db_path) with:
+//   fetch_runs         one row per run_fetch
+//   events             Gamma events, raw JSON kept, first/last seen
+//   markets            per condition id, with the event it belongs to
+//   tokens             outcome token → market + outcome label
+//   book_snapshots     REST books from run_fetch, and WS books at most once per
+//                      token every config.db_ws_book_interval_secs
+//   ws_updates         price changes, trades, tick sizes and resolutions
+//   arb_opportunities  one row per opportunity window, as first detected,
+//                      completed with its peak edge and close time
+//
+// Writes never block the caller: everything goes through DbWriter, a bounded
+// channel into one writer thread that commits in batches (one transaction per
+// batch, one savepoint per write so a bad row only loses itself) and prunes
+// old rows once an hour according to the retention settings. When the queue
+// is full (the disk can't keep up), new writes are dropped and counted.
+//
+// The schema is versioned with PRAGMA user_version; MIGRATIONS[i] takes the
+// database from version i to i + 1. Add new steps at the end, never edit old ones.
+
+use crate::arb::{ArbEvent, ArbOpportunity, ArbRecord};
+use crate::book::OrderBook;
+use crate::fetch::{event_league, Config};
+use crate::ws::MarketUpdate;
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+const BATCH_MAX: usize = 1000;
+const QUEUE_MAX: usize = 50_000; // writes waiting for the writer thread
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+const MIGRATIONS: &[&str] = &[
+    // 1 — initial schema
+    "CREATE TABLE fetch_runs (
+        id          INTEGER PRIMARY KEY,
+        started_ms  INTEGER NOT NULL,
+        events      INTEGER NOT NULL
+    );
+    CREATE TABLE events (
+        id            TEXT PRIMARY KEY,
+        title         TEXT NOT NULL,
+        slug          TEXT NOT NULL,
+        league        TEXT NOT NULL,
+        end_date      TEXT NOT NULL,
+        raw           TEXT NOT NULL,
+        first_seen_ms INTEGER NOT NULL,
+        last_seen_ms  INTEGER NOT NULL,
+        last_run_id   INTEGER REFERENCES fetch_runs(id)
+    );
+    CREATE TABLE markets (
+        condition_id  TEXT PRIMARY KEY,
+        event_id      TEXT NOT NULL REFERENCES events(id),
+        question      TEXT NOT NULL,
+        market_type   TEXT NOT NULL
+    );
+    CREATE INDEX markets_event ON markets(event_id);
+    CREATE TABLE tokens (
+        token_id      TEXT PRIMARY KEY,
+        condition_id  TEXT NOT NULL REFERENCES markets(condition_id),
+        outcome       TEXT NOT NULL
+    );
+    CREATE INDEX tokens_market ON tokens(condition_id);
+    CREATE TABLE book_snapshots (
+        id          INTEGER PRIMARY KEY,
+        token_id    TEXT NOT NULL,
+        source      TEXT NOT NULL,
+        run_id      INTEGER REFERENCES fetch_runs(id),
+        taken_ms    INTEGER NOT NULL,
+        best_bid    REAL,
+        best_ask    REAL,
+        bids        TEXT NOT NULL,
+        asks        TEXT NOT NULL
+    );
+    CREATE INDEX book_snapshots_token ON book_snapshots(token_id, taken_ms);
+    CREATE INDEX book_snapshots_time ON book_snapshots(taken_ms);
+    CREATE TABLE ws_updates (
+        id           INTEGER PRIMARY KEY,
+        received_ms  INTEGER NOT NULL,
+        kind         TEXT NOT NULL,
+        asset_id     TEXT NOT NULL,
+        market       TEXT NOT NULL,
+        payload      TEXT NOT NULL
+    );
+    CREATE INDEX ws_updates_time ON ws_updates(received_ms);
+    CREATE INDEX ws_updates_asset ON ws_updates(asset_id, received_ms);
+    CREATE TABLE arb_opportunities (
+        id               INTEGER PRIMARY KEY,
+        group_id         TEXT NOT NULL,
+        title            TEXT NOT NULL,
+        kind             TEXT NOT NULL,
+        detected_ms      INTEGER NOT NULL,
+        cost             REAL NOT NULL,
+        edge             REAL NOT NULL,
+        fill_size        REAL NOT NULL,
+        notional         REAL NOT NULL,
+        fees             REAL NOT NULL,
+        expected_profit  REAL NOT NULL,
+        legs             TEXT NOT NULL,
+        closed_ms        INTEGER,
+        peak_edge        REAL,
+        peak_profit      REAL
+    );
+    CREATE INDEX arb_opportunities_group ON arb_opportunities(group_id, detected_ms);
+    CREATE INDEX arb_opportunities_time ON arb_opportunities(detected_ms);",
+];
+
+// ================================================================================
+// SETTINGS / ROWS
+// ================================================================================
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    pub ws_hours: u64,   // ws_updates; 0 = keep forever
+    pub books_days: u64, // book_snapshots
+    pub arbs_days: u64,  // arb_opportunities
+}
+
+impl Retention {
+    pub fn from_config(config: &Config) -> Retention {
+        Retention {
+            ws_hours: config.db_retention_ws_hours,
+            books_days: config.db_retention_books_days,
+            arbs_days: config.db_retention_arbs_days,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BookRow {
+    pub token_id: String,
+    pub source: &'static str, // "rest" or "ws"
+    pub taken_ms: i64,
+    pub best_bid: Option<f64>,
+    pub best_ask: Option<f64>,
+    pub bids: Value, // [[price, size], …] best first
+    pub asks: Value,
+}
+
+impl BookRow {
+    pub fn from_book(token_id: &str, book: &OrderBook, source: &'static str, taken_ms: i64) -> BookRow {
+        BookRow {
+            token_id: token_id.to_string(),
+            source,
+            taken_ms,
+            best_bid: book.best_bid().map(|(p, _)| p),
+            best_ask: book.best_ask().map(|(p, _)| p),
+            bids: book.bids_descending().map(|(p, s)| json!([p, s])).collect(),
+            asks: book.asks_ascending().map(|(p, s)| json!([p, s])).collect(),
+        }
+    }
+
+    // REST /book body: levels are strings, bids ascending and asks descending
+    pub fn from_rest(token_id: &str, body: &Value, taken_ms: i64) -> BookRow {
+        let levels = |key: &str| -> Vec<(f64, f64)> {
+            body.get(key).and_then(Value::as_array).map(|arr| arr.iter().filter_map(|l| {
+                let p = l.get("price").and_then(Value::as_str)?.parse().ok()?;
+                let s = l.get("size").and_then(Value::as_str)?.parse().ok()?;
+                Some((p, s))
+            }).collect()).unwrap_or_default()
+        };
+        let mut bids = levels("bids");
+        let mut asks = levels("asks");
+        bids.sort_by(|a, b| b.0.total_cmp(&a.0));
+        asks.sort_by(|a, b| a.0.total_cmp(&b.0));
+        BookRow {
+            token_id: token_id.to_string(),
+            source: "rest",
+            taken_ms,
+            best_bid: bids.first().map(|l| l.0),
+            best_ask: asks.first().map(|l| l.0),
+            bids: bids.iter().map(|(p, s)| json!([p, s])).collect(),
+            asks: asks.iter().map(|(p, s)| json!([p, s])).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WsRow {
+    pub received_ms: i64,
+    pub kind: &'static str,
+    pub asset_id: String,
+    pub market: String,
+    pub payload: Value,
+}
+
+pub enum DbWrite {
+    // Events in the report plus the REST books fetched for them
+    FetchRun { started_ms: i64, events: Vec<Value>, books: Vec<BookRow> },
+    Book(BookRow),
+    Ws(WsRow),
+    ArbOpen(ArbOpportunity),
+    ArbClosed(ArbRecord),
+}
+
+impl DbWrite {
+    // What the write was, for logs
+    fn describe(&self) -> String {
+        match self {
+            DbWrite::FetchRun { events, books, .. } => format!("fetch run ({} events, {} books)", events.len(), books.len()),
+            DbWrite::Book(b) => format!("{} book for {}", b.source, b.token_id),
+            DbWrite::Ws(u) => format!("{} update for {}", u.kind, u.asset_id),
+            DbWrite::ArbOpen(opp) => format!("arb open for {}", opp.group_id),
+            DbWrite::ArbClosed(rec) => format!("arb close for {}", rec.group_id),
+        }
+    }
+}
+
+// ================================================================================
+// STORE
+// ================================================================================
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: &str) -> rusqlite::Result<Store> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        let mut store = Store { conn };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    pub fn schema_version(&self) -> rusqlite::Result<usize> {
+        self.conn.pragma_query_value(None, "user_version", |r| r.get(0))
+    }
+
+    fn migrate(&mut self) -> rusqlite::Result<()> {
+        let current = self.schema_version()?;
+        for (i, sql) in MIGRATIONS.iter().enumerate().skip(current) {
+            let tx = self.conn.transaction()?;
+            tx.execute_batch(sql)?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+            info!(version = i + 1, "migrated schema");
+        }
+        Ok(())
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    // Applies a batch of writes in one transaction. Each write gets its own
+    // savepoint, so one that fails is rolled back and logged on its own while
+    // the rest of the batch commits. Returns how many writes failed.
+    pub fn write(&mut self, batch: &[DbWrite]) -> rusqlite::Result<usize> {
+        let mut tx = self.conn.transaction()?;
+        let mut failed = 0;
+        for w in batch {
+            let sp = tx.savepoint()?;
+            match write_one(&sp, w) {
+                Ok(()) => sp.commit()?,
+                Err(e) => {
+                    warn!(write = %w.describe(), error = %e, "failed to write row, skipped");
+                    failed += 1; // dropping the savepoint rolls it back
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(failed)
+    }
+
+    // Deletes rows older than the retention windows; returns rows removed
+    pub fn prune(&self, retention: &Retention) -> rusqlite::Result<usize> {
+        let now = Utc::now().timestamp_millis();
+        let cutoff = |secs: u64| now - secs as i64 * 1000;
+        let mut removed = 0;
+        if retention.ws_hours > 0 {
+            removed += self.conn.execute("DELETE FROM ws_updates WHERE received_ms < ?1", params![cutoff(retention.ws_hours * 3600)])?;
+        }
+        if retention.books_days > 0 {
+            removed += self.conn.execute("DELETE FROM book_snapshots WHERE taken_ms < ?1", params![cutoff(retention.books_days * 86400)])?;
+        }
+        if retention.arbs_days > 0 {
+            removed += self.conn.execute("DELETE FROM arb_opportunities WHERE detected_ms < ?1", params![cutoff(retention.arbs_days * 86400)])?;
+        }
+        Ok(removed)
+    }
+}
+
+// One write, inside the savepoint Store::write opened for it
+fn write_one(conn: &Connection, w: &DbWrite) -> rusqlite::Result<()> {
+    match w {
+        DbWrite::FetchRun { started_ms, events, books } => {
+            conn.execute("INSERT INTO fetch_runs (started_ms, events) VALUES (?1, ?2)", params![started_ms, events.len()])?;
+            let run_id = conn.last_insert_rowid();
+            for event in events {
+                insert_event(conn, event, run_id, *started_ms)?;
+            }
+            for b in books {
+                insert_book(conn, b, Some(run_id))?;
+            }
+        }
+        DbWrite::Book(b) => insert_book(conn, b, None)?,
+        DbWrite::Ws(u) => {
+            conn.execute(
+                "INSERT INTO ws_updates (received_ms, kind, asset_id, market, payload) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![u.received_ms, u.kind, u.asset_id, u.market, u.payload.to_string()],
+            )?;
+        }
+        DbWrite::ArbOpen(opp) => {
+            // The engine re-sends Open while the window lasts; one row per episode
+            let open: Option<i64> = conn.query_row(
+                "SELECT id FROM arb_opportunities WHERE group_id = ?1 AND closed_ms IS NULL LIMIT 1",
+                params![opp.group_id],
+                |r| r.get(0),
+            ).optional()?;
+            if open.is_some() { return Ok(()); }
+            let legs: Value = opp.legs.iter().map(|l| json!({
+                "asset_id": l.asset_id, "outcome": l.outcome, "price": l.price,
+                "size": l.size, "avg_price": l.avg_price, "worst_price": l.worst_price,
+            })).collect();
+            conn.execute(
+                "INSERT INTO arb_opportunities (group_id, title, kind, detected_ms, cost, edge, fill_size, notional, fees, expected_profit, legs)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    opp.group_id, opp.title, kind_name(opp), opp.detected_at.timestamp_millis(),
+                    opp.cost, opp.edge, opp.fill_size, opp.notional, opp.fees + opp.gas,
+                    opp.expected_profit, legs.to_string(),
+                ],
+            )?;
+        }
+        DbWrite::ArbClosed(rec) => {
+            // The open row of this episode: latest for the group, not yet closed
+            let id: Option<i64> = conn.query_row(
+                "SELECT id FROM arb_opportunities WHERE group_id = ?1 AND closed_ms IS NULL ORDER BY detected_ms DESC LIMIT 1",
+                params![rec.group_id],
+                |r| r.get(0),
+            ).optional()?;
+            if let Some(id) = id {
+                conn.execute(
+                    "UPDATE arb_opportunities SET closed_ms = ?1, peak_edge = ?2, peak_profit = ?3 WHERE id = ?4",
+                    params![rec.closed_at_ms, rec.peak_edge, rec.peak_profit, id],
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn kind_name(opp: &ArbOpportunity) -> String {
+    serde_json::to_value(opp.kind).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+fn insert_event(tx: &Connection, event: &Value, run_id: i64, seen_ms: i64) -> rusqlite::Result<()> {
+    let s = |v: &Value, k: &str| v.get(k).and_then(Value::as_str).unwrap_or("").to_string();
+    let id = s(event, "id");
+    if id.is_empty() { return Ok(()); }
+    tx.execute(
+        "INSERT INTO events (id, title, slug, league, end_date, raw, first_seen_ms, last_seen_ms, last_run_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7, ?8)
+         ON CONFLICT(id) DO UPDATE SET title = excluded.title, slug = excluded.slug, league = excluded.league,
+             end_date = excluded.end_date, raw = excluded.raw, last_seen_ms = excluded.last_seen_ms,
+             last_run_id = excluded.last_run_id",
+        params![id, s(event, "title"), s(event, "slug"), event_league(event), s(event, "endDate"), event.to_string(), seen_ms, run_id],
+    )?;
+
+    for market in event.get("markets").and_then(Value::as_array).into_iter().flatten() {
+        let condition_id = s(market, "conditionId");
+        if condition_id.is_empty() { continue; }
+        tx.execute(
+            "INSERT INTO markets (condition_id, event_id, question, market_type) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(condition_id) DO UPDATE SET question = excluded.question, market_type = excluded.market_type",
+            params![condition_id, id, s(market, "question"), s(market, "sportsMarketType")],
+        )?;
+        let parse = |k: &str| -> Vec<String> { serde_json::from_str(&s(market, k)).unwrap_or_default() };
+        for (token, outcome) in parse("clobTokenIds").iter().zip(parse("outcomes")) {
+            tx.execute(
+                "INSERT INTO tokens (token_id, condition_id, outcome) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(token_id) DO UPDATE SET outcome = excluded.outcome",
+                params![token, condition_id, outcome],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn insert_book(tx: &Connection, b: &BookRow, run_id: Option<i64>) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO book_snapshots (token_id, source, run_id, taken_ms, best_bid, best_ask, bids, asks)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![b.token_id, b.source, run_id, b.taken_ms, b.best_bid, b.best_ask, b.bids.to_string(), b.asks.to_string()],
+    )?;
+    Ok(())
+}
+
+// ================================================================================
+// WRITER
+// ================================================================================
+#[derive(Clone)]
+pub struct DbWriter {
+    tx: mpsc::SyncSender<DbWrite>,
+    dropped: Arc<AtomicU64>, // writes lost to a full queue
+}
+
+impl DbWriter {
+    // Opens the store and starts the writer thread
+    pub fn start(path: &str, retention: Retention) -> rusqlite::Result<DbWriter> {
+        let mut store = Store::open(path)?;
+        let (tx, rx) = mpsc::sync_channel::<DbWrite>(QUEUE_MAX);
+        std::thread::spawn(move || {
+            let mut last_prune: Option<Instant> = None;
+            loop {
+                let first = match rx.recv_timeout(Duration::from_secs(1)) {
+                    Ok(w) => Some(w),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                };
+                let mut batch: Vec<DbWrite> = first.into_iter().collect();
+                while batch.len() < BATCH_MAX {
+                    match rx.try_recv() {
+                        Ok(w) => batch.push(w),
+                        Err(_) => break,
+                    }
+                }
+                if !batch.is_empty() {
+                    match store.write(&batch) {
+                        Ok(0) => {}
+                        Ok(n) => warn!(failed = n, batch = batch.len(), "some writes in the batch failed"),
+                        Err(e) => error!(batch = batch.len(), error = %e, "failed to write batch"),
+                    }
+                }
+                if last_prune.is_none_or(|t| t.elapsed() >= PRUNE_INTERVAL) {
+                    last_prune = Some(Instant::now());
+                    match store.prune(&retention) {
+                        Ok(0) => {}
+                        Ok(n) => info!(rows = n, "pruned rows past retention"),
+                        Err(e) => warn!(error = %e, "prune failed"),
+                    }
+                }
+            }
+        });
+        Ok(DbWriter { tx, dropped: Arc::new(AtomicU64::new(0)) })
+    }
+
+    // Config path, or None when the store is disabled (empty db_path) or can't open
+    pub fn from_config(config: &Config) -> Option<DbWriter> {
+        if config.db_path.is_empty() { return None; }
+        match DbWriter::start(&config.db_path, Retention::from_config(config)) {
+            Ok(w) => Some(w),
+            Err(e) => {
+                error!(path = %config.db_path, error = %e, "could not open database, store disabled");
+                None
+            }
+        }
+    }
+
+    // Never blocks: a full queue drops the write (logged on the first drop
+    // and every 1000th after that)
+    pub fn send(&self, write: DbWrite) {
+        match self.tx.try_send(write) {
+            Ok(()) => {}
+            Err(mpsc::TrySendError::Full(w)) => {
+                let n = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if n == 1 || n.is_multiple_of(1000) {
+                    warn!(write = %w.describe(), dropped = n, "write queue full, dropping writes");
+                }
+            }
+            Err(mpsc::TrySendError::Disconnected(_)) => {
+                error!("writer thread is gone, dropping write");
+            }
+        }
+    }
+
+    // Writes dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+// ── Background tasks ──────────────────────────────────────────────────────────
+// Stream updates: everything but books goes to ws_updates; books are sampled
+// into book_snapshots at most once per token per `book_interval`.
+pub fn spawn_update_recorder(mut updates: broadcast::Receiver<MarketUpdate>, db: DbWriter, book_interval: Duration) {
+    tokio::spawn(async move {
+        let mut last_book: HashMap<String, Instant> = HashMap::new();
+        loop {
+            let update = match updates.recv().await {
+                Ok(u) => u,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(skipped = n, "fell behind the WS pool, updates not stored");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let now = Utc::now().timestamp_millis();
+            let row = |kind, asset_id: &str, market: &str, payload| WsRow {
+                received_ms: now, kind, asset_id: asset_id.to_string(), market: market.to_string(), payload,
+            };
+            match &update {
+                MarketUpdate::Book { asset_id, book } => {
+                    if last_book.get(asset_id).is_some_and(|t| t.elapsed() < book_interval) { continue; }
+                    last_book.insert(asset_id.clone(), Instant::now());
+                    db.send(DbWrite::Book(BookRow::from_book(asset_id, book, "ws", now)));
+                }
+                MarketUpdate::PriceChange { market, timestamp, changes } => {
+                    for c in changes {
+                        db.send(DbWrite::Ws(row("price_change", &c.asset_id, market, json!({
+                            "timestamp": timestamp, "side": c.side.map(|s| format!("{:?}", s)),
+                            "price": c.price, "size": c.size, "best_bid": c.best_bid, "best_ask": c.best_ask,
+                        }))));
+                    }
+                }
+                MarketUpdate::Trade { asset_id, trade } => {
+                    db.send(DbWrite::Ws(row("trade", asset_id, "", json!({
+                        "timestamp": trade.timestamp, "side": trade.side.map(|s| format!("{:?}", s)),
+                        "price": trade.price, "size": trade.size, "fee_rate_bps": trade.fee_rate_bps,
+                    }))));
+                }
+                MarketUpdate::TickSize { asset_id, tick_size } => {
+                    db.send(DbWrite::Ws(row("tick_size", asset_id, "", json!({ "tick_size": tick_size }))));
+                }
+                MarketUpdate::MarketResolved { market, winning_asset_id } => {
+                    db.send(DbWrite::Ws(row("market_resolved", winning_asset_id, market, json!({}))));
+                }
+            }
+        }
+    });
+}
+
+// Every opportunity opened, and its close
+pub fn spawn_arb_recorder(mut events: broadcast::Receiver<ArbEvent>, db: DbWriter) {
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(ArbEvent::Open(opp)) => db.send(DbWrite::ArbOpen(opp)),
+                Ok(ArbEvent::Closed(rec)) => db.send(DbWrite::ArbClosed(rec)),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(skipped = n, "fell behind the arb engine, events not stored");
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+}
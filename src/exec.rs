@@ -0,0 +1,747 @@
+// exec.rs — Polymarket CLOB order execution
+//
+// Everything needed to place real orders:
+//   - Wallet: secp256k1 key from config, Ethereum address, recoverable signatures
+//   - EIP-712 hashing for the two typed messages the CLOB uses:
+//       · Order     — the order itself, verified on-chain by the CTF Exchange
+//       · ClobAuth  — proves wallet ownership to create / derive API keys ("L1")
+//   - API credentials: every trading request carries an HMAC-SHA256 of
+//     timestamp + method + path + body under the API secret ("L2" headers)
+//   - ClobClient: derive creds, build + sign orders, POST /order as FOK, IOC
+//     (the CLOB calls it FAK, fill-and-kill) or GTC, DELETE /cancel-all,
+//     GET /balance-allowance
+//
+// Nothing here holds state beyond the credentials, so it can be pointed at
+// mock_clob.rs for offline testing (see tests/exec.rs).
+//
+// CLOB docs: https://docs.polymarket.com/developers/CLOB/orders/create-order
+
+use crate::fetch::Config;
+use crate::metrics::metrics;
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use std::fmt;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Polygon mainnet exchange contracts — orders are signed against one of them
+pub const CTF_EXCHANGE: &str          = "0x4bFb41d5B3570DeFd03C39a9A4D8dE6Bd8B8982E";
+pub const NEG_RISK_CTF_EXCHANGE: &str = "0xC5d563A36AE78145C45a50134d48A1215220f80a";
+pub const ZERO_ADDRESS: [u8; 20] = [0; 20];
+
+const ORDER_TYPE: &str = "Order(uint256 salt,address maker,address signer,address taker,uint256 tokenId,uint256 makerAmount,uint256 takerAmount,uint256 expiration,uint256 nonce,uint256 feeRateBps,uint8 side,uint8 signatureType)";
+const CLOB_AUTH_TYPE: &str = "ClobAuth(address address,string timestamp,uint256 nonce,string message)";
+pub const CLOB_AUTH_MESSAGE: &str = "This message attests that I control the given wallet";
+
+// ================================================================================
+// ERRORS
+// ================================================================================
+#[derive(Debug, Clone)]
+pub enum ExecError {
+    Key(String),          // bad or missing private key / address
+    Network(String),      // request never got a response
+    Http(u16, String),    // non-2xx from the CLOB
+    Auth(String),         // no API creds, or the CLOB refused them
+    Rejected(String),     // order reached the CLOB and was refused
+    Killed(String),       // FOK/FAK found nothing (or not enough) to match — nothing filled
+    Malformed(String),    // response we couldn't parse
+    Halted(String),       // kill switch engaged — nothing is sent
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecError::Key(m)       => write!(f, "key error: {}", m),
+            ExecError::Network(m)   => write!(f, "network error: {}", m),
+            ExecError::Http(c, m)   => write!(f, "HTTP {}: {}", c, m),
+            ExecError::Auth(m)      => write!(f, "auth error: {}", m),
+            ExecError::Rejected(m)  => write!(f, "order rejected: {}", m),
+            ExecError::Killed(m)    => write!(f, "order killed: {}", m),
+            ExecError::Malformed(m) => write!(f, "malformed response: {}", m),
+            ExecError::Halted(m)    => write!(f, "halted: {}", m),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+// ================================================================================
+// HASHING / ENCODING
+// ================================================================================
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+// ── ABI words (32 bytes each) ─────────────────────────────────────────────────
+pub fn word_u64(v: u64) -> [u8; 32] {
+    let mut w = [0u8; 32];
+    w[24..].copy_from_slice(&v.to_be_bytes());
+    w
+}
+
+pub fn word_address(a: &[u8; 20]) -> [u8; 32] {
+    let mut w = [0u8; 32];
+    w[12..].copy_from_slice(a);
+    w
+}
+
+// Dynamic types (string, bytes) are hashed in EIP-712
+pub fn word_string(s: &str) -> [u8; 32] {
+    keccak256(s.as_bytes())
+}
+
+// uint256 from a decimal string — token ids are ~77 digit numbers
+pub fn word_u256_dec(s: &str) -> Option<[u8; 32]> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) { return None; }
+    let mut w = [0u8; 32];
+    for digit in s.bytes().map(|b| (b - b'0') as u32) {
+        // w = w * 10 + digit, big-endian
+        let mut carry = digit;
+        for byte in w.iter_mut().rev() {
+            let v = *byte as u32 * 10 + carry;
+            *byte = (v & 0xff) as u8;
+            carry = v >> 8;
+        }
+        if carry != 0 { return None; } // overflow
+    }
+    Some(w)
+}
+
+pub fn parse_address(s: &str) -> Result<[u8; 20], ExecError> {
+    let bytes = hex::decode(s.trim_start_matches("0x")).map_err(|e| ExecError::Key(format!("address {s}: {e}")))?;
+    bytes.try_into().map_err(|_| ExecError::Key(format!("address {s}: not 20 bytes")))
+}
+
+// EIP-55 mixed-case checksum address
+pub fn checksum_address(a: &[u8; 20]) -> String {
+    let lower = hex::encode(a);
+    let hash = keccak256(lower.as_bytes());
+    let mixed: String = lower.chars().enumerate().map(|(i, c)| {
+        let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+        if c.is_ascii_alphabetic() && nibble >= 8 { c.to_ascii_uppercase() } else { c }
+    }).collect();
+    format!("0x{}", mixed)
+}
+
+// keccak256(typeHash ‖ words…)
+pub fn hash_struct(type_string: &str, words: &[[u8; 32]]) -> [u8; 32] {
+    let mut buf = Vec::with_capacity(32 * (words.len() + 1));
+    buf.extend_from_slice(&keccak256(type_string.as_bytes()));
+    for w in words { buf.extend_from_slice(w); }
+    keccak256(&buf)
+}
+
+// ── EIP-712 domain ────────────────────────────────────────────────────────────
+#[derive(Debug, Clone)]
+pub struct Eip712Domain {
+    pub name: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub verifying_contract: Option<[u8; 20]>,
+}
+
+impl Eip712Domain {
+    pub fn separator(&self) -> [u8; 32] {
+        let mut words = vec![word_string(&self.name), word_string(&self.version), word_u64(self.chain_id)];
+        match &self.verifying_contract {
+            Some(c) => {
+                words.push(word_address(c));
+                hash_struct("EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)", &words)
+            }
+            None => hash_struct("EIP712Domain(string name,string version,uint256 chainId)", &words),
+        }
+    }
+
+    // Domain the CTF Exchange verifies orders against
+    pub fn exchange(chain_id: u64, neg_risk: bool) -> Eip712Domain {
+        let contract = if neg_risk { NEG_RISK_CTF_EXCHANGE } else { CTF_EXCHANGE };
+        Eip712Domain {
+            name: "Polymarket CTF Exchange".to_string(),
+            version: "1".to_string(),
+            chain_id,
+            verifying_contract: parse_address(contract).ok(),
+        }
+    }
+
+    pub fn clob_auth(chain_id: u64) -> Eip712Domain {
+        Eip712Domain { name: "ClobAuthDomain".to_string(), version: "1".to_string(), chain_id, verifying_contract: None }
+    }
+}
+
+// keccak256(0x19 0x01 ‖ domainSeparator ‖ hashStruct(message))
+pub fn typed_data_digest(domain: &Eip712Domain, struct_hash: &[u8; 32]) -> [u8; 32] {
+    let mut buf = Vec::with_capacity(66);
+    buf.extend_from_slice(&[0x19, 0x01]);
+    buf.extend_from_slice(&domain.separator());
+    buf.extend_from_slice(struct_hash);
+    keccak256(&buf)
+}
+
+// ================================================================================
+// WALLET
+// ================================================================================
+pub struct Wallet {
+    key: SigningKey,
+    address: [u8; 20],
+}
+
+impl Wallet {
+    pub fn from_hex(private_key: &str) -> Result<Wallet, ExecError> {
+        let bytes = hex::decode(private_key.trim().trim_start_matches("0x"))
+            .map_err(|_| ExecError::Key("private key is not hex".to_string()))?;
+        let key = SigningKey::from_slice(&bytes).map_err(|_| ExecError::Key("invalid secp256k1 private key".to_string()))?;
+        let address = address_of(key.verifying_key());
+        Ok(Wallet { key, address })
+    }
+
+    pub fn address(&self) -> [u8; 20] {
+        self.address
+    }
+
+    pub fn address_hex(&self) -> String {
+        checksum_address(&self.address)
+    }
+
+    // 65-byte r ‖ s ‖ v signature (v = 27/28) over a 32-byte digest
+    pub fn sign_digest(&self, digest: &[u8; 32]) -> Result<[u8; 65], ExecError> {
+        let (sig, recid) = self.key.sign_prehash_recoverable(digest).map_err(|e| ExecError::Key(e.to_string()))?;
+        let mut out = [0u8; 65];
+        out[..64].copy_from_slice(&sig.to_bytes());
+        out[64] = 27 + recid.to_byte();
+        Ok(out)
+    }
+}
+
+fn address_of(key: &VerifyingKey) -> [u8; 20] {
+    let point = key.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    hash[12..].try_into().expect("20 bytes")
+}
+
+// Address that produced `sig` over `digest`, or None if it doesn't verify
+pub fn recover_address(digest: &[u8; 32], sig: &[u8]) -> Option<[u8; 20]> {
+    if sig.len() != 65 { return None; }
+    let signature = Signature::from_slice(&sig[..64]).ok()?;
+    let recid = RecoveryId::from_byte(sig[64].checked_sub(27)?)?;
+    let key = VerifyingKey::recover_from_prehash(digest, &signature, recid).ok()?;
+    Some(address_of(&key))
+}
+
+pub fn signature_hex(sig: &[u8; 65]) -> String {
+    format!("0x{}", hex::encode(sig))
+}
+
+pub fn parse_signature(s: &str) -> Option<Vec<u8>> {
+    hex::decode(s.trim_start_matches("0x")).ok()
+}
+
+// ================================================================================
+// ORDERS
+// ================================================================================
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+impl OrderSide {
+    fn as_u8(self) -> u8 {
+        match self { OrderSide::Buy => 0, OrderSide::Sell => 1 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderType {
+    Fok, // fill completely now or cancel
+    Ioc, // fill what's available now, cancel the rest
+    Gtc, // rest on the book until filled or cancelled
+}
+
+impl OrderType {
+    // Name the CLOB uses on the wire
+    pub fn wire(self) -> &'static str {
+        match self { OrderType::Fok => "FOK", OrderType::Ioc => "FAK", OrderType::Gtc => "GTC" }
+    }
+
+    pub fn from_wire(s: &str) -> Option<OrderType> {
+        match s { "FOK" => Some(OrderType::Fok), "FAK" => Some(OrderType::Ioc), "GTC" => Some(OrderType::Gtc), _ => None }
+    }
+}
+
+// What the caller wants; ClobClient turns it into a SignedOrder
+#[derive(Debug, Clone)]
+pub struct OrderArgs {
+    pub token_id: String,
+    pub side: OrderSide,
+    pub price: f64,        // limit, 0..1
+    pub size: f64,         // shares
+    pub fee_rate_bps: u64, // must match the market's rate or the CLOB rejects it
+    pub neg_risk: bool,    // multi-outcome markets trade on the neg-risk exchange
+}
+
+// USDC and shares both have 6 decimals on-chain. Prices are whole 1/10_000
+// units and sizes whole 1/100 shares, so the products are exact.
+pub fn order_amounts(side: OrderSide, price: f64, size: f64) -> (u64, u64) {
+    let price_units = (price * 10_000.0).round() as u64;
+    let size_cents  = (size * 100.0 + 1e-9).floor() as u64;
+    let shares = size_cents * 10_000;
+    let usdc   = price_units * size_cents;
+    match side {
+        OrderSide::Buy  => (usdc, shares), // give USDC, get shares
+        OrderSide::Sell => (shares, usdc),
+    }
+}
+
+// Wire format of POST /order's "order" field. Numbers the exchange treats as
+// uint256 travel as decimal strings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedOrder {
+    pub salt: u64,
+    pub maker: String,
+    pub signer: String,
+    pub taker: String,
+    pub token_id: String,
+    #[serde(with = "u64_string")]
+    pub maker_amount: u64,
+    #[serde(with = "u64_string")]
+    pub taker_amount: u64,
+    #[serde(with = "u64_string")]
+    pub expiration: u64,
+    #[serde(with = "u64_string")]
+    pub nonce: u64,
+    #[serde(with = "u64_string")]
+    pub fee_rate_bps: u64,
+    pub side: OrderSide,
+    pub signature_type: u8,
+    #[serde(default)]
+    pub signature: String,
+}
+
+impl SignedOrder {
+    pub fn struct_hash(&self) -> Result<[u8; 32], ExecError> {
+        let token = word_u256_dec(&self.token_id).ok_or_else(|| ExecError::Key(format!("bad token id {}", self.token_id)))?;
+        Ok(hash_struct(ORDER_TYPE, &[
+            word_u64(self.salt),
+            word_address(&parse_address(&self.maker)?),
+            word_address(&parse_address(&self.signer)?),
+            word_address(&parse_address(&self.taker)?),
+            token,
+            word_u64(self.maker_amount),
+            word_u64(self.taker_amount),
+            word_u64(self.expiration),
+            word_u64(self.nonce),
+            word_u64(self.fee_rate_bps),
+            word_u64(self.side.as_u8() as u64),
+            word_u64(self.signature_type as u64),
+        ]))
+    }
+
+    pub fn digest(&self, domain: &Eip712Domain) -> Result<[u8; 32], ExecError> {
+        Ok(typed_data_digest(domain, &self.struct_hash()?))
+    }
+
+    // Price implied by the amounts
+    pub fn price(&self) -> f64 {
+        let (usdc, shares) = match self.side {
+            OrderSide::Buy  => (self.maker_amount, self.taker_amount),
+            OrderSide::Sell => (self.taker_amount, self.maker_amount),
+        };
+        if shares == 0 { 0.0 } else { usdc as f64 / shares as f64 }
+    }
+
+    pub fn shares(&self) -> f64 {
+        match self.side {
+            OrderSide::Buy  => self.taker_amount as f64 / 1e6,
+            OrderSide::Sell => self.maker_amount as f64 / 1e6,
+        }
+    }
+}
+
+mod u64_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &u64, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&v.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+        String::deserialize(d)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// Response body of POST /order
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OrderResponse {
+    #[serde(default)]
+    pub success: bool,
+    #[serde(default, rename = "errorMsg")]
+    pub error_msg: String,
+    #[serde(default, rename = "orderID")]
+    pub order_id: String,
+    #[serde(default)]
+    pub status: String, // "matched" | "live" | "delayed" | "unmatched"
+    #[serde(default, rename = "makingAmount")]
+    pub making_amount: String,
+    #[serde(default, rename = "takingAmount")]
+    pub taking_amount: String,
+}
+
+impl OrderResponse {
+    // Shares received (BUY) or sold (SELL), from the amounts the CLOB reports
+    pub fn filled_shares(&self, side: OrderSide) -> f64 {
+        let amount = match side { OrderSide::Buy => &self.taking_amount, OrderSide::Sell => &self.making_amount };
+        amount.parse().unwrap_or(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarketInfo {
+    pub neg_risk: bool,
+    pub fee_rate_bps: u64,
+}
+
+// ================================================================================
+// API CREDENTIALS
+// ================================================================================
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiCreds {
+    #[serde(rename = "apiKey")]
+    pub api_key: String,
+    pub secret: String, // URL-safe base64
+    pub passphrase: String,
+}
+
+// EIP-712 digest behind the L1 headers
+pub fn clob_auth_digest(chain_id: u64, address: &[u8; 20], timestamp: &str, nonce: u64) -> [u8; 32] {
+    let hash = hash_struct(CLOB_AUTH_TYPE, &[
+        word_address(address),
+        word_string(timestamp),
+        word_u64(nonce),
+        word_string(CLOB_AUTH_MESSAGE),
+    ]);
+    typed_data_digest(&Eip712Domain::clob_auth(chain_id), &hash)
+}
+
+// Headers for creating / deriving API keys
+pub fn l1_headers(wallet: &Wallet, chain_id: u64, timestamp: &str, nonce: u64) -> Result<Vec<(String, String)>, ExecError> {
+    let sig = wallet.sign_digest(&clob_auth_digest(chain_id, &wallet.address(), timestamp, nonce))?;
+    Ok(vec![
+        ("POLY_ADDRESS".to_string(), wallet.address_hex()),
+        ("POLY_SIGNATURE".to_string(), signature_hex(&sig)),
+        ("POLY_TIMESTAMP".to_string(), timestamp.to_string()),
+        ("POLY_NONCE".to_string(), nonce.to_string()),
+    ])
+}
+
+// base64url(HMAC-SHA256(base64url_decode(secret), timestamp + method + path + body))
+pub fn hmac_signature(secret: &str, timestamp: &str, method: &str, path: &str, body: &str) -> Result<String, ExecError> {
+    let key = URL_SAFE.decode(secret).map_err(|_| ExecError::Auth("API secret is not base64".to_string()))?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).map_err(|e| ExecError::Auth(e.to_string()))?;
+    mac.update(format!("{}{}{}{}", timestamp, method, path, body).as_bytes());
+    Ok(URL_SAFE.encode(mac.finalize().into_bytes()))
+}
+
+// Headers for every authenticated trading request
+pub fn l2_headers(address: &str, creds: &ApiCreds, timestamp: &str, method: &str, path: &str, body: &str) -> Result<Vec<(String, String)>, ExecError> {
+    Ok(vec![
+        ("POLY_ADDRESS".to_string(), address.to_string()),
+        ("POLY_SIGNATURE".to_string(), hmac_signature(&creds.secret, timestamp, method, path, body)?),
+        ("POLY_TIMESTAMP".to_string(), timestamp.to_string()),
+        ("POLY_API_KEY".to_string(), creds.api_key.clone()),
+        ("POLY_PASSPHRASE".to_string(), creds.passphrase.clone()),
+    ])
+}
+
+// ================================================================================
+// CLIENT
+// ================================================================================
+pub struct ClobClient {
+    http: Client,
+    base_url: String,
+    wallet: Wallet,
+    chain_id: u64,
+    signature_type: u8, // 0 = EOA, 1 = Polymarket proxy, 2 = Gnosis safe
+    funder: [u8; 20],   // address holding the funds; the wallet itself for EOA
+    creds: Option<ApiCreds>,
+}
+
+impl ClobClient {
+    pub fn new(http: Client, base_url: &str, wallet: Wallet, chain_id: u64, signature_type: u8, funder: Option<[u8; 20]>) -> ClobClient {
+        let funder = funder.unwrap_or_else(|| wallet.address());
+        ClobClient {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            wallet,
+            chain_id,
+            signature_type,
+            funder,
+            creds: None,
+        }
+    }
+
+    pub fn from_config(http: Client, config: &Config) -> Result<ClobClient, ExecError> {
+        if config.exec_private_key.is_empty() {
+            return Err(ExecError::Key("exec_private_key is not set in config.json".to_string()));
+        }
+        let wallet = Wallet::from_hex(&config.exec_private_key)?;
+        let funder = match config.exec_funder.as_str() {
+            "" => None,
+            f  => Some(parse_address(f)?),
+        };
+        Ok(ClobClient::new(http, &config.clob_url, wallet, config.exec_chain_id, config.exec_signature_type, funder))
+    }
+
+    pub fn address(&self) -> String {
+        self.wallet.address_hex()
+    }
+
+    pub fn set_creds(&mut self, creds: ApiCreds) {
+        self.creds = Some(creds);
+    }
+
+    // Derives the wallet's existing API key, creating one if it has none
+    pub async fn derive_api_key(&mut self) -> Result<ApiCreds, ExecError> {
+        let creds = match self.l1_request(reqwest::Method::GET, "/auth/derive-api-key").await {
+            Ok(c) => c,
+            Err(_) => self.l1_request(reqwest::Method::POST, "/auth/api-key").await?,
+        };
+        self.creds = Some(creds.clone());
+        Ok(creds)
+    }
+
+    async fn l1_request(&self, method: reqwest::Method, path: &str) -> Result<ApiCreds, ExecError> {
+        let timestamp = unix_secs().to_string();
+        let mut req = self.http.request(method, format!("{}{}", self.base_url, path));
+        for (k, v) in l1_headers(&self.wallet, self.chain_id, &timestamp, 0)? {
+            req = req.header(k, v);
+        }
+        let body = send(path, req).await?;
+        serde_json::from_value(body).map_err(|e| ExecError::Malformed(e.to_string()))
+    }
+
+    // Builds and signs an order; no network
+    pub fn build_order(&self, args: &OrderArgs) -> Result<SignedOrder, ExecError> {
+        let (maker_amount, taker_amount) = order_amounts(args.side, args.price, args.size);
+        if taker_amount == 0 || maker_amount == 0 {
+            return Err(ExecError::Rejected(format!("order too small: {} @ {}", args.size, args.price)));
+        }
+        let mut order = SignedOrder {
+            salt: salt(),
+            maker: checksum_address(&self.funder),
+            signer: self.wallet.address_hex(),
+            taker: checksum_address(&ZERO_ADDRESS),
+            token_id: args.token_id.clone(),
+            maker_amount,
+            taker_amount,
+            expiration: 0,
+            nonce: 0,
+            fee_rate_bps: args.fee_rate_bps,
+            side: args.side,
+            signature_type: self.signature_type,
+            signature: String::new(),
+        };
+        let digest = order.digest(&Eip712Domain::exchange(self.chain_id, args.neg_risk))?;
+        order.signature = signature_hex(&self.wallet.sign_digest(&digest)?);
+        Ok(order)
+    }
+
+    // POST /order. A response with success=false is returned as Rejected, or
+    // as Killed when it's the CLOB's FOK/FAK no-match answer.
+    pub async fn post_order(&self, order: &SignedOrder, order_type: OrderType) -> Result<OrderResponse, ExecError> {
+        let creds = self.creds.as_ref().ok_or_else(|| ExecError::Auth("no API credentials — call derive_api_key first".to_string()))?;
+        let body = json!({ "order": order, "owner": creds.api_key, "orderType": order_type.wire() }).to_string();
+        let path = "/order";
+        let timestamp = unix_secs().to_string();
+
+        let mut req = self.http.post(format!("{}{}", self.base_url, path))
+            .header("Content-Type", "application/json")
+            .body(body.clone());
+        for (k, v) in l2_headers(&self.wallet.address_hex(), creds, &timestamp, "POST", path, &body)? {
+            req = req.header(k, v);
+        }
+
+        let resp: OrderResponse = serde_json::from_value(send(path, req).await?).map_err(|e| ExecError::Malformed(e.to_string()))?;
+        if !resp.success {
+            return Err(rejection(resp.error_msg));
+        }
+        Ok(resp)
+    }
+
+    // Whether a token trades on the neg-risk exchange, and the fee rate its
+    // orders must carry. Both are public endpoints.
+    pub async fn market_info(&self, token_id: &str) -> Result<MarketInfo, ExecError> {
+        let neg = send("/neg-risk", self.http.get(format!("{}/neg-risk?token_id={}", self.base_url, token_id))).await?;
+        let fee = send("/fee-rate", self.http.get(format!("{}/fee-rate?token_id={}", self.base_url, token_id))).await?;
+        Ok(MarketInfo {
+            neg_risk: neg.get("neg_risk").and_then(Value::as_bool).unwrap_or(false),
+            fee_rate_bps: fee.get("base_fee").and_then(Value::as_u64).unwrap_or(0),
+        })
+    }
+
+    // build_order + post_order
+    pub async fn place(&self, args: &OrderArgs, order_type: OrderType) -> Result<OrderResponse, ExecError> {
+        let order = self.build_order(args)?;
+        self.post_order(&order, order_type).await
+    }
+
+    // Collateral (USDC) balance of the funder, in dollars. The HMAC covers the
+    // path without its query string.
+    pub async fn usdc_balance(&self) -> Result<f64, ExecError> {
+        let creds = self.creds.as_ref().ok_or_else(|| ExecError::Auth("no API credentials — call derive_api_key first".to_string()))?;
+        let path = "/balance-allowance";
+        let timestamp = unix_secs().to_string();
+
+        let url = format!("{}{}?asset_type=COLLATERAL&signature_type={}", self.base_url, path, self.signature_type);
+        let mut req = self.http.get(url);
+        for (k, v) in l2_headers(&self.wallet.address_hex(), creds, &timestamp, "GET", path, "")? {
+            req = req.header(k, v);
+        }
+
+        let body = send(path, req).await?;
+        let units: f64 = body.get("balance").and_then(Value::as_str).and_then(|b| b.parse().ok())
+            .ok_or_else(|| ExecError::Malformed(format!("no balance in {}", body)))?;
+        Ok(units / 1e6)
+    }
+
+    // Cancels every resting order of this API key; returns the cancelled IDs
+    pub async fn cancel_all(&self) -> Result<Vec<String>, ExecError> {
+        let creds = self.creds.as_ref().ok_or_else(|| ExecError::Auth("no API credentials — call derive_api_key first".to_string()))?;
+        let path = "/cancel-all";
+        let timestamp = unix_secs().to_string();
+
+        let mut req = self.http.delete(format!("{}{}", self.base_url, path));
+        for (k, v) in l2_headers(&self.wallet.address_hex(), creds, &timestamp, "DELETE", path, "")? {
+            req = req.header(k, v);
+        }
+
+        let body = send(path, req).await?;
+        Ok(body.get("canceled").and_then(Value::as_array)
+            .map(|ids| ids.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default())
+    }
+}
+
+// Sends a request and returns the JSON body of a 2xx response. `endpoint`
+// labels the request in metrics.rs.
+async fn send(endpoint: &str, req: reqwest::RequestBuilder) -> Result<Value, ExecError> {
+    let started = Instant::now();
+    let result = send_inner(req).await;
+    metrics().observe_request("clob", endpoint, started, result.is_ok());
+    result
+}
+
+async fn send_inner(req: reqwest::RequestBuilder) -> Result<Value, ExecError> {
+    let resp = req.send().await.map_err(|e| ExecError::Network(e.without_url().to_string()))?;
+    let status = resp.status();
+    let text = resp.text().await.map_err(|e| ExecError::Network(e.without_url().to_string()))?;
+    if !status.is_success() {
+        // The CLOB reports order rejections as 400 with {"errorMsg": ...}
+        let msg = serde_json::from_str::<Value>(&text).ok()
+            .and_then(|v| v.get("errorMsg").or_else(|| v.get("error")).and_then(Value::as_str).map(str::to_string))
+            .unwrap_or(text);
+        return Err(match status.as_u16() {
+            401 | 403 => ExecError::Auth(msg),
+            400       => rejection(msg),
+            code      => ExecError::Http(code, msg),
+        });
+    }
+    serde_json::from_str(&text).map_err(|e| ExecError::Malformed(e.to_string()))
+}
+
+// The CLOB answers an FOK that can't fill completely, or a FAK with nothing
+// to match, with a 400 like any other refusal — only the message tells a
+// harmless kill apart from a real problem (bad signature, balance, tick size)
+fn rejection(msg: String) -> ExecError {
+    let lower = msg.to_lowercase();
+    if lower.contains("fully filled or killed") || lower.contains("killed if no match") {
+        ExecError::Killed(msg)
+    } else {
+        ExecError::Rejected(msg)
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Unique per order — only has to differ between orders with equal fields
+fn salt() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    (nanos as u64) & ((1u64 << 53) - 1) // stays exact as a JSON number
+}
+
+// Known-answer vectors for the hardhat/anvil account #0 key, computed outside
+// this crate (pure-Python keccak-256, EIP-712 encoding and RFC 6979 secp256k1
+// with low-s), so a bug shared by the signer and recover_address can't hide.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    const ADDRESS: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+
+    fn wallet() -> Wallet {
+        Wallet::from_hex(KEY).expect("valid key")
+    }
+
+    #[test]
+    fn keccak_empty_input() {
+        assert_eq!(hex::encode(keccak256(b"")), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+    }
+
+    #[test]
+    fn address_from_key() {
+        assert_eq!(wallet().address_hex(), ADDRESS);
+    }
+
+    #[test]
+    fn order_digest_and_signature() {
+        let order = SignedOrder {
+            salt: 479249096354,
+            maker: ADDRESS.to_string(),
+            signer: ADDRESS.to_string(),
+            taker: checksum_address(&ZERO_ADDRESS),
+            token_id: "71321045679252212594626385532706912750332728571942532289631379312455583992563".to_string(),
+            maker_amount: 50_000_000,
+            taker_amount: 100_000_000,
+            expiration: 0,
+            nonce: 0,
+            fee_rate_bps: 0,
+            side: OrderSide::Buy,
+            signature_type: 0,
+            signature: String::new(),
+        };
+        assert_eq!(hex::encode(order.struct_hash().unwrap()), "3079e442fd3e01761188b94c81755206cd7d4f6784ad2059ae703ffe36c92507");
+        let digest = order.digest(&Eip712Domain::exchange(137, false)).unwrap();
+        assert_eq!(hex::encode(digest), "2d4e37d43ce67ac26fd34fbded7ac34fdcba1b2aff632aac52b36483f1d5eeb8");
+        assert_eq!(
+            signature_hex(&wallet().sign_digest(&digest).unwrap()),
+            "0x4e4a18de9ac827f073445bb64331b74a5f57feed1b86424cfaa61db51ae0c0de291110ad3c3541ac576a93bfd35adca6f9e4861ce3d46123e56eeadf3e55fd0c1c"
+        );
+    }
+
+    #[test]
+    fn clob_auth_digest_and_signature() {
+        let digest = clob_auth_digest(137, &wallet().address(), "10000000", 23);
+        assert_eq!(hex::encode(digest), "577908bf2b712def8592d5078487f59ab9aa28b656e6fda011c8ac4333792037");
+        assert_eq!(
+            signature_hex(&wallet().sign_digest(&digest).unwrap()),
+            "0x1a7118db6100dfd8efd102be36f472b59475dcac56eb4c9a2a94748d3655ba7c3c89deb8c19ee79eceb0a531122fbfbe88ed118034f9d8212e2b725e7b296b9d1c"
+        );
+    }
+
+    #[test]
+    fn kill_messages_are_not_rejections() {
+        assert!(matches!(rejection("order couldn't be fully filled. FOK orders are fully filled or killed.".to_string()), ExecError::Killed(_)));
+        assert!(matches!(rejection("no orders found to match with FAK order. FAK orders are partially filled or killed if no match is found.".to_string()), ExecError::Killed(_)));
+        assert!(matches!(rejection("not enough balance / allowance".to_string()), ExecError::Rejected(_)));
+    }
+}
@@ -0,0 +1,343 @@
+// executor.rs — multi-leg arb execution with leg-failure handling
+//
+// An arb is only riskless if every leg fills. The executor sends one FOK buy
+// per leg, all at once, at the limit price the arb engine worked out for that
+// leg. Then it compares what actually filled:
+//   - every leg filled the same size → done, we hold complete sets
+//   - anything else → some shares are "excess": bought on one leg with no
+//     matching shares on the others, i.e. a directional bet we never wanted
+//
+// Excess is handled by the configured policy (config.exec_partial_policy):
+//   unwind — sell the excess back (IOC) at no worse than fill price − slippage
+//   hedge  — first try to buy the missing legs (IOC) at up to their limit +
+//            slippage to complete the sets; whatever still can't be matched is
+//            unwound as above
+//   hold   — keep the excess and just report it
+//
+// Every step is reported to Telegram as it happens. Market info (neg-risk
+// exchange, fee rate) is fetched once per token and cached.
+//
+// Sizing and the kill switch come from risk.rs: every order checks the switch
+// right before it is sent, and the background task only executes what the
+// risk limits allow.
+
+use crate::arb::{ArbEvent, ArbOpportunity};
+use crate::exec::{ClobClient, ExecError, MarketInfo, OrderArgs, OrderSide, OrderType};
+use crate::fetch::Config;
+use crate::portfolio::Portfolio;
+use crate::risk::{KillSwitch, RiskManager};
+use crate::telegram::TgQueue;
+use chrono::Utc;
+use futures::future::join_all;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PartialPolicy {
+    #[default]
+    Unwind,
+    Hedge,
+    Hold,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ExecSettings {
+    pub policy: PartialPolicy,
+    pub unwind_slippage: f64, // max price we give up per share when selling excess
+    pub hedge_slippage: f64,  // max price we add per share when completing a set
+    pub max_signal_age: Duration, // older opportunities are skipped, not traded
+}
+
+impl ExecSettings {
+    pub fn from_config(config: &Config) -> ExecSettings {
+        ExecSettings {
+            policy: config.exec_partial_policy,
+            unwind_slippage: config.exec_unwind_slippage,
+            hedge_slippage: config.exec_hedge_slippage,
+            max_signal_age: Duration::from_millis(config.exec_max_signal_age_ms),
+        }
+    }
+}
+
+// ================================================================================
+// RESULTS
+// ================================================================================
+#[derive(Debug, Clone)]
+pub struct LegFill {
+    pub asset_id: String,
+    pub outcome: String,
+    pub market: String,  // condition id, from the arb group
+    pub limit: f64,
+    pub requested: f64,
+    pub filled: f64,     // shares bought, after any hedge
+    pub cost: f64,       // USDC paid for them
+    pub unwound: f64,    // excess shares sold back
+    pub proceeds: f64,   // USDC received for them
+    pub error: Option<String>,
+}
+
+impl LegFill {
+    pub fn avg_price(&self) -> f64 {
+        if self.filled > 0.0 { self.cost / self.filled } else { 0.0 }
+    }
+
+    // Shares still held
+    pub fn held(&self) -> f64 {
+        self.filled - self.unwound
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecOutcome {
+    Complete,  // all legs filled equally
+    Unwound,   // legs didn't match; excess sold back, no directional exposure left
+    Exposed,   // directional shares still held (hold policy, or unwind fell short)
+    NoFill,    // nothing filled at all
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecReport {
+    pub group_id: String,
+    pub title: String,
+    pub legs: Vec<LegFill>,
+    pub sets: f64,          // complete sets held
+    pub outcome: ExecOutcome,
+    pub steps: Vec<String>, // everything reported along the way
+}
+
+impl ExecReport {
+    // Net USDC spent (buys − unwind proceeds)
+    pub fn net_cost(&self) -> f64 {
+        self.legs.iter().map(|l| l.cost - l.proceeds).sum()
+    }
+}
+
+// ================================================================================
+// EXECUTOR
+// ================================================================================
+pub struct Executor {
+    clob: Arc<ClobClient>,
+    settings: ExecSettings,
+    kill: Arc<KillSwitch>,
+    tg: Option<TgQueue>,
+    info: Mutex<HashMap<String, MarketInfo>>, // token → neg-risk / fee rate
+}
+
+impl Executor {
+    pub fn new(clob: Arc<ClobClient>, settings: ExecSettings, kill: Arc<KillSwitch>, tg: Option<TgQueue>) -> Executor {
+        Executor { clob, settings, kill, tg, info: Mutex::new(HashMap::new()) }
+    }
+
+    fn report(&self, steps: &mut Vec<String>, text: String) {
+        info!(step = %text, "execution");
+        if let Some(tg) = &self.tg { tg.broadcast(text.clone()); }
+        steps.push(text);
+    }
+
+    async fn market_info(&self, token_id: &str) -> Result<MarketInfo, ExecError> {
+        if let Some(info) = self.info.lock().unwrap().get(token_id) {
+            return Ok(*info);
+        }
+        let info = self.clob.market_info(token_id).await?;
+        self.info.lock().unwrap().insert(token_id.to_string(), info);
+        Ok(info)
+    }
+
+    // One order; Ok((shares, usdc)) filled. Only the CLOB's no-match kill of
+    // an FOK/IOC is Ok((0, 0)) — every other rejection is an error on the leg.
+    async fn order(&self, token_id: &str, side: OrderSide, price: f64, size: f64, order_type: OrderType) -> Result<(f64, f64), ExecError> {
+        if self.kill.is_engaged() {
+            return Err(ExecError::Halted(self.kill.reason()));
+        }
+        let info = self.market_info(token_id).await?;
+        let args = OrderArgs {
+            token_id: token_id.to_string(),
+            side,
+            price: price.clamp(0.0001, 0.9999),
+            size,
+            fee_rate_bps: info.fee_rate_bps,
+            neg_risk: info.neg_risk,
+        };
+        match self.clob.place(&args, order_type).await {
+            Ok(resp) => {
+                let shares: f64 = resp.filled_shares(side);
+                let usdc: f64 = match side {
+                    OrderSide::Buy  => resp.making_amount.parse().unwrap_or(0.0),
+                    OrderSide::Sell => resp.taking_amount.parse().unwrap_or(0.0),
+                };
+                Ok((shares, usdc))
+            }
+            Err(ExecError::Killed(_)) if order_type != OrderType::Gtc => Ok((0.0, 0.0)),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Buys `sets` of every leg of the opportunity and deals with any mismatch
+    pub async fn execute(&self, opp: &ArbOpportunity, sets: f64) -> ExecReport {
+        let mut steps = Vec::new();
+        self.report(&mut steps, format!(
+            "⚙️ Executing {} [{}] — {} legs × {:.0} sets, expected profit ${:.2}",
+            opp.title, opp.kind.strategy(), opp.legs.len(), sets, opp.expected_profit * sets / opp.fill_size
+        ));
+
+        // ── 1. All legs at once ──
+        let results = join_all(opp.legs.iter().map(|leg| self.order(&leg.asset_id, OrderSide::Buy, leg.worst_price, sets, OrderType::Fok))).await;
+        let mut legs: Vec<LegFill> = opp.legs.iter().zip(results).map(|(leg, r)| {
+            let (filled, cost, error) = match r {
+                Ok((s, c)) => (s, c, None),
+                Err(e) => (0.0, 0.0, Some(e.to_string())),
+            };
+            LegFill {
+                asset_id: leg.asset_id.clone(),
+                outcome: leg.outcome.clone(),
+                market: leg.market.clone(),
+                limit: leg.worst_price,
+                requested: sets,
+                filled,
+                cost,
+                unwound: 0.0,
+                proceeds: 0.0,
+                error,
+            }
+        }).collect();
+        for l in &legs {
+            let line = match &l.error {
+                Some(e) => format!("❌ {} — error: {}", l.outcome, e),
+                None if l.filled > 0.0 => format!("✅ {} — {:.2} @ {:.3}", l.outcome, l.filled, l.avg_price()),
+                None => format!("❌ {} — killed (nothing at ≤ {:.3})", l.outcome, l.limit),
+            };
+            self.report(&mut steps, line);
+        }
+
+        // ── 2. Complete? ──
+        let max = legs.iter().map(|l| l.filled).fold(0.0, f64::max);
+        if max <= 0.0 {
+            self.report(&mut steps, "Nothing filled — no position taken".to_string());
+            return self.finish(opp, legs, ExecOutcome::NoFill, steps);
+        }
+        if legs.iter().all(|l| (l.filled - max).abs() < 1e-6) {
+            self.report(&mut steps, format!("✅ All legs filled — {:.2} complete sets", max));
+            return self.finish(opp, legs, ExecOutcome::Complete, steps);
+        }
+
+        // ── 3. Hedge: try to bring every leg up to the largest fill ──
+        if self.settings.policy == PartialPolicy::Hedge {
+            for l in legs.iter_mut() {
+                let missing = max - l.filled;
+                if missing < 0.01 { continue; }
+                let limit = l.limit + self.settings.hedge_slippage;
+                match self.order(&l.asset_id, OrderSide::Buy, limit, missing, OrderType::Ioc).await {
+                    Ok((s, c)) => {
+                        l.filled += s;
+                        l.cost += c;
+                        let text = format!("🛠 Hedge {} — bought {:.2} of {:.2} at ≤ {:.3}", l.outcome, s, missing, limit);
+                        self.report(&mut steps, text);
+                    }
+                    Err(e) => self.report(&mut steps, format!("🛠 Hedge {} failed: {}", l.outcome, e)),
+                }
+            }
+        }
+
+        // ── 4. Excess over the smallest leg is directional ──
+        let min = legs.iter().map(|l| l.filled).fold(f64::INFINITY, f64::min);
+        if legs.iter().all(|l| l.filled - min < 0.01) {
+            self.report(&mut steps, format!("✅ Hedged — {:.2} complete sets", min));
+            return self.finish(opp, legs, ExecOutcome::Complete, steps);
+        }
+
+        if self.settings.policy == PartialPolicy::Hold {
+            let excess: Vec<String> = legs.iter().filter(|l| l.filled - min >= 0.01)
+                .map(|l| format!("{} {:.2}", l.outcome, l.filled - min)).collect();
+            self.report(&mut steps, format!("⚠️ Holding unhedged shares: {}", excess.join(", ")));
+            return self.finish(opp, legs, ExecOutcome::Exposed, steps);
+        }
+
+        // ── 5. Unwind the excess ──
+        for l in legs.iter_mut() {
+            let excess = ((l.filled - min) * 100.0).floor() / 100.0;
+            if excess < 0.01 { continue; }
+            let floor = l.avg_price() - self.settings.unwind_slippage;
+            match self.order(&l.asset_id, OrderSide::Sell, floor, excess, OrderType::Ioc).await {
+                Ok((s, usdc)) => {
+                    l.unwound += s;
+                    l.proceeds += usdc;
+                    let text = format!("↩️ Unwind {} — sold {:.2} of {:.2} at ≥ {:.3} for ${:.2}", l.outcome, s, excess, floor, usdc);
+                    self.report(&mut steps, text);
+                }
+                Err(e) => self.report(&mut steps, format!("↩️ Unwind {} failed: {}", l.outcome, e)),
+            }
+        }
+
+        let exposed = legs.iter().any(|l| l.held() - min >= 0.01);
+        let outcome = if exposed { ExecOutcome::Exposed } else { ExecOutcome::Unwound };
+        if exposed {
+            self.report(&mut steps, "⚠️ Unwind incomplete — unhedged shares still held".to_string());
+        }
+        self.finish(opp, legs, outcome, steps)
+    }
+
+    fn finish(&self, opp: &ArbOpportunity, legs: Vec<LegFill>, outcome: ExecOutcome, mut steps: Vec<String>) -> ExecReport {
+        let sets = legs.iter().map(|l| l.held()).fold(f64::INFINITY, f64::min).max(0.0);
+        let report = ExecReport { group_id: opp.group_id.clone(), title: opp.title.clone(), legs, sets, outcome, steps: vec![] };
+        self.report(&mut steps, format!(
+            "Done: {:?} — {:.2} sets held, net cost ${:.2}", outcome, sets, report.net_cost()
+        ));
+        ExecReport { steps, ..report }
+    }
+}
+
+// ── Background task ───────────────────────────────────────────────────────────
+// Executes the first Open of every episode, sized to what the risk limits
+// allow. One execution at a time — legs of different arbs never interleave.
+// Fills go into the portfolio, which is saved after each execution.
+//
+// An Open that waited in the queue while an earlier execution ran is skipped
+// once it's older than max_signal_age (the prices behind it are gone); a
+// skipped Open doesn't end the episode, the next fresh one still trades. If
+// events are lost to lag, the episode set is cleared so a lost Closed can't
+// block a group for good.
+pub fn spawn(mut arbs: broadcast::Receiver<ArbEvent>, executor: Executor, risk: Arc<RiskManager>, portfolio: Arc<Mutex<Portfolio>>) {
+    tokio::spawn(async move {
+        let mut episodes: HashSet<String> = HashSet::new();
+        loop {
+            let opp = match arbs.recv().await {
+                Ok(ArbEvent::Open(opp)) => opp,
+                Ok(ArbEvent::Closed(rec)) => { episodes.remove(&rec.group_id); continue; }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(skipped = n, "fell behind the arb engine, forgetting episodes");
+                    episodes.clear();
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            if episodes.contains(&opp.group_id) { continue; }
+
+            let age = (Utc::now() - opp.detected_at).to_std().unwrap_or_default();
+            if age > executor.settings.max_signal_age {
+                info!(group_id = %opp.group_id, age_ms = age.as_millis() as u64, "skipping stale opportunity");
+                continue;
+            }
+
+            let sets = match risk.allowed_sets(&opp) {
+                Ok(sets) => sets,
+                Err(why) => {
+                    info!(group_id = %opp.group_id, reason = %why, "risk limits skip opportunity");
+                    continue;
+                }
+            };
+            // Only a traded Open ends the episode; a stale or blocked one
+            // leaves the next re-price free to go
+            episodes.insert(opp.group_id.clone());
+            let report = executor.execute(&opp, sets).await;
+            risk.record(&opp, &report);
+            let mut portfolio = portfolio.lock().unwrap();
+            portfolio.record(&report);
+            portfolio.save();
+        }
+    });
+}
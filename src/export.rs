@@ -0,0 +1,151 @@
+// export.rs — flat CSV / Parquet export of fetch reports
+//
+// The JSON report is nested event → market → side. For pandas and spreadsheets
+// it is flattened to one row per outcome:
+//
+//   event_id, title, slug, end_time_utc, question, outcome, token_id,
+//   best_bid, best_bid_size, best_ask, best_ask_size
+//
+// Prices and sizes are numbers (empty / null when the book side is empty).
+// Files are written with output::write_atomic, next to the JSON snapshot when
+// run from run_fetch (config.export_formats), or wherever the export binary
+// is pointed.
+
+use crate::output::write_atomic;
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Option<ExportFormat> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "parquet" => Some(ExportFormat::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportRow {
+    pub event_id: String,
+    pub title: String,
+    pub slug: String,
+    pub end_time_utc: String,
+    pub question: String,
+    pub outcome: String,
+    pub token_id: String,
+    pub best_bid: Option<f64>,
+    pub best_bid_size: Option<f64>,
+    pub best_ask: Option<f64>,
+    pub best_ask_size: Option<f64>,
+}
+
+// One row per side of every market entry in a report
+pub fn rows_from_report(report: &[Value]) -> Vec<ExportRow> {
+    let s = |v: &Value, k: &str| v.get(k).and_then(Value::as_str).unwrap_or("").to_string();
+    let n = |v: &Value, k: &str| v.get(k).and_then(Value::as_str).and_then(|x| x.parse().ok());
+
+    let mut rows = Vec::new();
+    for event in report {
+        for market in event.get("market_entries").and_then(Value::as_array).into_iter().flatten() {
+            for side in market.get("sides").and_then(Value::as_array).into_iter().flatten() {
+                rows.push(ExportRow {
+                    event_id: s(event, "id"),
+                    title: s(event, "title"),
+                    slug: s(event, "slug"),
+                    end_time_utc: s(event, "endDateUTC"),
+                    question: s(market, "question"),
+                    outcome: s(side, "outcome"),
+                    token_id: s(side, "token_id"),
+                    best_bid: n(side, "best_bid"),
+                    best_bid_size: n(side, "best_bid_size"),
+                    best_ask: n(side, "best_ask"),
+                    best_ask_size: n(side, "best_ask_size"),
+                });
+            }
+        }
+    }
+    rows
+}
+
+// ================================================================================
+// ENCODERS
+// ================================================================================
+pub fn to_csv(rows: &[ExportRow]) -> Result<Vec<u8>, String> {
+    let mut w = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        w.serialize(row).map_err(|e| e.to_string())?;
+    }
+    w.into_inner().map_err(|e| e.to_string())
+}
+
+pub fn to_parquet(rows: &[ExportRow]) -> Result<Vec<u8>, String> {
+    let text = |name: &str| Field::new(name, DataType::Utf8, false);
+    let num = |name: &str| Field::new(name, DataType::Float64, true);
+    let schema = Arc::new(Schema::new(vec![
+        text("event_id"), text("title"), text("slug"), text("end_time_utc"),
+        text("question"), text("outcome"), text("token_id"),
+        num("best_bid"), num("best_bid_size"), num("best_ask"), num("best_ask_size"),
+    ]));
+
+    let strings = |f: fn(&ExportRow) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(rows.iter().map(f)))
+    };
+    let floats = |f: fn(&ExportRow) -> Option<f64>| -> ArrayRef {
+        Arc::new(rows.iter().map(f).collect::<Float64Array>())
+    };
+    let columns = vec![
+        strings(|r| &r.event_id),
+        strings(|r| &r.title),
+        strings(|r| &r.slug),
+        strings(|r| &r.end_time_utc),
+        strings(|r| &r.question),
+        strings(|r| &r.outcome),
+        strings(|r| &r.token_id),
+        floats(|r| r.best_bid),
+        floats(|r| r.best_bid_size),
+        floats(|r| r.best_ask),
+        floats(|r| r.best_ask_size),
+    ];
+    let batch = RecordBatch::try_new(schema.clone(), columns).map_err(|e| e.to_string())?;
+
+    let mut buf = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buf, schema, None).map_err(|e| e.to_string())?;
+    writer.write(&batch).map_err(|e| e.to_string())?;
+    writer.close().map_err(|e| e.to_string())?;
+    Ok(buf)
+}
+
+// Writes `<stem>.<ext>` for every format; returns the paths written
+pub fn export_report(report: &[Value], formats: &[ExportFormat], stem: &Path) -> Result<Vec<String>, String> {
+    let rows = rows_from_report(report);
+    let mut written = Vec::new();
+    for format in formats {
+        let bytes = match format {
+            ExportFormat::Csv => to_csv(&rows)?,
+            ExportFormat::Parquet => to_parquet(&rows)?,
+        };
+        let path = stem.with_extension(format.extension());
+        write_atomic(&path, &bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+        written.push(path.display().to_string());
+    }
+    Ok(written)
+}
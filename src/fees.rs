@@ -0,0 +1,188 @@
+// fees.rs — trading cost model used to judge whether an arb is worth taking
+//
+// Three kinds of cost, all configurable under "fees" in config.json:
+//   - taker fee on every leg we buy, either a flat bps of notional or
+//     Polymarket's curve (bps × min(price, 1 − price) per share), which is
+//     cheapest near 0 and 1 and most expensive at 0.50
+//   - winner fee, a cut of the profit on whichever leg pays out
+//   - a flat gas / relayer estimate per order submitted
+//
+// Individual events or markets can override the fee fields by id:
+//   "overrides": { "<event_id>": { "taker_fee_bps": 200 },
+//                  "<condition_id>": { "taker_schedule": "polymarket" } }
+// A market's override covers the groups that trade only that market (both
+// sides of it); all-Yes / all-No groups span several markets and take the
+// event's.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TakerSchedule {
+    #[default]
+    Flat,       // bps × price × shares
+    Polymarket, // bps × min(price, 1 − price) × shares
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FeeOverride {
+    pub taker_fee_bps: Option<f64>,
+    pub taker_schedule: Option<TakerSchedule>,
+    pub winner_fee_pct: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FeeConfig {
+    #[serde(default)]
+    pub taker_fee_bps: f64,
+    #[serde(default)]
+    pub taker_schedule: TakerSchedule,
+    #[serde(default)]
+    pub winner_fee_pct: f64,       // percent of the winning leg's profit, e.g. 2.0
+    #[serde(default)]
+    pub gas_usdc_per_order: f64,
+    #[serde(default)]
+    pub overrides: HashMap<String, FeeOverride>, // keyed by event id, condition id or group id
+}
+
+impl FeeConfig {
+    // Resolves the model for one arb group from its id and the condition ids
+    // of its legs. Group ids look like "<event_id>:<question>", so an override
+    // on the event id covers every group in that event; one on the market's
+    // condition id wins over it, and one on the full group id wins over both.
+    pub fn for_group<'a>(&self, group_id: &str, markets: impl IntoIterator<Item = &'a str>) -> FeeModel {
+        let mut model = FeeModel {
+            taker_fee_bps: self.taker_fee_bps,
+            taker_schedule: self.taker_schedule,
+            winner_fee_pct: self.winner_fee_pct,
+            gas_usdc_per_order: self.gas_usdc_per_order,
+        };
+        let event_id = group_id.split(':').next().unwrap_or(group_id);
+        let mut markets = markets.into_iter();
+        let first = markets.next().filter(|m| !m.is_empty());
+        let market = first.filter(|m| markets.all(|other| other == *m));
+        for key in [Some(event_id), market, Some(group_id)].into_iter().flatten() {
+            if let Some(o) = self.overrides.get(key) {
+                if let Some(v) = o.taker_fee_bps  { model.taker_fee_bps = v; }
+                if let Some(v) = o.taker_schedule { model.taker_schedule = v; }
+                if let Some(v) = o.winner_fee_pct { model.winner_fee_pct = v; }
+            }
+        }
+        model
+    }
+}
+
+// ================================================================================
+// FEE MODEL
+// Resolved costs for one group — plain numbers, no lookups.
+// ================================================================================
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeeModel {
+    pub taker_fee_bps: f64,
+    pub taker_schedule: TakerSchedule,
+    pub winner_fee_pct: f64,
+    pub gas_usdc_per_order: f64,
+}
+
+impl FeeModel {
+    // USDC taker fee for buying `shares` at `price`
+    pub fn taker_fee(&self, price: f64, shares: f64) -> f64 {
+        let rate = self.taker_fee_bps / 10_000.0;
+        match self.taker_schedule {
+            TakerSchedule::Flat       => rate * price * shares,
+            TakerSchedule::Polymarket => rate * price.min(1.0 - price) * shares,
+        }
+    }
+
+    // Worst-case winner fee per full set when `winners` legs pay out. Each
+    // winning leg's profit is 1 − price; the worst case is the `winners`
+    // cheapest legs winning, since they carry the biggest profit.
+    pub fn winner_fee_per_set(&self, prices: &[f64], winners: usize) -> f64 {
+        let mut profits: Vec<f64> = prices.iter().map(|p| 1.0 - p).collect();
+        profits.sort_by(|a, b| b.total_cmp(a));
+        self.winner_fee_pct / 100.0 * profits.iter().take(winners).sum::<f64>()
+    }
+
+    // All per-set costs of buying one share of every leg at these prices
+    pub fn fees_per_set(&self, prices: &[f64], winners: usize) -> f64 {
+        prices.iter().map(|p| self.taker_fee(*p, 1.0)).sum::<f64>() + self.winner_fee_per_set(prices, winners)
+    }
+
+    // Fixed cost of submitting one order per leg
+    pub fn gas(&self, legs: usize) -> f64 {
+        self.gas_usdc_per_order * legs as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn near(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn model(taker_fee_bps: f64, taker_schedule: TakerSchedule, winner_fee_pct: f64) -> FeeModel {
+        FeeModel { taker_fee_bps, taker_schedule, winner_fee_pct, gas_usdc_per_order: 0.0 }
+    }
+
+    #[test]
+    fn taker_fee_schedules() {
+        let flat = model(100.0, TakerSchedule::Flat, 0.0);
+        assert!(near(flat.taker_fee(0.40, 10.0), 0.04));
+        assert!(near(flat.taker_fee(0.80, 10.0), 0.08));
+
+        // Priced on the cheaper side of the share: highest at 0.50
+        let curve = model(100.0, TakerSchedule::Polymarket, 0.0);
+        assert!(near(curve.taker_fee(0.50, 10.0), 0.05));
+        assert!(near(curve.taker_fee(0.80, 10.0), 0.02));
+        assert!(near(curve.taker_fee(0.20, 10.0), 0.02));
+    }
+
+    #[test]
+    fn winner_fee_assumes_the_cheapest_legs_win() {
+        let m = model(0.0, TakerSchedule::Flat, 2.0);
+        // One winner: the 0.30 leg's 0.70 profit
+        assert!(near(m.winner_fee_per_set(&[0.60, 0.30], 1), 0.014));
+        // Two winners of three: 0.70 + 0.50
+        assert!(near(m.winner_fee_per_set(&[0.60, 0.30, 0.50], 2), 0.024));
+        assert_eq!(m.winner_fee_per_set(&[0.60, 0.30], 0), 0.0);
+
+        let both = model(100.0, TakerSchedule::Flat, 2.0);
+        assert!(near(both.fees_per_set(&[0.60, 0.30], 1), 0.009 + 0.014));
+    }
+
+    #[test]
+    fn overrides_by_event_then_group() {
+        let config: FeeConfig = serde_json::from_value(json!({
+            "taker_fee_bps": 100,
+            "winner_fee_pct": 2.0,
+            "gas_usdc_per_order": 0.01,
+            "overrides": {
+                "1001": { "taker_fee_bps": 200, "taker_schedule": "polymarket" },
+                "0xdraw": { "taker_fee_bps": 50, "winner_fee_pct": 1.0 },
+                "1001:Who wins?": { "taker_fee_bps": 0 },
+            },
+        })).expect("fee config");
+
+        let other = config.for_group("2002:Who wins?", ["0xother", "0xother"]);
+        assert_eq!((other.taker_fee_bps, other.taker_schedule), (100.0, TakerSchedule::Flat));
+
+        let event = config.for_group("1001:Will Home win?", ["0xhome", "0xhome"]);
+        assert_eq!((event.taker_fee_bps, event.taker_schedule, event.winner_fee_pct), (200.0, TakerSchedule::Polymarket, 2.0));
+
+        // The market's condition id wins over its event
+        let market = config.for_group("1001:Draw?", ["0xdraw", "0xdraw"]);
+        assert_eq!((market.taker_fee_bps, market.taker_schedule, market.winner_fee_pct), (50.0, TakerSchedule::Polymarket, 1.0));
+
+        // A group across several markets only takes the event's
+        let n_way = config.for_group("1001:3-way", ["0xhome", "0xdraw", "0xaway"]);
+        assert_eq!((n_way.taker_fee_bps, n_way.winner_fee_pct), (200.0, 2.0));
+
+        let group = config.for_group("1001:Who wins?", ["0xdraw", "0xdraw"]);
+        assert_eq!((group.taker_fee_bps, group.taker_schedule, group.winner_fee_pct), (0.0, TakerSchedule::Polymarket, 1.0));
+        assert!(near(group.gas(3), 0.03));
+    }
+}
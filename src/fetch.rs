@@ -1,12 +1,18 @@
 // fetch.rs — parallel fetching helpers for Polymarket bot
 
+use crate::executor::PartialPolicy;
+use crate::fees::FeeConfig;
+use crate::metrics::metrics;
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::Value;
 use chrono::{DateTime, Utc, Duration};
 use futures::future::join_all;
 use chrono_tz::Pacific::Honolulu;
+use std::collections::BTreeMap;
 use std::fs;
+use std::time::Instant;
+use tracing::{debug, info_span, instrument, warn, Instrument};
 
 // ================================================================================
 // CONFIG
@@ -20,8 +26,235 @@ pub struct Config {
     pub pool_max_idle_per_host: usize,
     pub request_timeout_secs: u64,
     pub tag_ids: Vec<String>,
+
+    // ── Telegram send queue (see telegram.rs) ─────────────────────────────────
+    // Telegram allows ~30 msgs/sec across all chats and ~1 msg/sec into a
+    // single chat. Anything over that comes back as a 429.
+    #[serde(default = "default_tg_global_per_sec")]
+    pub tg_global_per_sec: usize,
+    #[serde(default = "default_tg_per_chat_interval_ms")]
+    pub tg_per_chat_interval_ms: u64,
+    // Oldest queued messages for a chat are dropped beyond this many
+    #[serde(default = "default_tg_max_pending_per_chat")]
+    pub tg_max_pending_per_chat: usize,
+    // Messages that sat in the queue longer than this are reported as delayed
+    #[serde(default = "default_tg_delay_warn_secs")]
+    pub tg_delay_warn_secs: u64,
+    // Broadcast chat list — chats are disabled here when they block the bot
+    #[serde(default = "default_subscribers_path")]
+    pub subscribers_path: String,
+    // Chats besides chat_id that may /start and query positions
+    #[serde(default)]
+    pub tg_allowed_chat_ids: Vec<String>,
+
+    // ── Logging (see logging.rs) ──────────────────────────────────────────────
+    // EnvFilter directives; RUST_LOG overrides
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    // "text" or "json"
+    #[serde(default = "default_log_format")]
+    pub log_format: String,
+
+    // ── Metrics (see metrics.rs) ──────────────────────────────────────────────
+    // Address for the Prometheus /metrics endpoint, e.g. "127.0.0.1:9464";
+    // empty disables it
+    #[serde(default)]
+    pub metrics_addr: String,
+
+    // ── WebSocket connection (see ws.rs) ──────────────────────────────────────
+    // Reconnect delay doubles from initial up to max, with jitter
+    #[serde(default = "default_ws_backoff_initial_ms")]
+    pub ws_backoff_initial_ms: u64,
+    #[serde(default = "default_ws_backoff_max_secs")]
+    pub ws_backoff_max_secs: u64,
+    #[serde(default = "default_ws_ping_interval_secs")]
+    pub ws_ping_interval_secs: u64,
+    // Reconnect if the socket is open but no market data (PONGs don't count)
+    // has arrived for this long
+    #[serde(default = "default_ws_stale_timeout_secs")]
+    pub ws_stale_timeout_secs: u64,
+    // Push stale/reconnect notices to Telegram subscribers
+    #[serde(default)]
+    pub ws_lifecycle_alerts: bool,
+    // Subscriptions are spread over up to this many connections
+    #[serde(default = "default_ws_max_shards")]
+    pub ws_max_shards: usize,
+    #[serde(default = "default_ws_max_assets_per_shard")]
+    pub ws_max_assets_per_shard: usize,
+    // Record every raw message to <dir>/ws_<timestamp>.ndjson.gz for replay
+    // (see recording.rs); "" = off
+    #[serde(default)]
+    pub ws_record_dir: String,
+
+    // ── Arbitrage (see arb.rs) ────────────────────────────────────────────────
+    // Flag a group when the best asks across all its outcomes sum below this
+    #[serde(default = "default_arb_threshold")]
+    pub arb_threshold: f64,
+    // Only report opportunities whose profit at max fillable size, after fees
+    // and gas, is at least this much
+    #[serde(default = "default_arb_min_profit_usdc")]
+    pub arb_min_profit_usdc: f64,
+    #[serde(default)]
+    pub fees: FeeConfig,
+    // Push opportunities to Telegram subscribers (see alerts.rs)
+    #[serde(default)]
+    pub arb_alerts: bool,
+    // Don't alert the same group again within this many seconds of its last alert
+    #[serde(default = "default_arb_alert_cooldown_secs")]
+    pub arb_alert_cooldown_secs: u64,
+    // JSONL history of closed opportunities, read by /stats and arb_report (see stats.rs)
+    #[serde(default = "default_arb_log_path")]
+    pub arb_log_path: String,
+
+    // ── Paper trading (see paper.rs) ──────────────────────────────────────────
+    #[serde(default)]
+    pub paper_trading: bool,
+    // Simulated delay between detection and our orders reaching the book
+    #[serde(default = "default_paper_latency_ms")]
+    pub paper_latency_ms: u64,
+    // Cap on USDC spent per simulated trade, across all legs
+    #[serde(default = "default_paper_max_notional_usdc")]
+    pub paper_max_notional_usdc: f64,
+    #[serde(default = "default_paper_summary_path")]
+    pub paper_summary_path: String,
+
+    // ── Backtesting (see backtest.rs) ─────────────────────────────────────────
+    // Strategy grid; empty = just the live arb_threshold / paper_latency_ms
+    #[serde(default)]
+    pub backtest_thresholds: Vec<f64>,
+    #[serde(default)]
+    pub backtest_latencies_ms: Vec<u64>,
+    // Named fee models, same shape as "fees"; empty = just "fees"
+    #[serde(default)]
+    pub backtest_fee_models: BTreeMap<String, FeeConfig>,
+    // Per-opportunity CSV; the summary goes next to it as <stem>_summary.csv
+    #[serde(default = "default_backtest_results_path")]
+    pub backtest_results_path: String,
+
+    // ── Order execution (see exec.rs) ─────────────────────────────────────────
+    #[serde(default = "default_clob_url")]
+    pub clob_url: String,
+    // Hex secp256k1 key of the signing wallet; empty = execution disabled
+    #[serde(default)]
+    pub exec_private_key: String,
+    #[serde(default = "default_exec_chain_id")]
+    pub exec_chain_id: u64,
+    // 0 = plain wallet (EOA), 1 = Polymarket proxy wallet, 2 = Gnosis safe
+    #[serde(default)]
+    pub exec_signature_type: u8,
+    // Address holding the funds when it isn't the signing wallet (proxy / safe)
+    #[serde(default)]
+    pub exec_funder: String,
+    // Send real orders for detected opportunities (see executor.rs)
+    #[serde(default)]
+    pub exec_live: bool,
+    #[serde(default = "default_exec_max_notional_usdc")]
+    pub exec_max_notional_usdc: f64,
+    // What to do when legs fill unevenly: "unwind", "hedge" or "hold"
+    #[serde(default)]
+    pub exec_partial_policy: PartialPolicy,
+    #[serde(default = "default_exec_unwind_slippage")]
+    pub exec_unwind_slippage: f64,
+    #[serde(default = "default_exec_hedge_slippage")]
+    pub exec_hedge_slippage: f64,
+    // Opportunities detected longer ago than this are not traded
+    #[serde(default = "default_exec_max_signal_age_ms")]
+    pub exec_max_signal_age_ms: u64,
+    // ── Fetch report files (see output.rs) ───────────────────────────────────
+    #[serde(default = "default_output_dir")]
+    pub output_dir: String,
+    // {timestamp} = UTC run time, e.g. 20260118T043000Z
+    #[serde(default = "default_output_filename_template")]
+    pub output_filename_template: String,
+    // Always points at the newest snapshot; empty = none
+    #[serde(default = "default_output_latest_name")]
+    pub output_latest_name: String,
+    // Snapshots kept in output_dir; 0 = keep all
+    #[serde(default = "default_output_keep_last")]
+    pub output_keep_last: usize,
+    // Also write each snapshot as "csv" and / or "parquet" (see export.rs)
+    #[serde(default)]
+    pub export_formats: Vec<String>,
+
+    // ── SQLite store (see db.rs) ──────────────────────────────────────────────
+    // Empty = store disabled
+    #[serde(default = "default_db_path")]
+    pub db_path: String,
+    // Retention per table; 0 = keep forever
+    #[serde(default = "default_db_retention_ws_hours")]
+    pub db_retention_ws_hours: u64,
+    #[serde(default = "default_db_retention_books_days")]
+    pub db_retention_books_days: u64,
+    #[serde(default)]
+    pub db_retention_arbs_days: u64,
+    // WS books are sampled into book_snapshots at most this often per token
+    #[serde(default = "default_db_ws_book_interval_secs")]
+    pub db_ws_book_interval_secs: u64,
+
+    // Positions, balance and P&L of live execution (see portfolio.rs)
+    #[serde(default = "default_portfolio_path")]
+    pub portfolio_path: String,
+
+    // ── Risk limits (see risk.rs) ─────────────────────────────────────────────
+    // exec_max_notional_usdc above is the per-trade limit
+    #[serde(default = "default_risk_max_market_notional_usdc")]
+    pub risk_max_market_notional_usdc: f64,
+    #[serde(default = "default_risk_max_event_notional_usdc")]
+    pub risk_max_event_notional_usdc: f64,
+    #[serde(default = "default_risk_max_daily_notional_usdc")]
+    pub risk_max_daily_notional_usdc: f64,
+    #[serde(default = "default_risk_max_open_positions")]
+    pub risk_max_open_positions: usize,
+    #[serde(default = "default_risk_max_consecutive_failures")]
+    pub risk_max_consecutive_failures: u32,
+    // Creating this file halts all order submission; empty = no file switch
+    #[serde(default = "default_kill_switch_path")]
+    pub kill_switch_path: String,
 }
 
+fn default_tg_global_per_sec() -> usize { 30 }
+fn default_tg_per_chat_interval_ms() -> u64 { 1000 }
+fn default_tg_max_pending_per_chat() -> usize { 50 }
+fn default_tg_delay_warn_secs() -> u64 { 10 }
+fn default_subscribers_path() -> String { "subscribers.json".to_string() }
+fn default_log_level() -> String { "info".to_string() }
+fn default_log_format() -> String { "text".to_string() }
+fn default_ws_backoff_initial_ms() -> u64 { 500 }
+fn default_ws_backoff_max_secs() -> u64 { 60 }
+fn default_ws_ping_interval_secs() -> u64 { 10 }
+fn default_ws_stale_timeout_secs() -> u64 { 30 }
+fn default_ws_max_shards() -> usize { 8 }
+fn default_ws_max_assets_per_shard() -> usize { 100 }
+fn default_arb_threshold() -> f64 { 0.98 }
+fn default_arb_min_profit_usdc() -> f64 { 1.0 }
+fn default_arb_alert_cooldown_secs() -> u64 { 300 }
+fn default_arb_log_path() -> String { "arb_history.jsonl".to_string() }
+fn default_paper_latency_ms() -> u64 { 250 }
+fn default_paper_max_notional_usdc() -> f64 { 100.0 }
+fn default_paper_summary_path() -> String { "paper_summary.json".to_string() }
+fn default_backtest_results_path() -> String { "backtest_results.csv".to_string() }
+fn default_clob_url() -> String { "https://clob.polymarket.com".to_string() }
+fn default_exec_chain_id() -> u64 { 137 }
+fn default_exec_max_notional_usdc() -> f64 { 50.0 }
+fn default_exec_unwind_slippage() -> f64 { 0.05 }
+fn default_exec_hedge_slippage() -> f64 { 0.02 }
+fn default_exec_max_signal_age_ms() -> u64 { 1000 }
+fn default_portfolio_path() -> String { "portfolio.json".to_string() }
+fn default_output_dir() -> String { "events".to_string() }
+fn default_output_filename_template() -> String { "sports_events_{timestamp}.json".to_string() }
+fn default_output_latest_name() -> String { "latest.json".to_string() }
+fn default_output_keep_last() -> usize { 48 }
+fn default_db_path() -> String { "polymarket.db".to_string() }
+fn default_db_retention_ws_hours() -> u64 { 48 }
+fn default_db_retention_books_days() -> u64 { 14 }
+fn default_db_ws_book_interval_secs() -> u64 { 60 }
+fn default_risk_max_market_notional_usdc() -> f64 { 100.0 }
+fn default_risk_max_event_notional_usdc() -> f64 { 200.0 }
+fn default_risk_max_daily_notional_usdc() -> f64 { 500.0 }
+fn default_risk_max_open_positions() -> usize { 10 }
+fn default_risk_max_consecutive_failures() -> u32 { 3 }
+fn default_kill_switch_path() -> String { "KILL".to_string() }
+
 impl Config {
     pub fn load(path: &str) -> Config {
         let contents = fs::read_to_string(path)
@@ -52,6 +285,12 @@ pub fn utc_to_hst(utc_str: &str) -> String {
     hst.format("%B %d, %Y %I:%M %p HST").to_string()
 }
 
+// Hour of day (0-23) in HST, for time-of-day stats
+pub fn hst_hour(utc: &DateTime<Utc>) -> u32 {
+    use chrono::Timelike;
+    utc.with_timezone(&Honolulu).hour()
+}
+
 pub fn now_and_window(hours: i64) -> (DateTime<Utc>, DateTime<Utc>, String) {
     let now = Utc::now();
     let later = now + Duration::hours(hours);
@@ -73,21 +312,31 @@ pub async fn fetch_all_tags(client: &Client, tag_ids: &[&str], now_str: &str) ->
                 now_str, tag_id
             );
             let client = client.clone();
+            let span = info_span!("gamma_tag", tag_id = %tag_id);
             async move {
+                let started = Instant::now();
                 match client.get(&url).send().await {
                     Ok(resp) => match resp.json::<Vec<Value>>().await {
-                        Ok(events) => events,
+                        Ok(events) => {
+                            metrics().observe_request("gamma", "/events", started, true);
+                            metrics().events_fetched.with_label_values(&[tag_id]).inc_by(events.len() as u64);
+                            debug!(events = events.len(), latency_ms = elapsed_ms(started), "fetched tag");
+                            events
+                        }
                         Err(e) => {
-                            eprintln!("Failed to parse events for tag {}: {}", tag_id, e);
+                            metrics().observe_request("gamma", "/events", started, false);
+                            warn!(latency_ms = elapsed_ms(started), error = %e, "failed to parse events");
                             vec![]
                         }
                     },
                     Err(e) => {
-                        eprintln!("Failed to fetch tag {}: {}", tag_id, e);
+                        metrics().observe_request("gamma", "/events", started, false);
+                        warn!(latency_ms = elapsed_ms(started), error = %e, "failed to fetch tag");
                         vec![]
                     }
                 }
             }
+            .instrument(span)
         })
         .collect();
 
@@ -103,8 +352,13 @@ pub async fn fetch_all_tags(client: &Client, tag_ids: &[&str], now_str: &str) ->
 // ================================================================================
 #[derive(Debug)]
 pub struct OrderbookEntry {
+    pub token_id: String,
     pub outcome: String,
     pub best_ask: String,
+    pub best_ask_size: Option<String>,
+    pub best_bid: Option<String>,
+    pub best_bid_size: Option<String>,
+    pub book: Value, // raw /book body, for the SQLite store
 }
 
 pub async fn fetch_orderbooks(
@@ -119,28 +373,52 @@ pub async fn fetch_orderbooks(
             let url = format!("https://clob.polymarket.com/book?token_id={}", token);
             let outcome = outcomes.get(i).cloned().unwrap_or_else(|| "Unknown".to_string());
             let client = client.clone();
+            let span = info_span!("clob_book", token_id = %token);
             async move {
+                let started = Instant::now();
                 let resp = match client.get(&url).send().await {
                     Ok(r) => r,
                     Err(e) => {
-                        eprintln!("Orderbook fetch failed for token {}: {}", token, e);
+                        metrics().observe_request("clob", "/book", started, false);
+                        warn!(latency_ms = elapsed_ms(started), error = %e, "orderbook fetch failed");
                         return None;
                     }
                 };
                 let book: Value = match resp.json().await {
                     Ok(v) => v,
-                    Err(_) => return None,
+                    Err(e) => {
+                        metrics().observe_request("clob", "/book", started, false);
+                        warn!(latency_ms = elapsed_ms(started), error = %e, "failed to parse orderbook");
+                        return None;
+                    }
                 };
-                let best_ask = book
-                    .get("asks")
+                metrics().observe_request("clob", "/book", started, true);
+                debug!(latency_ms = elapsed_ms(started), "fetched orderbook");
+                // Best level is the last one on both sides (bids ascending, asks descending)
+                let best = |side: &str, field: &str| book
+                    .get(side)
                     .and_then(Value::as_array)
                     .and_then(|b| b.last())
-                    .and_then(|b| b.get("price"))
+                    .and_then(|b| b.get(field))
                     .and_then(Value::as_str)
                     .map(str::to_string);
+                let best_ask = best("asks", "price");
+
+                let best_ask_size = best("asks", "size");
+                let best_bid = best("bids", "price");
+                let best_bid_size = best("bids", "size");
 
-                best_ask.map(|ask| OrderbookEntry { outcome, best_ask: ask })
+                best_ask.map(|ask| OrderbookEntry {
+                    token_id: token.clone(),
+                    outcome,
+                    best_ask: ask,
+                    best_ask_size,
+                    best_bid,
+                    best_bid_size,
+                    book,
+                })
             }
+            .instrument(span)
         })
         .collect();
 
@@ -148,6 +426,41 @@ pub async fn fetch_orderbooks(
     join_all(futures).await.into_iter().flatten().collect()
 }
 
+// ================================================================================
+// SINGLE BOOK SNAPSHOT
+// Full REST /book body for one token — same shape as the WS `book` message,
+// so ws.rs can use it to resync a local order book.
+// ================================================================================
+#[instrument(name = "clob_book", skip(client))]
+pub async fn fetch_book(client: &Client, token_id: &str) -> Option<Value> {
+    let url = format!("https://clob.polymarket.com/book?token_id={}", token_id);
+    let started = Instant::now();
+    match client.get(&url).send().await {
+        Ok(resp) => match resp.json::<Value>().await {
+            Ok(book) => {
+                metrics().observe_request("clob", "/book", started, true);
+                debug!(latency_ms = elapsed_ms(started), "fetched book");
+                Some(book)
+            }
+            Err(e) => {
+                metrics().observe_request("clob", "/book", started, false);
+                warn!(latency_ms = elapsed_ms(started), error = %e, "failed to parse book");
+                None
+            }
+        },
+        Err(e) => {
+            metrics().observe_request("clob", "/book", started, false);
+            warn!(latency_ms = elapsed_ms(started), error = %e, "book fetch failed");
+            None
+        }
+    }
+}
+
+// Milliseconds since `started`, for latency_ms fields
+pub fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
 // ================================================================================
 // EVENT FILTERING
 // Pure logic — no I/O. Filters a flat event list down to game events
@@ -217,19 +530,24 @@ pub fn extract_moneyline_markets(event: &Value) -> Vec<(String, Vec<String>, Vec
 }
 
 // ================================================================================
-// TELEGRAM HELPER
-// Thin wrapper so you're not formatting URLs all over main.rs
+// EVENT LEAGUE
+// Best-effort league name for grouping stats: the series slug when Gamma sends
+// one, otherwise the first tag that isn't one of the generic ones
+// (1 = Sports, 100639 = Games). "unknown" if neither is present.
 // ================================================================================
-pub async fn tg_send(client: &Client, bot_token: &str, chat_id: &str, text: &str) {
-    let url = format!(
-        "https://api.telegram.org/bot{}/sendMessage?chat_id={}&text={}",
-        bot_token,
-        chat_id,
-        urlencoding::encode(text)
-    );
-    if let Err(e) = client.get(&url).send().await {
-        eprintln!("Telegram send failed: {}", e);
+pub fn event_league(event: &Value) -> String {
+    if let Some(series) = event.get("seriesSlug").and_then(Value::as_str).filter(|s| !s.is_empty()) {
+        return series.to_string();
     }
+    event
+        .get("tags")
+        .and_then(Value::as_array)
+        .and_then(|tags| tags.iter().find_map(|t| {
+            let id = t.get("id").and_then(Value::as_str)?;
+            if id == "1" || id == "100639" { return None; }
+            t.get("label").and_then(Value::as_str).map(str::to_string)
+        }))
+        .unwrap_or_else(|| "unknown".to_string())
 }
 
 // ================================================================================
@@ -1,4 +1,25 @@
 // lib.rs — exposes internal modules so binaries in src/bin/ can import them.
 // main.rs is a private entry point, so anything src/bin/ needs must come through here.
+pub mod alerts;
+pub mod arb;
+pub mod backtest;
+pub mod book;
+pub mod db;
+pub mod exec;
+pub mod executor;
+pub mod export;
+pub mod fees;
 pub mod fetch;
+pub mod logging;
+pub mod messages;
+pub mod metrics;
+pub mod mock_clob;
+pub mod output;
+pub mod paper;
+pub mod portfolio;
+pub mod recording;
+pub mod risk;
+pub mod stats;
+pub mod tape;
+pub mod telegram;
 pub mod ws;
@@ -0,0 +1,170 @@
+// logging.rs — tracing subscriber setup and secret redaction
+//
+// fetch.rs, ws.rs and the main loop log through `tracing`: spans carry the
+// context (fetch run id, tag id, WS shard, token id) and events carry the
+// numbers (counts, latency_ms), so one fetch run or one shard can be followed
+// by filtering on a field instead of grepping prefixes.
+//
+// Config:
+//   "log_level"  — EnvFilter directives, e.g. "info" or
+//                  "info,latex_telegram_bot::ws=debug"; RUST_LOG overrides it
+//   "log_format" — "text" (human-readable) or "json" (one object per line,
+//                  span fields included)
+//
+// Everything the subscriber writes goes through Redactor, which replaces the
+// bot token — configured value and anything shaped like a Telegram
+// "bot<id>:<secret>" URL segment — with "<redacted>" before it reaches the
+// terminal, so a URL inside an error message can't leak it.
+
+use crate::fetch::Config;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::warn;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
+
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(s: &str) -> Option<LogFormat> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" | "pretty" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogSettings {
+    pub filter: String,
+    pub format: LogFormat,
+    pub secrets: Vec<String>, // redacted from every line
+}
+
+impl LogSettings {
+    pub fn from_config(config: &Config) -> LogSettings {
+        LogSettings {
+            filter: config.log_level.clone(),
+            format: LogFormat::parse(&config.log_format).unwrap_or(LogFormat::Text),
+            secrets: vec![config.bot_token.clone()],
+        }
+    }
+}
+
+// Installs the global subscriber. Safe to call more than once — only the
+// first call takes effect. A bad log_level falls back to "info" and is
+// reported once the subscriber is up.
+pub fn init(settings: &LogSettings) {
+    let mut bad_filter = None;
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&settings.filter))
+        .unwrap_or_else(|e| {
+            bad_filter = Some(e.to_string());
+            EnvFilter::new("info")
+        });
+    let writer = Redactor::new(settings.secrets.clone());
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_writer(writer);
+    let _ = match settings.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).with_current_span(true).with_span_list(false).try_init(),
+    };
+    if let Some(error) = bad_filter {
+        warn!(log_level = %settings.filter, error = %error, "bad log_level, using \"info\"");
+    }
+}
+
+pub fn init_from_config(config: &Config) {
+    init(&LogSettings::from_config(config));
+}
+
+// Short id for one fetch run: ms since epoch in hex plus a process-local
+// counter, so two runs in the same millisecond still differ
+pub fn run_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{:x}-{}", chrono::Utc::now().timestamp_millis(), n)
+}
+
+// ================================================================================
+// REDACTION
+// ================================================================================
+// Replaces every secret, and every Telegram "bot<digits>:<token>" segment,
+// with <redacted>
+pub fn redact(text: &str, secrets: &[String]) -> String {
+    let mut out = text.to_string();
+    for secret in secrets.iter().filter(|s| !s.is_empty()) {
+        out = out.replace(secret.as_str(), REDACTED);
+    }
+    redact_bot_tokens(&out)
+}
+
+// URL-shaped helper for call sites that log a URL on purpose
+pub fn redact_url(url: &str) -> String {
+    redact_bot_tokens(url)
+}
+
+fn redact_bot_tokens(text: &str) -> String {
+    let is_token_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find("/bot") {
+        let (head, tail) = rest.split_at(i + 4); // keep "/bot"
+        out.push_str(head);
+        let digits = tail.len() - tail.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let after = &tail[digits..];
+        let secret = after.strip_prefix(':').map(|s| s.len() - s.trim_start_matches(is_token_char).len()).unwrap_or(0);
+        if digits > 0 && secret > 0 {
+            out.push_str(REDACTED);
+            rest = &after[1 + secret..];
+        } else {
+            rest = tail;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// ── Writer ────────────────────────────────────────────────────────────────────
+// The fmt layer formats each event into a buffer and hands it over in one
+// write, so redacting per write sees whole lines.
+#[derive(Clone)]
+pub struct Redactor {
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(secrets: Vec<String>) -> Redactor {
+        Redactor { secrets: secrets.into_iter().filter(|s| !s.is_empty()).collect() }
+    }
+}
+
+pub struct RedactingStdout<'a> {
+    secrets: &'a [String],
+}
+
+impl Write for RedactingStdout<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        std::io::stdout().lock().write_all(redact(&text, self.secrets).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for Redactor {
+    type Writer = RedactingStdout<'a>;
+
+    fn make_writer(&'a self) -> RedactingStdout<'a> {
+        RedactingStdout { secrets: &self.secrets }
+    }
+}
@@ -3,31 +3,70 @@
 // Add to Cargo.toml:
 // tokio = { version = "1", features = ["full"] }
 // serde = { version = "1", features = ["derive"] }
+//
+// Modules are pulled from the library crate (lib.rs) rather than declared
+// here, so main.rs and src/bin/ share one copy of each.
 
-mod fetch;
-
-use fetch::{
+use latex_telegram_bot::fetch::{
     build_client, fetch_all_tags, fetch_orderbooks,
     filter_game_events, extract_moneyline_markets,
-    now_and_window, utc_to_hst, tg_send, print_event,
+    now_and_window, utc_to_hst, print_event, elapsed_ms,
     Config,
 };
+use latex_telegram_bot::arb::{self, ArbEngine, GroupChange};
+use latex_telegram_bot::db::{self, BookRow, DbWrite, DbWriter};
+use latex_telegram_bot::exec::ClobClient;
+use latex_telegram_bot::export::{export_report, ExportFormat};
+use latex_telegram_bot::{alerts, executor, logging, metrics, paper, portfolio, risk, stats, ws};
+use latex_telegram_bot::output::{save_snapshot, OutputSettings};
+use latex_telegram_bot::portfolio::Portfolio;
+use latex_telegram_bot::risk::{write_kill_file, KillSwitch, RiskManager};
+use latex_telegram_bot::stats::{load_records, StatsReport};
+use latex_telegram_bot::telegram::{ChatAllowlist, Subscribers, TgLimits, TgQueue};
+use chrono::Utc;
 use serde_json::Value;
-use std::fs::{self, write};
-use tokio::sync::mpsc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
 #[tokio::main]
 async fn main() {
     // ── Load config ──────────────────────────────────────────────────────────────
     let config = Config::load("config.json");
+    logging::init_from_config(&config);
+
+    // Prometheus /metrics endpoint (see metrics.rs) unless metrics_addr is empty
+    metrics::serve_from_config(&config).await;
 
     // Build ONE client — shared across all requests for the lifetime of the bot
     let client = build_client(&config);
     let mut offset: i64 = 0;
 
+    // All outgoing Telegram messages go through this rate-limited queue.
+    // Only allowlisted chats may subscribe.
+    let allowed = ChatAllowlist::from_config(&config);
+    let mut subscribers = Subscribers::load(&config.subscribers_path, &config.chat_id);
+    let dropped = subscribers.retain_allowed(&allowed);
+    if dropped > 0 {
+        warn!(chats = dropped, "disabled subscribers not in the allowlist");
+    }
+    let tg = TgQueue::spawn(client.clone(), config.bot_token.clone(), TgLimits::from_config(&config), subscribers);
+
+    // Fetch history goes to SQLite (see db.rs) unless db_path is empty
+    let db = DbWriter::from_config(&config);
+
     // Convert Vec<String> from config into Vec<&str> for fetch_all_tags
     let tag_ids: Vec<&str> = config.tag_ids.iter().map(|s| s.as_str()).collect();
 
+    // ── Live pipeline ────────────────────────────────────────────────────────────
+    // WS books → arb engine → alerts / stats / history / paper / execution, over
+    // every event a fetch finds. One fetch at startup gives it something to watch.
+    let mut pipeline = Pipeline::start(&client, &config, &tg, db.as_ref()).await;
+    let events = run_fetch(&client, &config, &tag_ids, db.as_ref()).await;
+    pipeline.watch(&events);
+
     // ── Stdin command channel ────────────────────────────────────────────────────
     // Spawns a background task that reads lines from stdin and forwards any
     // recognised commands into the main loop via a channel — non-blocking.
@@ -37,15 +76,11 @@ async fn main() {
         use std::io::BufRead;
         let stdin = std::io::stdin();
         let mut lines = stdin.lock().lines();
-        loop {
-            match lines.next() {
-                Some(Ok(line)) => {
-                    let cmd = line.trim().to_string();
-                    if !cmd.is_empty() {
-                        let _ = stdin_tx.blocking_send(cmd);
-                    }
-                }
-                _ => break, // stdin closed / EOF
+        // Ends when stdin is closed / EOF
+        while let Some(Ok(line)) = lines.next() {
+            let cmd = line.trim().to_string();
+            if !cmd.is_empty() {
+                let _ = stdin_tx.blocking_send(cmd);
             }
         }
     });
@@ -69,7 +104,12 @@ async fn main() {
             result = tg_future => {
                 let body = match result {
                     Ok(r) => r.text().await.unwrap_or_default(),
-                    Err(_) => { tokio::time::sleep(std::time::Duration::from_secs(2)).await; continue; }
+                    Err(e) => {
+                        // without_url() keeps the bot token out of the log
+                        warn!(error = %e.without_url(), "Telegram getUpdates failed, retrying in 2s");
+                        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                        continue;
+                    }
                 };
 
                 let json: Value = serde_json::from_str(&body).unwrap_or_default();
@@ -82,17 +122,87 @@ async fn main() {
                     let update_id = update.get("update_id").and_then(Value::as_i64).unwrap_or(0);
                     offset = update_id + 1;
 
-                    let text = update
-                        .get("message")
+                    let message = update.get("message");
+                    let text = message
                         .and_then(|m| m.get("text"))
                         .and_then(Value::as_str)
                         .unwrap_or("");
+                    let chat_id = message
+                        .and_then(|m| m.get("chat"))
+                        .and_then(|c| c.get("id"))
+                        .and_then(Value::as_i64)
+                        .map(|id| id.to_string())
+                        .unwrap_or_default();
+
+                    // ── Subscription commands ────────────────────────────────────
+                    // /start re-enables a chat that was disabled after blocking the
+                    // bot. Broadcasts carry trading details, so allowlisted chats only.
+                    if text == "/start" && !allowed.contains(&chat_id) {
+                        warn!(chat_id = %chat_id, "/start from a chat not in the allowlist");
+                        if !chat_id.is_empty() {
+                            tg.send(&chat_id, "This bot is private.");
+                        }
+                        continue;
+                    }
+                    if text == "/start" {
+                        if tg.subscribers().lock().unwrap().add(&chat_id) {
+                            info!(chat_id = %chat_id, "subscribed chat");
+                        }
+                        tg.send(&chat_id, "Subscribed — send /stop to unsubscribe");
+                        continue;
+                    }
+                    if text == "/stop" && !chat_id.is_empty() {
+                        tg.send(&chat_id, "Unsubscribed — send /start to subscribe again");
+                        if tg.subscribers().lock().unwrap().disable(&chat_id, "unsubscribed") {
+                            info!(chat_id = %chat_id, "unsubscribed chat");
+                        }
+                        continue;
+                    }
 
-                    if text != "fetch games" { continue; }
+                    // ── Arb window statistics (see stats.rs) ─────────────────────
+                    if text == "/stats" && allowed.contains(&chat_id) {
+                        let report = StatsReport::build(&load_records(&config.arb_log_path));
+                        tg.send(&chat_id, report.summary());
+                        continue;
+                    }
 
-                    println!("Received 'fetch games' command (Telegram)");
-                    tg_send(&client, &config.bot_token, &config.chat_id, "Received 'fetch games' command, running...").await;
-                    run_fetch(&client, &config, &tag_ids).await;
+                    // ── Live positions and P&L (see portfolio.rs) ────────────────
+                    if text == "/positions" && allowed.contains(&chat_id) {
+                        tg.send(&chat_id, pipeline.with_portfolio(&config, Portfolio::positions_text));
+                        continue;
+                    }
+                    if text == "/pnl" && allowed.contains(&chat_id) {
+                        tg.send(&chat_id, pipeline.with_portfolio(&config, Portfolio::pnl_text));
+                        continue;
+                    }
+
+                    // ── Kill switch (see risk.rs) — owner chat only ──────────────
+                    // Halts the executor in this process at once (the risk task
+                    // cancels resting orders and reports back); the kill file
+                    // keeps it halted across a restart.
+                    if text == "/kill" && chat_id == config.chat_id {
+                        let reason = format!("/kill from Telegram at {}", Utc::now().to_rfc3339());
+                        let engaged = pipeline.kill.as_ref().is_some_and(|k| k.engage(&reason));
+                        let file = write_kill_file(&config.kill_switch_path, &reason);
+                        let mut reply = match (engaged, pipeline.kill.is_some()) {
+                            (true, _) => "🛑 Kill switch engaged — order submission halted, cancelling resting orders.".to_string(),
+                            (false, true) => "🛑 Kill switch was already engaged.".to_string(),
+                            (false, false) => "🛑 Live execution isn't running in this process.".to_string(),
+                        };
+                        match file {
+                            Ok(()) => reply.push_str(&format!(" Delete {} and restart to resume.", config.kill_switch_path)),
+                            Err(e) => reply.push_str(&format!(" Could not write {}: {}", config.kill_switch_path, e)),
+                        }
+                        tg.send(&chat_id, reply);
+                        continue;
+                    }
+
+                    if text != "fetch games" || !allowed.contains(&chat_id) { continue; }
+
+                    info!(source = "telegram", chat_id = %chat_id, "fetch games requested");
+                    tg.send(&config.chat_id, "Received 'fetch games' command, running...");
+                    let events = run_fetch(&client, &config, &tag_ids, db.as_ref()).await;
+                    pipeline.watch(&events);
                 }
             }
 
@@ -101,30 +211,214 @@ async fn main() {
             // which lets select! race it properly against the Telegram future.
             Some(cmd) = stdin_rx.recv() => {
                 if cmd == "fetch games" {
-                    println!("Received 'fetch games' command (terminal)");
-                    // tg_send(&client, &config.bot_token, &config.chat_id, "Received 'fetch games' command, running...").await;
-                    run_fetch(&client, &config, &tag_ids).await;
+                    info!(source = "terminal", "fetch games requested");
+                    // tg.send(&config.chat_id, "Received 'fetch games' command, running...");
+                    let events = run_fetch(&client, &config, &tag_ids, db.as_ref()).await;
+                    pipeline.watch(&events);
                 } else {
                     println!("Unknown command: '{}' — try 'fetch games'", cmd);
+                    debug!(command = %cmd, "unknown terminal command");
                 }
             }
         }
     }
 }
 
+// ================================================================================
+// PIPELINE
+// The WS pool and everything downstream of it. Events are added as fetches
+// find them. An event that drops out of a later fetch loses its arb groups
+// at once, but its tokens keep streaming until every one of its markets has
+// resolved, so open positions (live or paper) still get settled.
+// ================================================================================
+struct Pipeline {
+    ws: ws::WsHandle,
+    groups: mpsc::UnboundedSender<GroupChange>,
+    watched: HashMap<String, Watched>,    // event id → what it streams
+    leaving: HashMap<String, Watched>,    // gone from the fetch, waiting on resolution
+    resolved: Arc<Mutex<HashSet<String>>>, // condition ids seen resolving
+    kill: Option<Arc<KillSwitch>>,
+    portfolio: Option<Arc<Mutex<Portfolio>>>,
+}
+
+struct Watched {
+    tokens: Vec<String>,
+    markets: Vec<String>, // condition ids; empty if Gamma sent none
+}
+
+impl Pipeline {
+    async fn start(client: &reqwest::Client, config: &Config, tg: &TgQueue, db: Option<&DbWriter>) -> Pipeline {
+        let handle = ws::spawn(client.clone(), vec![], ws::WsSettings::from_config(config));
+        let (groups, groups_rx) = mpsc::unbounded_channel();
+        let arb_events = arb::spawn_with_groups(handle.updates(), ArbEngine::from_config(config), groups_rx);
+        let resolved = Arc::new(Mutex::new(HashSet::new()));
+        spawn_resolution_tracker(handle.updates(), resolved.clone());
+        stats::spawn_recorder(arb_events.subscribe(), config.arb_log_path.clone());
+
+        // History in SQLite (db_path empty = off)
+        if let Some(db) = db {
+            db::spawn_update_recorder(handle.updates(), db.clone(), Duration::from_secs(config.db_ws_book_interval_secs));
+            db::spawn_arb_recorder(arb_events.subscribe(), db.clone());
+        }
+
+        if config.ws_lifecycle_alerts {
+            ws::forward_lifecycle_to_telegram(&handle, tg.clone());
+        }
+        if config.arb_alerts {
+            alerts::forward_arbs_to_telegram(arb_events.subscribe(), tg.clone(), alerts::AlertSettings::from_config(config));
+        }
+
+        // Paper trading on the same streams
+        if config.paper_trading {
+            let trader = paper::PaperTrader::new(paper::PaperSettings::from_config(config), config.fees.clone());
+            paper::spawn(handle.updates(), arb_events.subscribe(), trader, Some(tg.clone()));
+        }
+
+        // Live execution (needs exec_private_key)
+        let mut pipeline = Pipeline {
+            ws: handle,
+            groups,
+            watched: HashMap::new(),
+            leaving: HashMap::new(),
+            resolved,
+            kill: None,
+            portfolio: None,
+        };
+        if config.exec_live {
+            match ClobClient::from_config(client.clone(), config) {
+                Ok(mut clob) => match clob.derive_api_key().await {
+                    Ok(_) => {
+                        info!(address = %clob.address(), "live execution enabled");
+                        let clob = Arc::new(clob);
+                        let kill = Arc::new(KillSwitch::from_config(config));
+                        let risk = Arc::new(RiskManager::new(risk::RiskLimits::from_config(config), kill.clone()));
+                        risk::spawn(pipeline.ws.updates(), risk.clone(), clob.clone(), Some(tg.clone()));
+                        let portfolio = Arc::new(Mutex::new(Portfolio::load(&config.portfolio_path)));
+                        portfolio::spawn(pipeline.ws.updates(), portfolio.clone(), Some(clob.clone()), Some(tg.clone()));
+                        let executor = executor::Executor::new(clob, executor::ExecSettings::from_config(config), kill.clone(), Some(tg.clone()));
+                        executor::spawn(arb_events.subscribe(), executor, risk, portfolio.clone());
+                        pipeline.kill = Some(kill);
+                        pipeline.portfolio = Some(portfolio);
+                    }
+                    Err(e) => error!(error = %e, "could not derive API key, execution disabled"),
+                },
+                Err(e) => error!(error = %e, "execution disabled"),
+            }
+        }
+        pipeline
+    }
+
+    // Subscribes the tokens and adds the arb groups of every event not yet
+    // watched; drops the groups of watched events the fetch no longer returns
+    // and unsubscribes those whose markets have all resolved
+    fn watch(&mut self, events: &[Value]) {
+        let mut tokens = Vec::new();
+        for event in events {
+            let id = event.get("id").and_then(Value::as_str).unwrap_or("");
+            if self.watched.contains_key(id) { continue; }
+            let groups = arb::groups_from_event(event);
+            for group in &groups {
+                let _ = self.groups.send(GroupChange::Add(group.clone()));
+            }
+            // Back before it resolved: its tokens never stopped streaming
+            if let Some(entry) = self.leaving.remove(id) {
+                self.watched.insert(id.to_string(), entry);
+                continue;
+            }
+            self.ws.record_meta(event.clone()); // lets `replay` rebuild the arb groups
+            let mut entry = Watched { tokens: Vec::new(), markets: Vec::new() };
+            for (_, token_ids, outcomes) in extract_moneyline_markets(event) {
+                entry.tokens.extend(token_ids.iter().cloned());
+                tokens.extend(token_ids.into_iter().zip(outcomes));
+            }
+            entry.markets = groups.iter()
+                .flat_map(|g| g.legs.iter().map(|l| l.market.clone()))
+                .filter(|m| !m.is_empty())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect();
+            self.watched.insert(id.to_string(), entry);
+        }
+        if !tokens.is_empty() {
+            info!(tokens = tokens.len(), events = self.watched.len(), "streaming new events");
+            self.ws.subscribe(tokens);
+        }
+
+        // An empty result is a failed fetch, not every game ending at once
+        if events.is_empty() { return; }
+        let current: HashSet<&str> = events.iter().filter_map(|e| e.get("id").and_then(Value::as_str)).collect();
+        let gone: Vec<String> = self.watched.keys().filter(|id| !current.contains(id.as_str())).cloned().collect();
+        for id in gone {
+            if let Some(entry) = self.watched.remove(&id) {
+                let _ = self.groups.send(GroupChange::RemoveEvent(id.clone()));
+                self.leaving.insert(id, entry);
+            }
+        }
+
+        let mut resolved = self.resolved.lock().unwrap();
+        let done: Vec<String> = self.leaving.iter()
+            .filter(|(_, w)| w.markets.iter().all(|m| resolved.contains(m)))
+            .map(|(id, _)| id.clone())
+            .collect();
+        let mut tokens = Vec::new();
+        for id in &done {
+            if let Some(entry) = self.leaving.remove(id) {
+                for m in &entry.markets { resolved.remove(m); }
+                tokens.extend(entry.tokens);
+            }
+        }
+        if !tokens.is_empty() {
+            info!(tokens = tokens.len(), events = done.len(), "unsubscribing resolved events");
+            self.ws.unsubscribe(tokens);
+        }
+    }
+
+    // Reads the live portfolio when this process trades, the saved file otherwise
+    fn with_portfolio(&self, config: &Config, f: impl Fn(&Portfolio) -> String) -> String {
+        match &self.portfolio {
+            Some(p) => f(&p.lock().unwrap()),
+            None => f(&Portfolio::load(&config.portfolio_path)),
+        }
+    }
+}
+
+// Records the condition id of every market_resolved on the stream, so the
+// pipeline knows when a departed event can be unsubscribed
+fn spawn_resolution_tracker(mut updates: broadcast::Receiver<ws::MarketUpdate>, resolved: Arc<Mutex<HashSet<String>>>) {
+    tokio::spawn(async move {
+        loop {
+            match updates.recv().await {
+                Ok(ws::MarketUpdate::MarketResolved { market, .. }) => { resolved.lock().unwrap().insert(market); }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => warn!(skipped = n, "resolution tracker fell behind"),
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+}
+
 // ================================================================================
 // RUN FETCH
 // Extracted from the main loop so both Telegram and terminal commands
-// can trigger it without duplicating the logic.
+// can trigger it without duplicating the logic. Returns the game events found,
+// for the live pipeline.
 // ================================================================================
-async fn run_fetch(client: &reqwest::Client, config: &Config, tag_ids: &[&str]) {
+async fn run_fetch(client: &reqwest::Client, config: &Config, tag_ids: &[&str], db: Option<&DbWriter>) -> Vec<Value> {
+    let span = info_span!("fetch_run", run_id = %logging::run_id());
+    run_fetch_inner(client, config, tag_ids, db).instrument(span).await
+}
+
+async fn run_fetch_inner(client: &reqwest::Client, config: &Config, tag_ids: &[&str], db: Option<&DbWriter>) -> Vec<Value> {
+    let run_started = Instant::now();
     // ── Time window ──────────────────────────────────────────────────────────
     let (now, window_end, now_str) = now_and_window(config.hours_window);
+    let started_ms = now.timestamp_millis();
 
     // ── 1. Fetch all tags IN PARALLEL ────────────────────────────────────────
-    println!("Fetching {} tags in parallel...", tag_ids.len());
+    info!(tags = tag_ids.len(), "fetching tags in parallel");
+    let tags_started = Instant::now();
     let all_events = fetch_all_tags(client, tag_ids, &now_str).await;
-    println!("Got {} total events across all tags", all_events.len());
+    info!(events = all_events.len(), latency_ms = elapsed_ms(tags_started), "fetched events across all tags");
 
     // ── 2. Deduplicate by event id ────────────────────────────────────────────
     let mut seen_ids = std::collections::HashSet::new();
@@ -135,11 +429,11 @@ async fn run_fetch(client: &reqwest::Client, config: &Config, tag_ids: &[&str])
             seen_ids.insert(id.to_string())
         })
         .collect();
-    println!("{} unique events after dedup", all_events.len());
+    info!(events = all_events.len(), "unique events after dedup");
 
     // ── 3. Filter to game events in the time window ───────────────────────────
     let game_events = filter_game_events(&all_events, &now, &window_end);
-    println!("{} game events in window", game_events.len());
+    info!(events = game_events.len(), hours_window = config.hours_window, "game events in window");
 
     // ── 4. Build jobs grouped by event ───────────────────────────────────────
     struct MarketJob {
@@ -175,10 +469,16 @@ async fn run_fetch(client: &reqwest::Client, config: &Config, tag_ids: &[&str])
         .flat_map(|(i, ej)| ej.markets.iter().map(move |m| FlatJob { event_idx: i, market: m }))
         .collect();
 
-    println!("Fetching orderbooks for {} markets in parallel...\n", flat_jobs.len());
+    info!(markets = flat_jobs.len(), "fetching orderbooks in parallel");
+    let books_started = Instant::now();
     let all_orderbooks = futures::future::join_all(
         flat_jobs.iter().map(|fj| fetch_orderbooks(client, &fj.market.tokens, &fj.market.outcomes))
     ).await;
+    info!(
+        books = all_orderbooks.iter().map(Vec::len).sum::<usize>(),
+        latency_ms = elapsed_ms(books_started),
+        "fetched orderbooks"
+    );
 
     // ── 6. Assemble JSON output ───────────────────────────────────────────────
     let mut filtered: Vec<Value> = Vec::new();
@@ -210,7 +510,14 @@ async fn run_fetch(client: &reqwest::Client, config: &Config, tag_ids: &[&str])
             if sides.len() < 2 { continue; }
 
             let side_entries: Vec<Value> = sides.iter()
-                .map(|s| serde_json::json!({ "outcome": s.outcome, "best_ask": s.best_ask }))
+                .map(|s| serde_json::json!({
+                    "outcome": s.outcome,
+                    "token_id": s.token_id,
+                    "best_bid": s.best_bid,
+                    "best_bid_size": s.best_bid_size,
+                    "best_ask": s.best_ask,
+                    "best_ask_size": s.best_ask_size,
+                }))
                 .collect();
 
             market_entries.push(serde_json::json!({
@@ -230,14 +537,39 @@ async fn run_fetch(client: &reqwest::Client, config: &Config, tag_ids: &[&str])
             "title": title,
             "slug": slug,
             "endDateHST": end_date_hst,
+            "endDateUTC": end_date_str,
             "market_entries": market_entries
         }));
     }
 
     // ── 7. Save ───────────────────────────────────────────────────────────────
-    let result = serde_json::to_string_pretty(&filtered).unwrap();
-    fs::create_dir_all("events").unwrap();
-    write("events/polymarket_btc_events.json", result).unwrap();
+    let result = serde_json::to_string_pretty(&filtered).unwrap_or_else(|_| "[]".to_string());
+    match save_snapshot(&OutputSettings::from_config(config), result.as_bytes(), &now) {
+        Ok(path) => {
+            info!(events = filtered.len(), path = %path.display(), "saved report");
+            // Flat copies for analysis (see export.rs)
+            let formats: Vec<ExportFormat> = config.export_formats.iter().filter_map(|f| ExportFormat::parse(f)).collect();
+            if !formats.is_empty() {
+                match export_report(&filtered, &formats, &path) {
+                    Ok(paths) => info!(paths = %paths.join(", "), "exported report"),
+                    Err(e) => error!(error = %e, "export failed"),
+                }
+            }
+        }
+        Err(e) => error!(dir = %config.output_dir, error = %e, "failed to save report"),
+    }
+
+    // ── 8. History ────────────────────────────────────────────────────────────
+    let events: Vec<Value> = game_events.iter().map(|e| (*e).clone()).collect();
+    if let Some(db) = db {
+        let books = all_orderbooks.iter().flatten()
+            .map(|ob| BookRow::from_rest(&ob.token_id, &ob.book, started_ms))
+            .collect();
+        db.send(DbWrite::FetchRun { started_ms, events: events.clone(), books });
+    }
+
+    info!(latency_ms = elapsed_ms(run_started), "fetch run finished");
 
-    // tg_send(client, &config.bot_token, &config.chat_id, ".json file updated!").await;
+    // tg.send(&config.chat_id, ".json file updated!");
+    events
 }
@@ -0,0 +1,323 @@
+// messages.rs — typed CLOB WebSocket market-channel messages
+//
+// Every event the market channel can send, deserialized straight from the
+// wire format. Polymarket sends numbers as strings ("0.535"), so fields stay
+// String here and the parse_* helpers convert where a number is needed.
+//
+// REST /book returns the same shape as the `book` event, so BookMsg doubles
+// as the resync snapshot type.
+//
+// Market channel docs:
+// https://docs.polymarket.com/developers/CLOB/websocket/market-channel
+
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Level {
+    pub price: String,
+    pub size: String,
+}
+
+// ── book ──────────────────────────────────────────────────────────────────────
+// Full snapshot for one asset. Sent on subscribe and after every trade.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BookMsg {
+    pub asset_id: String,
+    #[serde(default)]
+    pub market: String,
+    #[serde(default)]
+    pub timestamp: String,
+    #[serde(default)]
+    pub hash: Option<String>,
+    #[serde(default, alias = "buys")]
+    pub bids: Vec<Level>,
+    #[serde(default, alias = "sells")]
+    pub asks: Vec<Level>,
+    // Only present on REST /book responses
+    #[serde(default)]
+    pub tick_size: Option<String>,
+}
+
+// ── price_change ──────────────────────────────────────────────────────────────
+// One or more level updates. `size` is the new total at that price, not a delta.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceChangeMsg {
+    #[serde(default)]
+    pub market: String,
+    #[serde(default)]
+    pub timestamp: String,
+    pub price_changes: Vec<PriceChange>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriceChange {
+    pub asset_id: String,
+    pub price: String,
+    pub size: String,
+    pub side: String, // "BUY" | "SELL"
+    #[serde(default)]
+    pub hash: Option<String>,
+    #[serde(default)]
+    pub best_bid: Option<String>,
+    #[serde(default)]
+    pub best_ask: Option<String>,
+}
+
+// ── tick_size_change ──────────────────────────────────────────────────────────
+// Sent when a market's price approaches 0 or 1 and the tick size shrinks.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TickSizeChangeMsg {
+    pub asset_id: String,
+    #[serde(default)]
+    pub market: String,
+    pub old_tick_size: String,
+    pub new_tick_size: String,
+    #[serde(default)]
+    pub timestamp: String,
+}
+
+// ── last_trade_price ──────────────────────────────────────────────────────────
+// A maker and taker order matched.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LastTradePriceMsg {
+    pub asset_id: String,
+    #[serde(default)]
+    pub market: String,
+    pub price: String,
+    #[serde(default)]
+    pub size: String,
+    #[serde(default)]
+    pub side: String,
+    #[serde(default)]
+    pub fee_rate_bps: Option<String>,
+    #[serde(default)]
+    pub timestamp: String,
+}
+
+// ── best_bid_ask ──────────────────────────────────────────────────────────────
+// Top-of-book change (only sent when custom features are enabled).
+#[derive(Debug, Clone, Deserialize)]
+pub struct BestBidAskMsg {
+    pub asset_id: String,
+    #[serde(default)]
+    pub market: String,
+    pub best_bid: String,
+    pub best_ask: String,
+    #[serde(default)]
+    pub spread: Option<String>,
+    #[serde(default)]
+    pub timestamp: String,
+}
+
+// ── new_market / market_resolved ──────────────────────────────────────────────
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewMarketMsg {
+    #[serde(default)]
+    pub market: String,
+    #[serde(default)]
+    pub question: String,
+    #[serde(default)]
+    pub slug: String,
+    #[serde(default)]
+    pub assets_ids: Vec<String>,
+    #[serde(default)]
+    pub outcomes: Vec<String>,
+    #[serde(default)]
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MarketResolvedMsg {
+    #[serde(default)]
+    pub market: String,
+    #[serde(default)]
+    pub winning_asset_id: String,
+    #[serde(default)]
+    pub winning_outcome: String,
+    #[serde(default)]
+    pub timestamp: String,
+}
+
+// ================================================================================
+// MARKET MESSAGE
+// Dispatch on event_type. Anything we don't recognise — or can't deserialize —
+// becomes Unknown with its event_type kept so the caller can count it.
+// ================================================================================
+#[derive(Debug, Clone)]
+pub enum MarketMessage {
+    Book(BookMsg),
+    PriceChange(PriceChangeMsg),
+    TickSizeChange(TickSizeChangeMsg),
+    LastTradePrice(LastTradePriceMsg),
+    BestBidAsk(BestBidAskMsg),
+    NewMarket(NewMarketMsg),
+    MarketResolved(MarketResolvedMsg),
+    Unknown(String),
+}
+
+impl MarketMessage {
+    // Parses one message object. Returns None for frames without an
+    // event_type (subscription acks).
+    pub fn parse(msg: Value) -> Option<MarketMessage> {
+        let event_type = msg.get("event_type").and_then(Value::as_str)?.to_string();
+
+        fn typed<T: for<'de> Deserialize<'de>>(msg: Value, f: fn(T) -> MarketMessage, et: &str) -> MarketMessage {
+            serde_json::from_value(msg).map(f).unwrap_or_else(|_| MarketMessage::Unknown(format!("{et} (malformed)")))
+        }
+
+        Some(match event_type.as_str() {
+            "book"             => typed(msg, MarketMessage::Book, &event_type),
+            "price_change"     => typed(msg, MarketMessage::PriceChange, &event_type),
+            "tick_size_change" => typed(msg, MarketMessage::TickSizeChange, &event_type),
+            "last_trade_price" => typed(msg, MarketMessage::LastTradePrice, &event_type),
+            "best_bid_ask"     => typed(msg, MarketMessage::BestBidAsk, &event_type),
+            "new_market"       => typed(msg, MarketMessage::NewMarket, &event_type),
+            "market_resolved"  => typed(msg, MarketMessage::MarketResolved, &event_type),
+            _                  => MarketMessage::Unknown(event_type),
+        })
+    }
+
+    // Name used for per-type message counts
+    pub fn kind(&self) -> &str {
+        match self {
+            MarketMessage::Book(_)           => "book",
+            MarketMessage::PriceChange(_)    => "price_change",
+            MarketMessage::TickSizeChange(_) => "tick_size_change",
+            MarketMessage::LastTradePrice(_) => "last_trade_price",
+            MarketMessage::BestBidAsk(_)     => "best_bid_ask",
+            MarketMessage::NewMarket(_)      => "new_market",
+            MarketMessage::MarketResolved(_) => "market_resolved",
+            MarketMessage::Unknown(et)       => et,
+        }
+    }
+}
+
+// ── Parsing helpers ───────────────────────────────────────────────────────────
+pub fn parse_f64(s: &str) -> Option<f64> {
+    s.parse().ok()
+}
+
+// Millisecond timestamps arrive as strings; missing/garbage → 0
+pub fn parse_ts(s: &str) -> u64 {
+    s.parse().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(msg: Value) -> MarketMessage {
+        MarketMessage::parse(msg).expect("event_type present")
+    }
+
+    #[test]
+    fn book() {
+        let msg = parse(json!({
+            "event_type": "book", "asset_id": "111", "market": "0xm", "timestamp": "1700000000000", "hash": "0xh",
+            "bids": [{ "price": "0.48", "size": "30" }], "asks": [{ "price": "0.52", "size": "25" }],
+        }));
+        let MarketMessage::Book(book) = &msg else { panic!("{:?}", msg) };
+        assert_eq!((book.asset_id.as_str(), book.bids.len(), book.asks[0].price.as_str()), ("111", 1, "0.52"));
+        assert_eq!(parse_ts(&book.timestamp), 1_700_000_000_000);
+        assert_eq!(msg.kind(), "book");
+
+        // Older payloads name the sides buys / sells
+        let msg = parse(json!({ "event_type": "book", "asset_id": "111", "buys": [{ "price": "0.4", "size": "1" }], "sells": [] }));
+        assert!(matches!(&msg, MarketMessage::Book(b) if b.bids.len() == 1 && b.asks.is_empty()), "{:?}", msg);
+    }
+
+    #[test]
+    fn price_change() {
+        let msg = parse(json!({
+            "event_type": "price_change", "market": "0xm", "timestamp": "1700000000001",
+            "price_changes": [
+                { "asset_id": "111", "price": "0.5", "size": "200", "side": "BUY", "hash": "0xh", "best_bid": "0.5", "best_ask": "0.52" },
+                { "asset_id": "222", "price": "0.5", "size": "0", "side": "SELL" },
+            ],
+        }));
+        let MarketMessage::PriceChange(pc) = &msg else { panic!("{:?}", msg) };
+        assert_eq!(pc.price_changes.len(), 2);
+        assert_eq!(pc.price_changes[0].best_ask.as_deref(), Some("0.52"));
+        assert_eq!(pc.price_changes[1].best_bid, None);
+        assert_eq!(msg.kind(), "price_change");
+    }
+
+    #[test]
+    fn tick_size_change() {
+        let msg = parse(json!({ "event_type": "tick_size_change", "asset_id": "111", "old_tick_size": "0.01", "new_tick_size": "0.001" }));
+        assert!(matches!(&msg, MarketMessage::TickSizeChange(t) if parse_f64(&t.new_tick_size) == Some(0.001)), "{:?}", msg);
+        assert_eq!(msg.kind(), "tick_size_change");
+    }
+
+    #[test]
+    fn last_trade_price() {
+        let msg = parse(json!({
+            "event_type": "last_trade_price", "asset_id": "111", "market": "0xm",
+            "price": "0.456", "size": "219.21", "side": "BUY", "fee_rate_bps": "0",
+        }));
+        assert!(matches!(&msg, MarketMessage::LastTradePrice(t) if t.price == "0.456" && t.fee_rate_bps.as_deref() == Some("0")), "{:?}", msg);
+        assert_eq!(msg.kind(), "last_trade_price");
+    }
+
+    #[test]
+    fn best_bid_ask() {
+        let msg = parse(json!({ "event_type": "best_bid_ask", "asset_id": "111", "best_bid": "0.73", "best_ask": "0.77", "spread": "0.04" }));
+        assert!(matches!(&msg, MarketMessage::BestBidAsk(b) if b.best_bid == "0.73" && b.spread.as_deref() == Some("0.04")), "{:?}", msg);
+        assert_eq!(msg.kind(), "best_bid_ask");
+    }
+
+    #[test]
+    fn new_market_and_market_resolved() {
+        let msg = parse(json!({
+            "event_type": "new_market", "market": "0xm", "question": "Will A win?",
+            "assets_ids": ["111", "222"], "outcomes": ["Yes", "No"],
+        }));
+        assert!(matches!(&msg, MarketMessage::NewMarket(m) if m.assets_ids == ["111", "222"] && m.slug.is_empty()), "{:?}", msg);
+        assert_eq!(msg.kind(), "new_market");
+
+        let msg = parse(json!({ "event_type": "market_resolved", "market": "0xm", "winning_asset_id": "111", "winning_outcome": "Yes" }));
+        assert!(matches!(&msg, MarketMessage::MarketResolved(r) if r.winning_asset_id == "111"), "{:?}", msg);
+        assert_eq!(msg.kind(), "market_resolved");
+    }
+
+    #[test]
+    fn malformed_messages_are_unknown() {
+        let cases = [
+            json!({ "event_type": "book", "bids": [] }),                                      // no asset_id
+            json!({ "event_type": "book", "asset_id": "111", "bids": [{ "price": "0.4" }] }), // level without size
+            json!({ "event_type": "price_change", "market": "0xm" }),                         // no price_changes
+            json!({ "event_type": "price_change", "price_changes": [{ "asset_id": "111" }] }),
+            json!({ "event_type": "tick_size_change", "asset_id": "111", "old_tick_size": "0.01" }),
+            json!({ "event_type": "last_trade_price", "asset_id": "111", "price": 0.5 }),     // number, not string
+            json!({ "event_type": "best_bid_ask", "asset_id": "111", "best_bid": "0.7" }),
+            json!({ "event_type": "new_market", "assets_ids": "111" }),
+            json!({ "event_type": "market_resolved", "winning_asset_id": ["111"] }),
+        ];
+        for case in cases {
+            let et = case["event_type"].as_str().unwrap_or_default().to_string();
+            let msg = parse(case);
+            assert_eq!(msg.kind(), format!("{et} (malformed)"), "{:?}", msg);
+        }
+    }
+
+    #[test]
+    fn unknown_types_and_acks() {
+        let msg = parse(json!({ "event_type": "something_new", "market": "0xm" }));
+        assert!(matches!(&msg, MarketMessage::Unknown(et) if et == "something_new"));
+        assert_eq!(msg.kind(), "something_new");
+
+        assert!(MarketMessage::parse(json!({ "message": "subscribed" })).is_none());
+        assert!(MarketMessage::parse(json!({ "event_type": 7 })).is_none());
+        assert!(MarketMessage::parse(json!([])).is_none());
+    }
+
+    #[test]
+    fn number_helpers() {
+        assert_eq!(parse_f64("0.535"), Some(0.535));
+        assert_eq!(parse_f64(""), None);
+        assert_eq!(parse_ts("garbage"), 0);
+        assert_eq!(parse_ts(""), 0);
+    }
+}
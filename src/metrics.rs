@@ -0,0 +1,191 @@
+// metrics.rs — Prometheus counters/histograms and the /metrics endpoint
+//
+// One process-wide registry, reached through metrics(), so fetch.rs, exec.rs,
+// ws.rs, arb.rs and telegram.rs can count without threading a handle through
+// every call. Exposed series:
+//
+//   polymarket_request_duration_seconds{api,endpoint}  histogram, Gamma/CLOB REST
+//   polymarket_request_errors_total{api,endpoint}      network, HTTP or parse errors
+//   gamma_events_fetched_total{tag_id}                 events returned per tag
+//   ws_messages_total{event_type}                      WS messages by type
+//   ws_reconnects_total                                reconnect attempts, all shards
+//   ws_seconds_since_last_message                      computed at scrape time
+//   arb_opportunities_total                            new arb windows opened
+//   telegram_send_failures_total{kind}                 rate_limited | transient | permanent
+//
+// With metrics_addr set, serve() answers GET /metrics in the Prometheus text
+// format — plain HTTP/1.1 over a tokio TcpListener, one request per
+// connection, like mock_clob.rs.
+
+use crate::fetch::Config;
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, warn};
+
+// REST latency buckets in seconds — Gamma pages take a few hundred ms, CLOB
+// /book tens of ms, timeouts land in the last bucket
+// A client that opens a connection and never finishes its request is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const LATENCY_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub struct Metrics {
+    registry: Registry,
+    pub request_duration: HistogramVec,
+    pub request_errors: IntCounterVec,
+    pub events_fetched: IntCounterVec,
+    pub ws_messages: IntCounterVec,
+    pub ws_reconnects: IntCounter,
+    pub arb_opportunities: IntCounter,
+    pub tg_send_failures: IntCounterVec,
+    ws_since_last: Gauge,
+    ws_last_message_ms: AtomicI64, // ms since epoch; process start until the first message
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let registry = Registry::new();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new("polymarket_request_duration_seconds", "Gamma/CLOB REST request latency")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["api", "endpoint"],
+        ).unwrap();
+        let request_errors = IntCounterVec::new(
+            Opts::new("polymarket_request_errors_total", "Gamma/CLOB REST requests that failed"),
+            &["api", "endpoint"],
+        ).unwrap();
+        let events_fetched = IntCounterVec::new(
+            Opts::new("gamma_events_fetched_total", "Events returned by Gamma, per tag"),
+            &["tag_id"],
+        ).unwrap();
+        let ws_messages = IntCounterVec::new(
+            Opts::new("ws_messages_total", "WebSocket messages received, by event_type"),
+            &["event_type"],
+        ).unwrap();
+        let ws_reconnects = IntCounter::new("ws_reconnects_total", "WebSocket reconnect attempts").unwrap();
+        let ws_since_last = Gauge::new("ws_seconds_since_last_message", "Seconds since the last WebSocket message").unwrap();
+        let arb_opportunities = IntCounter::new("arb_opportunities_total", "Arb windows opened").unwrap();
+        let tg_send_failures = IntCounterVec::new(
+            Opts::new("telegram_send_failures_total", "Telegram sendMessage calls that failed"),
+            &["kind"],
+        ).unwrap();
+
+        // Names are fixed and unique, so registration can't fail
+        registry.register(Box::new(request_duration.clone())).unwrap();
+        registry.register(Box::new(request_errors.clone())).unwrap();
+        registry.register(Box::new(events_fetched.clone())).unwrap();
+        registry.register(Box::new(ws_messages.clone())).unwrap();
+        registry.register(Box::new(ws_reconnects.clone())).unwrap();
+        registry.register(Box::new(ws_since_last.clone())).unwrap();
+        registry.register(Box::new(arb_opportunities.clone())).unwrap();
+        registry.register(Box::new(tg_send_failures.clone())).unwrap();
+
+        Metrics {
+            registry,
+            request_duration,
+            request_errors,
+            events_fetched,
+            ws_messages,
+            ws_reconnects,
+            arb_opportunities,
+            tg_send_failures,
+            ws_since_last,
+            ws_last_message_ms: AtomicI64::new(chrono::Utc::now().timestamp_millis()),
+        }
+    }
+
+    // One REST call to `api` ("gamma" / "clob"), timed from `started`
+    pub fn observe_request(&self, api: &str, endpoint: &str, started: Instant, ok: bool) {
+        self.request_duration.with_label_values(&[api, endpoint]).observe(started.elapsed().as_secs_f64());
+        if !ok {
+            self.request_errors.with_label_values(&[api, endpoint]).inc();
+        }
+    }
+
+    pub fn ws_message_seen(&self) {
+        self.ws_last_message_ms.store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    // Text exposition format, as served on /metrics
+    pub fn render(&self) -> String {
+        let since_ms = chrono::Utc::now().timestamp_millis() - self.ws_last_message_ms.load(Ordering::Relaxed);
+        self.ws_since_last.set(since_ms.max(0) as f64 / 1000.0);
+
+        let mut buf = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buf) {
+            warn!(error = %e, "could not encode metrics");
+        }
+        String::from_utf8(buf).unwrap_or_default()
+    }
+}
+
+// ================================================================================
+// HTTP
+// ================================================================================
+// Binds `addr` and serves /metrics in the background; returns the bound
+// address (port 0 picks a free one)
+pub async fn serve(addr: &str) -> std::io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr).await?;
+    let local = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let _ = respond(stream).await;
+            });
+        }
+    });
+    Ok(local)
+}
+
+// serve() on config.metrics_addr, unless it's empty. A bind failure is logged
+// and the bot carries on without metrics.
+pub async fn serve_from_config(config: &Config) {
+    if config.metrics_addr.is_empty() { return; }
+    match serve(&config.metrics_addr).await {
+        Ok(addr) => info!(addr = %addr, "serving Prometheus metrics on /metrics"),
+        Err(e) => warn!(addr = %config.metrics_addr, error = %e, "could not start metrics endpoint"),
+    }
+}
+
+async fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    // Only the request line matters — read until the end of the headers
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let read_head = async {
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < 16 * 1024 {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 { break; }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        Ok::<_, std::io::Error>(())
+    };
+    tokio::time::timeout(READ_TIMEOUT, read_head).await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "request headers not received"))??;
+    let head = String::from_utf8_lossy(&buf);
+    let mut start = head.lines().next().unwrap_or("").split_whitespace();
+    let method = start.next().unwrap_or("");
+    let path = start.next().unwrap_or("").split('?').next().unwrap_or("");
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", "text/plain; version=0.0.4", metrics().render()),
+        _ => ("404 Not Found", "text/plain", "try /metrics\n".to_string()),
+    };
+    let resp = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    );
+    stream.write_all(resp.as_bytes()).await?;
+    stream.shutdown().await
+}
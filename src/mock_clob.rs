@@ -0,0 +1,335 @@
+// mock_clob.rs — local stand-in for the Polymarket CLOB REST API
+//
+// Serves just enough of the API for exec.rs to run end to end offline:
+//   GET  /auth/derive-api-key, POST /auth/api-key   — checks the L1 EIP-712 signature
+//   POST /order                                     — checks the L2 HMAC and the
+//                                                     order's EIP-712 signature,
+//                                                     then matches it
+//   DELETE /cancel-all                              — cancels every "live" order
+//   GET  /balance-allowance                         — USDC balance set with set_balance
+//   GET  /neg-risk, /fee-rate                       — per-token market info
+//
+// Matching is against resting liquidity set with set_asks / set_bids and
+// follows the real order types: FOK fills completely or is rejected, FAK (IOC)
+// fills what it can, GTC fills what it can and rests the remainder ("live").
+// Filled size is taken out of the mock book. Every accepted order is kept so
+// tests can inspect it.
+//
+// Plain HTTP/1.1 over a tokio TcpListener, one request per connection.
+
+use crate::book::{price_to_units, units_to_price};
+use crate::exec::{
+    clob_auth_digest, hmac_signature, parse_address, parse_signature, recover_address,
+    ApiCreds, Eip712Domain, OrderSide, OrderType, SignedOrder,
+};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::warn;
+
+#[derive(Debug, Clone)]
+pub struct MockOrder {
+    pub order_id: String,
+    pub order: SignedOrder,
+    pub order_type: OrderType,
+    pub status: String,
+    pub filled: f64, // shares
+}
+
+#[derive(Default)]
+struct MockState {
+    chain_id: u64,
+    creds: Option<ApiCreds>,
+    asks: HashMap<String, BTreeMap<u32, f64>>, // token → price units → size
+    bids: HashMap<String, BTreeMap<u32, f64>>,
+    orders: Vec<MockOrder>,
+    reject_all: Option<String>,
+    neg_risk: HashMap<String, bool>,
+    fee_rate: HashMap<String, u64>,
+    balance: f64, // USDC
+}
+
+#[derive(Clone)]
+pub struct MockClob {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockClob {
+    // Binds 127.0.0.1 on a free port and starts serving
+    pub async fn start(chain_id: u64) -> std::io::Result<MockClob> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let mock = MockClob {
+            addr: listener.local_addr()?,
+            state: Arc::new(Mutex::new(MockState { chain_id, ..Default::default() })),
+        };
+        let state = mock.state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, state).await {
+                        warn!(error = %e, "mock CLOB connection error");
+                    }
+                });
+            }
+        });
+        Ok(mock)
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    // Replaces the resting asks for a token: (price, size) pairs
+    pub fn set_asks(&self, token_id: &str, levels: &[(f64, f64)]) {
+        self.state.lock().unwrap().asks.insert(token_id.to_string(), to_levels(levels));
+    }
+
+    pub fn set_bids(&self, token_id: &str, levels: &[(f64, f64)]) {
+        self.state.lock().unwrap().bids.insert(token_id.to_string(), to_levels(levels));
+    }
+
+    // Rejects every order with this message until cleared with None
+    pub fn reject_all(&self, msg: Option<&str>) {
+        self.state.lock().unwrap().reject_all = msg.map(str::to_string);
+    }
+
+    pub fn set_market_info(&self, token_id: &str, neg_risk: bool, fee_rate_bps: u64) {
+        let mut st = self.state.lock().unwrap();
+        st.neg_risk.insert(token_id.to_string(), neg_risk);
+        st.fee_rate.insert(token_id.to_string(), fee_rate_bps);
+    }
+
+    pub fn set_balance(&self, usdc: f64) {
+        self.state.lock().unwrap().balance = usdc;
+    }
+
+    pub fn orders(&self) -> Vec<MockOrder> {
+        self.state.lock().unwrap().orders.clone()
+    }
+
+    pub fn creds(&self) -> Option<ApiCreds> {
+        self.state.lock().unwrap().creds.clone()
+    }
+}
+
+fn to_levels(levels: &[(f64, f64)]) -> BTreeMap<u32, f64> {
+    levels.iter()
+        .filter_map(|(p, s)| Some((price_to_units(&p.to_string())?, *s)))
+        .collect()
+}
+
+// ================================================================================
+// HTTP
+// ================================================================================
+struct Request {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>, // lowercased names
+    body: String,
+}
+
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<MockState>>) -> std::io::Result<()> {
+    let Some(req) = read_request(&mut stream).await? else { return Ok(()) };
+    let (code, body) = route(&req, &state);
+    let body = body.to_string();
+    let reason = if code == 200 { "OK" } else { "Error" };
+    let resp = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code, reason, body.len(), body
+    );
+    stream.write_all(resp.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<Request>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 { return Ok(None); }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") { break i + 4; }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut start = lines.next().unwrap_or("").split_whitespace();
+    let method = start.next().unwrap_or("").to_string();
+    let path = start.next().unwrap_or("").to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect();
+
+    let len: usize = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+    while buf.len() < header_end + len {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 { break; }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = String::from_utf8_lossy(&buf[header_end..]).to_string();
+    Ok(Some(Request { method, path, headers, body }))
+}
+
+fn route(req: &Request, state: &Mutex<MockState>) -> (u16, Value) {
+    let (path, query) = req.path.split_once('?').unwrap_or((req.path.as_str(), ""));
+    let token = query.strip_prefix("token_id=").unwrap_or("");
+    match (req.method.as_str(), path) {
+        ("GET", "/neg-risk") => (200, json!({ "neg_risk": state.lock().unwrap().neg_risk.get(token).copied().unwrap_or(false) })),
+        ("GET", "/fee-rate") => (200, json!({ "base_fee": state.lock().unwrap().fee_rate.get(token).copied().unwrap_or(0) })),
+        ("GET", "/auth/derive-api-key") | ("POST", "/auth/api-key") => auth(req, state),
+        ("POST", "/order") => post_order(req, state),
+        ("DELETE", "/cancel-all") => cancel_all(req, state),
+        ("GET", "/balance-allowance") => balance(req, state),
+        _ => (404, json!({ "error": format!("no route {} {}", req.method, req.path) })),
+    }
+}
+
+fn error(code: u16, msg: &str) -> (u16, Value) {
+    (code, json!({ "success": false, "errorMsg": msg }))
+}
+
+// ── L1: API key ───────────────────────────────────────────────────────────────
+fn auth(req: &Request, state: &Mutex<MockState>) -> (u16, Value) {
+    let h = |k: &str| req.headers.get(k).cloned().unwrap_or_default();
+    let Ok(address) = parse_address(&h("poly_address")) else { return error(401, "bad POLY_ADDRESS") };
+    let nonce: u64 = h("poly_nonce").parse().unwrap_or(0);
+    let mut st = state.lock().unwrap();
+
+    let digest = clob_auth_digest(st.chain_id, &address, &h("poly_timestamp"), nonce);
+    let signer = parse_signature(&h("poly_signature")).and_then(|s| recover_address(&digest, &s));
+    if signer != Some(address) {
+        return error(401, "invalid L1 signature");
+    }
+
+    let creds = st.creds.get_or_insert_with(|| ApiCreds {
+        api_key: "00000000-mock-4000-8000-000000000000".to_string(),
+        secret: "bW9jay1jbG9iLXNlY3JldC1tb2NrLWNsb2Itc2VjcmV0".to_string(),
+        passphrase: "mock-passphrase".to_string(),
+    });
+    (200, serde_json::to_value(&*creds).unwrap_or(Value::Null))
+}
+
+// ── L2: orders ────────────────────────────────────────────────────────────────
+// API key + HMAC of the request; returns the creds it was signed with
+fn check_l2(req: &Request, st: &MockState) -> Result<ApiCreds, (u16, Value)> {
+    let h = |k: &str| req.headers.get(k).cloned().unwrap_or_default();
+    let Some(creds) = st.creds.clone() else { return Err(error(401, "no API key")) };
+    if h("poly_api_key") != creds.api_key || h("poly_passphrase") != creds.passphrase {
+        return Err(error(401, "unknown API key"));
+    }
+    let path = req.path.split('?').next().unwrap_or("");
+    let expected = hmac_signature(&creds.secret, &h("poly_timestamp"), &req.method, path, &req.body);
+    if expected.ok().as_deref() != Some(h("poly_signature").as_str()) {
+        return Err(error(401, "invalid L2 signature"));
+    }
+    Ok(creds)
+}
+
+fn post_order(req: &Request, state: &Mutex<MockState>) -> (u16, Value) {
+    let mut st = state.lock().unwrap();
+    let creds = match check_l2(req, &st) {
+        Ok(c) => c,
+        Err(e) => return e,
+    };
+
+    let Ok(body) = serde_json::from_str::<Value>(&req.body) else { return error(400, "body is not JSON") };
+    let Some(order) = body.get("order").and_then(|o| serde_json::from_value::<SignedOrder>(o.clone()).ok()) else {
+        return error(400, "invalid order payload");
+    };
+    let Some(order_type) = body.get("orderType").and_then(Value::as_str).and_then(OrderType::from_wire) else {
+        return error(400, "invalid orderType");
+    };
+    if body.get("owner").and_then(Value::as_str) != Some(creds.api_key.as_str()) {
+        return error(400, "owner does not match API key");
+    }
+
+    // Either exchange may be the verifying contract
+    let Some(sig) = parse_signature(&order.signature) else { return error(400, "invalid signature") };
+    let signer = parse_address(&order.signer).ok();
+    let valid = [false, true].iter().any(|neg_risk| {
+        order.digest(&Eip712Domain::exchange(st.chain_id, *neg_risk)).ok()
+            .and_then(|d| recover_address(&d, &sig)) == signer
+    });
+    if !valid || signer.is_none() {
+        return error(400, "invalid order signature");
+    }
+
+    if let Some(msg) = st.reject_all.clone() {
+        return error(400, &msg);
+    }
+
+    // ── Matching ──
+    let limit = price_to_units(&format!("{:.4}", order.price())).unwrap_or(0);
+    let wanted = order.shares();
+    let levels = match order.side {
+        OrderSide::Buy  => st.asks.entry(order.token_id.clone()).or_default(),
+        OrderSide::Sell => st.bids.entry(order.token_id.clone()).or_default(),
+    };
+    let crosses = |p: u32| match order.side { OrderSide::Buy => p <= limit, OrderSide::Sell => p >= limit };
+    let available: f64 = levels.iter().filter(|(p, _)| crosses(**p)).map(|(_, s)| s).sum();
+
+    if order_type == OrderType::Fok && available + 1e-9 < wanted {
+        return error(400, "order couldn't be fully filled. FOK orders are fully filled or killed.");
+    }
+    if order_type == OrderType::Ioc && available <= 0.0 {
+        return error(400, "no orders found to match with FAK order. FAK orders are partially filled or killed if no match is found.");
+    }
+
+    // Best prices first
+    let prices: Vec<u32> = match order.side {
+        OrderSide::Buy  => levels.keys().copied().filter(|p| crosses(*p)).collect(),
+        OrderSide::Sell => levels.keys().rev().copied().filter(|p| crosses(*p)).collect(),
+    };
+    let mut left = wanted;
+    let mut usdc = 0.0;
+    for p in prices {
+        if left <= 1e-9 { break; }
+        let Some(size) = levels.get_mut(&p) else { continue };
+        let take = left.min(*size);
+        *size -= take;
+        left -= take;
+        usdc += take * units_to_price(p);
+        if *size <= 1e-9 { levels.remove(&p); }
+    }
+    let filled = wanted - left;
+    let status = if left <= 1e-9 { "matched" } else if order_type == OrderType::Gtc { "live" } else { "matched" };
+
+    let (making, taking) = match order.side {
+        OrderSide::Buy  => (usdc, filled),
+        OrderSide::Sell => (filled, usdc),
+    };
+    let order_id = format!("0x{:016x}{:04x}", order.salt, st.orders.len());
+    st.orders.push(MockOrder { order_id: order_id.clone(), order, order_type, status: status.to_string(), filled });
+
+    (200, json!({
+        "success": true,
+        "errorMsg": "",
+        "orderID": order_id,
+        "status": status,
+        "makingAmount": format!("{:.6}", making),
+        "takingAmount": format!("{:.6}", taking),
+    }))
+}
+
+fn cancel_all(req: &Request, state: &Mutex<MockState>) -> (u16, Value) {
+    let mut st = state.lock().unwrap();
+    if let Err(e) = check_l2(req, &st) { return e; }
+    let mut canceled = Vec::new();
+    for o in st.orders.iter_mut().filter(|o| o.status == "live") {
+        o.status = "canceled".to_string();
+        canceled.push(o.order_id.clone());
+    }
+    (200, json!({ "canceled": canceled, "not_canceled": {} }))
+}
+
+fn balance(req: &Request, state: &Mutex<MockState>) -> (u16, Value) {
+    let st = state.lock().unwrap();
+    if let Err(e) = check_l2(req, &st) { return e; }
+    (200, json!({ "balance": format!("{:.0}", st.balance * 1e6), "allowance": "0" }))
+}
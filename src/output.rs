@@ -0,0 +1,130 @@
+// output.rs — fetch report files
+//
+// Each run_fetch writes a new snapshot into config.output_dir, named from
+// config.output_filename_template with {timestamp} replaced by the UTC run time
+// (20260118T043000Z — sorts chronologically). Then:
+//   - "latest" (config.output_latest_name) is repointed at the new file, so
+//     readers have one stable path; a symlink on unix, a copy elsewhere
+//   - only the newest config.output_keep_last snapshots are kept, along with
+//     any CSV / Parquet exports of them
+//
+// Every write goes to a temporary file in the same directory, is flushed to
+// disk, and is renamed over the target, so a crash mid-write leaves the
+// previous file intact instead of a truncated one.
+
+use crate::fetch::Config;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Debug, Clone)]
+pub struct OutputSettings {
+    pub dir: String,
+    pub template: String,    // must contain {timestamp} for retention to apply
+    pub latest_name: String, // "" = no latest link
+    pub keep_last: usize,    // 0 = keep everything
+}
+
+impl OutputSettings {
+    pub fn from_config(config: &Config) -> OutputSettings {
+        OutputSettings {
+            dir: config.output_dir.clone(),
+            template: config.output_filename_template.clone(),
+            latest_name: config.output_latest_name.clone(),
+            keep_last: config.output_keep_last,
+        }
+    }
+
+    pub fn filename(&self, at: &DateTime<Utc>) -> String {
+        self.template.replace("{timestamp}", &at.format(TIMESTAMP_FORMAT).to_string())
+    }
+
+    // Whether a file name was produced by the template
+    fn is_snapshot(&self, name: &str) -> bool {
+        let Some((prefix, suffix)) = self.template.split_once("{timestamp}") else { return false };
+        name.len() > prefix.len() + suffix.len()
+            && name.starts_with(prefix)
+            && name.ends_with(suffix)
+            && chrono::NaiveDateTime::parse_from_str(&name[prefix.len()..name.len() - suffix.len()], TIMESTAMP_FORMAT).is_ok()
+    }
+}
+
+// Writes to "<path>.tmp", syncs it, then renames it over `path`
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+// Saves one report snapshot, repoints "latest", and prunes old snapshots.
+// Returns the path written.
+pub fn save_snapshot(settings: &OutputSettings, contents: &[u8], at: &DateTime<Utc>) -> std::io::Result<PathBuf> {
+    let dir = Path::new(&settings.dir);
+    fs::create_dir_all(dir)?;
+    let name = settings.filename(at);
+    let path = dir.join(&name);
+    write_atomic(&path, contents)?;
+
+    if !settings.latest_name.is_empty() && settings.latest_name != name
+        && let Err(e) = point_latest(dir, &settings.latest_name, &name, contents)
+    {
+        warn!(name = %settings.latest_name, error = %e, "failed to update latest snapshot");
+    }
+    if settings.keep_last > 0 {
+        match prune(settings) {
+            Ok(0) => {}
+            Ok(n) => info!(removed = n, "pruned old snapshots"),
+            Err(e) => warn!(dir = %settings.dir, error = %e, "failed to prune snapshots"),
+        }
+    }
+    Ok(path)
+}
+
+// Relative symlink, swapped in atomically with a rename
+#[cfg(unix)]
+fn point_latest(dir: &Path, latest: &str, target: &str, _contents: &[u8]) -> std::io::Result<()> {
+    let tmp = dir.join(format!("{}.tmp", latest));
+    let _ = fs::remove_file(&tmp);
+    std::os::unix::fs::symlink(target, &tmp)?;
+    fs::rename(&tmp, dir.join(latest))
+}
+
+#[cfg(not(unix))]
+fn point_latest(dir: &Path, latest: &str, _target: &str, contents: &[u8]) -> std::io::Result<()> {
+    write_atomic(&dir.join(latest), contents)
+}
+
+// Deletes all but the newest keep_last snapshots; returns how many went
+fn prune(settings: &OutputSettings) -> std::io::Result<usize> {
+    let mut names: Vec<String> = fs::read_dir(&settings.dir)?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|n| settings.is_snapshot(n))
+        .collect();
+    names.sort();
+    let excess = names.len().saturating_sub(settings.keep_last);
+    for name in &names[..excess] {
+        fs::remove_file(Path::new(&settings.dir).join(name))?;
+        // Exports written next to the snapshot share its stem (see export.rs)
+        if let Some(stem) = Path::new(name).file_stem().and_then(|s| s.to_str()) {
+            for ext in ["csv", "parquet"] {
+                let _ = fs::remove_file(Path::new(&settings.dir).join(format!("{}.{}", stem, ext)));
+            }
+        }
+    }
+    Ok(excess)
+}
@@ -0,0 +1,563 @@
+// paper.rs — paper-trading simulator for detected arbitrage
+//
+// Answers "what would we have made" without sending a single order. For each
+// opportunity the engine opens, the simulator waits the configured latency
+// (our reaction + round trip to the CLOB), then sends one fill-or-kill buy per
+// leg against its own copy of the order books *as they are at that moment*:
+//   - a leg fills only if enough size rests at or below the opportunity's
+//     limit price for that leg, otherwise it's killed and buys nothing
+//   - filled size is taken out of the simulated book, so two trades can't
+//     both eat the same liquidity before the next snapshot arrives
+//   - legs fill or die independently, just like real FOK orders, so a trade
+//     can end up with unhedged legs — that's the risk we want to measure
+//
+// Positions are held until a market_resolved event pays them out; realized
+// P&L is then net of taker fees and the winner fee (see fees.rs).
+//
+// Each opportunity is traded at most once per open episode. Results go to
+// Telegram subscribers and to a JSON summary file rewritten after every change.
+
+use crate::arb::{ArbEvent, ArbOpportunity, GroupKind};
+use crate::book::{price_to_units, units_to_price, OrderBook};
+use crate::fees::FeeConfig;
+use crate::fetch::Config;
+use crate::output::write_atomic;
+use crate::telegram::TgQueue;
+use crate::ws::MarketUpdate;
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone)]
+pub struct PaperSettings {
+    pub latency: Duration,   // delay between detection and the simulated orders
+    pub max_notional: f64,   // USDC cap per trade, across all legs
+    pub summary_path: String,
+}
+
+impl PaperSettings {
+    pub fn from_config(config: &Config) -> PaperSettings {
+        PaperSettings {
+            latency: Duration::from_millis(config.paper_latency_ms),
+            max_notional: config.paper_max_notional_usdc,
+            summary_path: config.paper_summary_path.clone(),
+        }
+    }
+}
+
+// ================================================================================
+// RESULTS
+// ================================================================================
+#[derive(Debug, Clone)]
+pub enum LegResult {
+    Filled { outcome: String, shares: f64, avg_price: f64, fee: f64 },
+    Killed { outcome: String, wanted: f64, available: f64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct PaperTrade {
+    pub group_id: String,
+    pub title: String,
+    pub kind: GroupKind,
+    pub sets: f64,             // sets requested per leg
+    pub expected_profit: f64,  // engine's estimate at detection, scaled to `sets`
+    pub legs: Vec<LegResult>,
+}
+
+impl PaperTrade {
+    pub fn fully_filled(&self) -> bool {
+        self.legs.iter().all(|l| matches!(l, LegResult::Filled { .. }))
+    }
+
+    pub fn spent(&self) -> f64 {
+        self.legs.iter().map(|l| match l {
+            LegResult::Filled { shares, avg_price, fee, .. } => shares * avg_price + fee,
+            LegResult::Killed { .. } => 0.0,
+        }).sum()
+    }
+
+    pub fn message(&self) -> String {
+        let status = if self.fully_filled() { "filled" } else { "PARTIAL — unhedged legs" };
+        let legs: Vec<String> = self.legs.iter().map(|l| match l {
+            LegResult::Filled { outcome, shares, avg_price, .. } => format!("✅ {} — {:.0} @ {:.3}", outcome, shares, avg_price),
+            LegResult::Killed { outcome, wanted, available } => format!("❌ {} — killed, wanted {:.0}, {:.0} available", outcome, wanted, available),
+        }).collect();
+        format!(
+            "🧪 Paper trade ({}) — {} [{}]\n{}\nSpent ${:.2} · expected profit at detection ${:.2}",
+            status, self.title, self.kind.strategy(), legs.join("\n"), self.spent(), self.expected_profit
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Position {
+    pub asset_id: String,
+    pub market: String,      // condition id, from the arb group
+    pub title: String,
+    pub outcome: String,
+    pub shares: f64,
+    pub cost: f64,           // USDC paid for the shares
+    pub fees: f64,           // taker fees paid
+    pub winner_fee_pct: f64, // charged on profit if this position wins
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PaperSummary {
+    pub trades: u64,
+    pub fully_filled: u64,
+    pub partial: u64,        // at least one leg killed
+    pub legs_filled: u64,
+    pub legs_killed: u64,
+    pub notional: f64,       // USDC spent on shares
+    pub fees: f64,           // taker + winner fees
+    pub expected_profit: f64,// sum of engine estimates for traded sets
+    pub realized_pnl: f64,   // from resolved positions
+    pub open_cost: f64,      // USDC tied up in unresolved positions
+    pub open_positions: Vec<Position>,
+    pub updated_at: String,
+}
+
+// ================================================================================
+// SIMULATOR
+// ================================================================================
+pub struct PaperTrader {
+    settings: PaperSettings,
+    fees: FeeConfig,
+    books: HashMap<String, OrderBook>,
+    markets: HashMap<String, String>,      // asset_id → market (condition id), from price_change — fallback when Gamma sent none
+    positions: HashMap<String, Position>,  // asset_id → position
+    episodes: HashSet<String>,             // groups already traded this episode
+    summary: PaperSummary,
+}
+
+impl PaperTrader {
+    pub fn new(settings: PaperSettings, fees: FeeConfig) -> PaperTrader {
+        PaperTrader {
+            settings,
+            fees,
+            books: HashMap::new(),
+            markets: HashMap::new(),
+            positions: HashMap::new(),
+            episodes: HashSet::new(),
+            summary: PaperSummary::default(),
+        }
+    }
+
+    // Keeps books and asset → market current, and settles resolved markets.
+    // Returns one message per position settled.
+    pub fn on_update(&mut self, update: &MarketUpdate) -> Vec<String> {
+        match update {
+            MarketUpdate::Book { asset_id, book } => {
+                self.books.insert(asset_id.clone(), book.clone());
+                vec![]
+            }
+            MarketUpdate::PriceChange { market, changes, .. } => {
+                for c in changes {
+                    self.markets.insert(c.asset_id.clone(), market.clone());
+                }
+                vec![]
+            }
+            MarketUpdate::MarketResolved { market, winning_asset_id } => self.settle(market, winning_asset_id),
+            _ => vec![],
+        }
+    }
+
+    // First Open of an episode → true (and remembered); repeats → false
+    pub fn should_trade(&mut self, event: &ArbEvent) -> bool {
+        match event {
+            ArbEvent::Open(opp) => self.episodes.insert(opp.group_id.clone()),
+            ArbEvent::Closed(rec) => { self.episodes.remove(&rec.group_id); false }
+        }
+    }
+
+    // Sends one FOK buy per leg against the current books. None if the
+    // notional cap doesn't allow even one whole set.
+    pub fn execute(&mut self, opp: &ArbOpportunity) -> Option<PaperTrade> {
+        let per_set = opp.notional / opp.fill_size;
+        let sets = opp.fill_size.min(self.settings.max_notional / per_set).floor();
+        if sets < 1.0 { return None; }
+        let model = self.fees.for_group(&opp.group_id, opp.legs.iter().map(|l| l.market.as_str()));
+
+        let mut legs = Vec::new();
+        for leg in &opp.legs {
+            let book = self.books.entry(leg.asset_id.clone()).or_default();
+            match fok_buy(book, sets, leg.worst_price) {
+                Ok(cost) => {
+                    let avg_price = cost / sets;
+                    let fee = model.taker_fee(avg_price, sets);
+                    let pos = self.positions.entry(leg.asset_id.clone()).or_insert_with(|| Position {
+                        asset_id: leg.asset_id.clone(),
+                        market: leg.market.clone(),
+                        title: opp.title.clone(),
+                        outcome: leg.outcome.clone(),
+                        shares: 0.0,
+                        cost: 0.0,
+                        fees: 0.0,
+                        winner_fee_pct: model.winner_fee_pct,
+                    });
+                    pos.shares += sets;
+                    pos.cost += cost;
+                    pos.fees += fee;
+                    self.summary.legs_filled += 1;
+                    self.summary.notional += cost;
+                    self.summary.fees += fee;
+                    legs.push(LegResult::Filled { outcome: leg.outcome.clone(), shares: sets, avg_price, fee });
+                }
+                Err(available) => {
+                    self.summary.legs_killed += 1;
+                    legs.push(LegResult::Killed { outcome: leg.outcome.clone(), wanted: sets, available });
+                }
+            }
+        }
+
+        let trade = PaperTrade {
+            group_id: opp.group_id.clone(),
+            title: opp.title.clone(),
+            kind: opp.kind,
+            sets,
+            expected_profit: opp.expected_profit * sets / opp.fill_size,
+            legs,
+        };
+        self.summary.trades += 1;
+        if trade.fully_filled() {
+            self.summary.fully_filled += 1;
+            self.summary.expected_profit += trade.expected_profit;
+        } else {
+            self.summary.partial += 1;
+        }
+        Some(trade)
+    }
+
+    // Pays out every position in the resolved market
+    fn settle(&mut self, market: &str, winning_asset_id: &str) -> Vec<String> {
+        let resolved: Vec<String> = self.positions.iter()
+            .filter(|(a, p)| *a == winning_asset_id || p.market == market
+                || self.markets.get(*a).is_some_and(|m| m == market))
+            .map(|(a, _)| a.clone())
+            .collect();
+
+        let mut msgs = Vec::new();
+        for asset_id in resolved {
+            let Some(pos) = self.positions.remove(&asset_id) else { continue };
+            let won = asset_id == winning_asset_id;
+            let payout = if won { pos.shares } else { 0.0 };
+            let winner_fee = if won { pos.winner_fee_pct / 100.0 * (payout - pos.cost).max(0.0) } else { 0.0 };
+            let pnl = payout - pos.cost - pos.fees - winner_fee;
+            self.summary.fees += winner_fee;
+            self.summary.realized_pnl += pnl;
+            msgs.push(format!(
+                "🧪 Paper position resolved — {} · {}\n{} {:.0} shares → ${:.2} · P&L ${:.2} (total ${:.2})",
+                pos.title, pos.outcome, if won { "WON" } else { "lost" }, pos.shares, payout, pnl, self.summary.realized_pnl
+            ));
+        }
+        msgs
+    }
+
+    pub fn summary(&self) -> PaperSummary {
+        let mut summary = self.summary.clone();
+        summary.open_positions = self.positions.values().cloned().collect();
+        summary.open_positions.sort_by(|a, b| a.title.cmp(&b.title).then(a.outcome.cmp(&b.outcome)));
+        summary.open_cost = summary.open_positions.iter().map(|p| p.cost).sum();
+        summary.updated_at = Utc::now().to_rfc3339();
+        summary
+    }
+
+    pub fn write_summary(&self) {
+        let path = &self.settings.summary_path;
+        match serde_json::to_string_pretty(&self.summary()) {
+            Ok(json) => {
+                if let Err(e) = write_atomic(Path::new(path), json.as_bytes()) {
+                    warn!(path = %path, error = %e, "failed to write paper summary");
+                }
+            }
+            Err(e) => warn!(error = %e, "failed to serialize paper summary"),
+        }
+    }
+}
+
+// ── Fill-or-kill against a local book ─────────────────────────────────────────
+// Buys exactly `shares` from asks priced ≤ limit. Ok(total cost) and the
+// levels are consumed; Err(available size) and the book is untouched.
+fn fok_buy(book: &mut OrderBook, shares: f64, limit: f64) -> Result<f64, f64> {
+    let Some(limit_units) = price_to_units(&limit.to_string()) else { return Err(0.0) };
+    let available: f64 = book.asks.range(..=limit_units).map(|(_, s)| s).sum();
+    if shares <= 0.0 || available + 1e-9 < shares { return Err(available); }
+
+    let mut left = shares;
+    let mut cost = 0.0;
+    let mut emptied = Vec::new();
+    for (price, size) in book.asks.range_mut(..=limit_units) {
+        let take = left.min(*size);
+        cost += take * units_to_price(*price);
+        *size -= take;
+        left -= take;
+        if *size <= 1e-9 { emptied.push(*price); }
+        if left <= 1e-9 { break; }
+    }
+    for price in emptied { book.asks.remove(&price); }
+    Ok(cost)
+}
+
+// ── Background task ───────────────────────────────────────────────────────────
+// Tracks books/resolutions from the update stream and trades every new
+// opportunity after the configured latency.
+pub fn spawn(
+    mut updates: broadcast::Receiver<MarketUpdate>,
+    mut arbs: broadcast::Receiver<ArbEvent>,
+    mut trader: PaperTrader,
+    tg: Option<TgQueue>,
+) {
+    tokio::spawn(async move {
+        let (due_tx, mut due_rx) = mpsc::unbounded_channel::<ArbOpportunity>();
+        let latency = trader.settings.latency;
+        let notify = |text: String| {
+            info!(message = %text, "paper trading");
+            if let Some(tg) = &tg { tg.broadcast(text); }
+        };
+
+        loop {
+            tokio::select! {
+                update = updates.recv() => match update {
+                    Ok(update) => {
+                        let msgs = trader.on_update(&update);
+                        if !msgs.is_empty() { trader.write_summary(); }
+                        for m in msgs { notify(m); }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => warn!(skipped = n, "fell behind market updates"),
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                event = arbs.recv() => match event {
+                    Ok(event) => {
+                        if !trader.should_trade(&event) { continue; }
+                        let ArbEvent::Open(opp) = event else { continue };
+                        let due_tx = due_tx.clone();
+                        tokio::spawn(async move {
+                            tokio::time::sleep(latency).await;
+                            let _ = due_tx.send(opp);
+                        });
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "fell behind the arb engine, forgetting episodes");
+                        trader.episodes.clear();
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                Some(opp) = due_rx.recv() => {
+                    let Some(trade) = trader.execute(&opp) else { continue };
+                    trader.write_summary();
+                    notify(trade.message());
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arb::{ArbLeg, ArbRecord};
+    use crate::book::PRICE_SCALE;
+    use serde_json::json;
+
+    fn near(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn book(asks: &[(f64, f64)]) -> OrderBook {
+        OrderBook {
+            asks: asks.iter().map(|(p, s)| ((p * PRICE_SCALE).round() as u32, *s)).collect(),
+            in_sync: true,
+            ..OrderBook::default()
+        }
+    }
+
+    fn settings(max_notional: f64) -> PaperSettings {
+        PaperSettings { latency: Duration::from_millis(250), max_notional, summary_path: String::new() }
+    }
+
+    // Binary A/B in market "0xm": legs limited at `limits`, `fill_size` sets
+    // detected at a combined 0.95 per set
+    fn opp(limits: [f64; 2], fill_size: f64) -> ArbOpportunity {
+        let leg = |asset: &str, outcome: &str, limit: f64| ArbLeg {
+            asset_id: asset.to_string(),
+            outcome: outcome.to_string(),
+            market: "0xm".to_string(),
+            price: limit,
+            size: fill_size,
+            avg_price: limit,
+            worst_price: limit,
+        };
+        ArbOpportunity {
+            group_id: "1001:Who wins?".to_string(),
+            title: "A vs B".to_string(),
+            kind: GroupKind::Binary,
+            legs: vec![leg("111", "A", limits[0]), leg("222", "B", limits[1])],
+            cost: 0.95,
+            payout: 1.0,
+            edge: 0.05,
+            fill_size,
+            notional: 0.95 * fill_size,
+            fees: 0.0,
+            gas: 0.0,
+            expected_profit: 0.05 * fill_size,
+            detected_at: Utc::now(),
+        }
+    }
+
+    fn trader(max_notional: f64, fees: FeeConfig) -> PaperTrader {
+        let mut trader = PaperTrader::new(settings(max_notional), fees);
+        trader.on_update(&MarketUpdate::Book { asset_id: "111".to_string(), book: book(&[(0.55, 60.0), (0.56, 100.0)]) });
+        trader.on_update(&MarketUpdate::Book { asset_id: "222".to_string(), book: book(&[(0.40, 100.0)]) });
+        trader
+    }
+
+    #[test]
+    fn fok_buy_walks_levels_up_to_the_limit() {
+        let mut b = book(&[(0.40, 50.0), (0.42, 50.0), (0.50, 100.0)]);
+        let cost = fok_buy(&mut b, 80.0, 0.42).expect("enough size at or below 0.42");
+        assert!(near(cost, 50.0 * 0.40 + 30.0 * 0.42), "{}", cost);
+        assert_eq!(b.asks.values().copied().collect::<Vec<_>>(), [20.0, 100.0]);
+
+        // Not enough left under the limit: killed, book untouched
+        assert_eq!(fok_buy(&mut b, 30.0, 0.42), Err(20.0));
+        assert_eq!(b.asks.values().copied().collect::<Vec<_>>(), [20.0, 100.0]);
+        assert_eq!(fok_buy(&mut b, 0.0, 0.42), Err(20.0));
+    }
+
+    #[test]
+    fn execute_sizes_sets_to_the_notional_cap() {
+        // 0.95 per set: $50 buys 52 whole sets of the 100 detected
+        let mut capped = trader(50.0, FeeConfig::default());
+        let trade = capped.execute(&opp([0.56, 0.40], 100.0)).expect("trade");
+        assert_eq!(trade.sets, 52.0);
+        assert!(near(trade.expected_profit, 0.05 * 52.0), "{}", trade.expected_profit);
+
+        // Less than one set's worth is not traded at all
+        let mut tiny = trader(0.5, FeeConfig::default());
+        assert!(tiny.execute(&opp([0.56, 0.40], 100.0)).is_none());
+        assert_eq!(tiny.summary().trades, 0);
+    }
+
+    #[test]
+    fn full_fill_opens_positions_net_of_taker_fees() {
+        let fees: FeeConfig = serde_json::from_value(json!({ "taker_fee_bps": 100 })).expect("fees");
+        let mut trader = trader(1000.0, fees);
+        let trade = trader.execute(&opp([0.56, 0.40], 100.0)).expect("trade");
+        assert!(trade.fully_filled(), "{:?}", trade.legs);
+
+        // 60 @ 0.55 + 40 @ 0.56 = 55.4 on A, 40 on B, 1% of each on top
+        let [LegResult::Filled { avg_price: a, fee: fee_a, .. }, LegResult::Filled { avg_price: b, fee: fee_b, .. }] = trade.legs.as_slice()
+            else { panic!("{:?}", trade.legs) };
+        assert!(near(*a, 0.554) && near(*b, 0.40), "{} {}", a, b);
+        assert!(near(*fee_a, 0.554) && near(*fee_b, 0.40), "{} {}", fee_a, fee_b);
+        assert!(near(trade.spent(), 95.4 + 0.954), "{}", trade.spent());
+
+        let summary = trader.summary();
+        assert_eq!((summary.trades, summary.fully_filled, summary.partial), (1, 1, 0));
+        assert_eq!((summary.legs_filled, summary.legs_killed), (2, 0));
+        assert!(near(summary.notional, 95.4) && near(summary.open_cost, 95.4), "{:?}", summary);
+        let positions: Vec<(&str, f64)> = summary.open_positions.iter().map(|p| (p.asset_id.as_str(), p.shares)).collect();
+        assert_eq!(positions, [("111", 100.0), ("222", 100.0)]);
+    }
+
+    #[test]
+    fn killed_leg_leaves_the_other_unhedged() {
+        // Only 60 of A rest at or below 0.55
+        let mut trader = trader(1000.0, FeeConfig::default());
+        let trade = trader.execute(&opp([0.55, 0.40], 100.0)).expect("trade");
+        assert!(!trade.fully_filled());
+        assert!(matches!(&trade.legs[0], LegResult::Killed { wanted, available, .. } if *wanted == 100.0 && *available == 60.0), "{:?}", trade.legs);
+        assert!(matches!(&trade.legs[1], LegResult::Filled { shares, .. } if *shares == 100.0), "{:?}", trade.legs);
+        assert!(trade.message().contains("PARTIAL"), "{}", trade.message());
+
+        let summary = trader.summary();
+        assert_eq!((summary.trades, summary.fully_filled, summary.partial), (1, 0, 1));
+        assert_eq!((summary.legs_filled, summary.legs_killed), (1, 1));
+        assert_eq!(summary.expected_profit, 0.0);
+        assert_eq!(summary.open_positions.len(), 1);
+    }
+
+    #[test]
+    fn filled_size_is_taken_out_of_the_book() {
+        let mut trader = trader(1000.0, FeeConfig::default());
+        assert!(trader.execute(&opp([0.56, 0.40], 100.0)).expect("first").fully_filled());
+
+        // B's only level is gone until the next snapshot
+        let trade = trader.execute(&opp([0.56, 0.40], 50.0)).expect("second");
+        assert!(matches!(&trade.legs[1], LegResult::Killed { available, .. } if *available == 0.0), "{:?}", trade.legs);
+    }
+
+    #[test]
+    fn resolution_settles_with_the_winner_fee() {
+        let fees: FeeConfig = serde_json::from_value(json!({ "winner_fee_pct": 2.0 })).expect("fees");
+        let mut trader = trader(1000.0, fees);
+        trader.execute(&opp([0.56, 0.40], 100.0)).expect("trade");
+
+        let msgs = trader.on_update(&MarketUpdate::MarketResolved { market: "0xm".to_string(), winning_asset_id: "111".to_string() });
+        assert_eq!(msgs.len(), 2, "{:?}", msgs);
+
+        // A: 100 − 55.4 − 2% of 44.6 profit; B: −40
+        let summary = trader.summary();
+        assert!(near(summary.realized_pnl, 100.0 - 55.4 - 0.892 - 40.0), "{}", summary.realized_pnl);
+        assert!(near(summary.fees, 0.892), "{}", summary.fees);
+        assert!(summary.open_positions.is_empty());
+        assert_eq!(summary.open_cost, 0.0);
+    }
+
+    #[test]
+    fn one_trade_per_episode() {
+        let mut trader = trader(1000.0, FeeConfig::default());
+        let open = ArbEvent::Open(opp([0.56, 0.40], 100.0));
+        assert!(trader.should_trade(&open));
+        assert!(!trader.should_trade(&open), "still the same window");
+
+        let closed = ArbEvent::Closed(ArbRecord {
+            group_id: "1001:Who wins?".to_string(),
+            title: "A vs B".to_string(),
+            league: String::new(),
+            kind: GroupKind::Binary,
+            opened_at_ms: 0,
+            closed_at_ms: 1,
+            peak_edge: 0.05,
+            peak_size: 100.0,
+            peak_profit: 5.0,
+            updates: 1,
+        });
+        assert!(!trader.should_trade(&closed));
+        assert!(trader.should_trade(&open), "next window");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn trades_after_the_latency_against_the_books_then() {
+        let path = std::env::temp_dir().join(format!("paper_summary_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let settings = PaperSettings { summary_path: path.to_string_lossy().into_owned(), ..settings(1000.0) };
+        let (updates_tx, _) = broadcast::channel(16);
+        let (arbs_tx, _) = broadcast::channel(16);
+        spawn(updates_tx.subscribe(), arbs_tx.subscribe(), PaperTrader::new(settings, FeeConfig::default()), None);
+
+        let send_book = |asset: &str, asks: &[(f64, f64)]| {
+            updates_tx.send(MarketUpdate::Book { asset_id: asset.to_string(), book: book(asks) }).expect("update receiver");
+        };
+        send_book("111", &[(0.55, 100.0)]);
+        send_book("222", &[(0.40, 100.0)]);
+        arbs_tx.send(ArbEvent::Open(opp([0.55, 0.40], 100.0))).expect("arb receiver");
+
+        // B moves away during the delay: the trade sees the book at fill time
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        send_book("222", &[(0.45, 100.0)]);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!path.exists(), "traded before the latency elapsed");
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let json = std::fs::read_to_string(&path).expect("summary written after the trade");
+        let summary: serde_json::Value = serde_json::from_str(&json).expect("summary json");
+        assert_eq!((summary["trades"].as_u64(), summary["partial"].as_u64()), (Some(1), Some(1)));
+        assert_eq!(summary["legs_killed"].as_u64(), Some(1));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,326 @@
+// portfolio.rs — live positions, USDC balance and P&L
+//
+// Tracks what the executor actually holds:
+//   - per outcome token: shares, average entry price, the market it belongs to
+//   - USDC balance: moved by every fill, and re-synced from the CLOB
+//     (/balance-allowance) periodically so deposits and outside trades show up
+//   - realized P&L from unwinds (sells) and from resolutions
+//   - unrealized P&L, marked to the mid of the local order book
+//   - final P&L per position once its market resolves: the winning token pays
+//     $1 a share, every other token in the market pays nothing
+//
+// State is one JSON file (config.portfolio_path) rewritten atomically after
+// every fill and resolution and, while marks move, every SAVE_INTERVAL. It is
+// loaded at startup, and main.rs reads it to answer /positions and /pnl. A
+// file that doesn't parse is moved aside to "<path>.corrupt-<unix secs>"
+// rather than silently overwritten by an empty portfolio.
+
+use crate::exec::ClobClient;
+use crate::executor::ExecReport;
+use crate::output::write_atomic;
+use crate::telegram::TgQueue;
+use crate::ws::MarketUpdate;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+const BALANCE_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Holding {
+    pub asset_id: String,
+    pub market: String, // condition id, from the arb group (or the stream)
+    pub title: String,
+    pub outcome: String,
+    pub shares: f64,
+    pub avg_price: f64,
+    pub mark: Option<f64>, // last book mid
+}
+
+impl Holding {
+    pub fn cost_basis(&self) -> f64 {
+        self.shares * self.avg_price
+    }
+
+    pub fn unrealized(&self) -> f64 {
+        self.mark.map_or(0.0, |m| self.shares * (m - self.avg_price))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedPosition {
+    pub title: String,
+    pub outcome: String,
+    pub shares: f64,
+    pub avg_price: f64,
+    pub won: bool,
+    pub payout: f64,
+    pub pnl: f64,
+    pub resolved_at: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Portfolio {
+    #[serde(skip)]
+    path: String,
+    pub usdc: f64,
+    pub usdc_synced_at: Option<String>,
+    pub holdings: BTreeMap<String, Holding>, // asset id → holding
+    pub realized_pnl: f64,
+    pub resolved: Vec<ResolvedPosition>,
+    pub updated_at: String,
+    #[serde(default)]
+    markets: HashMap<String, String>, // asset id → market, learned from the stream
+}
+
+impl Portfolio {
+    pub fn load(path: &str) -> Portfolio {
+        let mut p: Portfolio = match std::fs::read_to_string(path) {
+            Ok(s) => serde_json::from_str(&s).unwrap_or_else(|e| {
+                let backup = format!("{}.corrupt-{}", path, Utc::now().timestamp());
+                match std::fs::rename(path, &backup) {
+                    Ok(()) => error!(path, error = %e, backup = %backup, "portfolio doesn't parse, moved aside, starting empty"),
+                    Err(re) => error!(path, error = %e, rename_error = %re, "portfolio doesn't parse and couldn't be moved aside"),
+                }
+                Portfolio::default()
+            }),
+            Err(_) => Portfolio::default(),
+        };
+        p.path = path.to_string();
+        p
+    }
+
+    pub fn save(&mut self) {
+        self.updated_at = Utc::now().to_rfc3339();
+        match serde_json::to_string_pretty(self) {
+            Ok(json) => {
+                if let Err(e) = write_atomic(Path::new(&self.path), json.as_bytes()) {
+                    error!(path = %self.path, error = %e, "failed to write portfolio");
+                }
+            }
+            Err(e) => error!(error = %e, "failed to serialize portfolio"),
+        }
+    }
+
+    // ── Fills ─────────────────────────────────────────────────────────────────
+    pub fn buy(&mut self, asset_id: &str, market: &str, title: &str, outcome: &str, shares: f64, usdc: f64) {
+        if shares <= 0.0 { return; }
+        let market = match market {
+            "" => self.markets.get(asset_id).cloned().unwrap_or_default(),
+            m => m.to_string(),
+        };
+        let h = self.holdings.entry(asset_id.to_string()).or_insert_with(|| Holding {
+            asset_id: asset_id.to_string(),
+            market,
+            title: title.to_string(),
+            outcome: outcome.to_string(),
+            shares: 0.0,
+            avg_price: 0.0,
+            mark: None,
+        });
+        h.avg_price = (h.cost_basis() + usdc) / (h.shares + shares);
+        h.shares += shares;
+        self.usdc -= usdc;
+    }
+
+    // Realized P&L is the proceeds over the average entry of the shares sold
+    pub fn sell(&mut self, asset_id: &str, shares: f64, usdc: f64) {
+        if shares <= 0.0 { return; }
+        self.usdc += usdc;
+        let Some(h) = self.holdings.get_mut(asset_id) else {
+            self.realized_pnl += usdc;
+            return;
+        };
+        let sold = shares.min(h.shares);
+        self.realized_pnl += usdc - sold * h.avg_price;
+        h.shares -= sold;
+        if h.shares < 1e-6 {
+            self.holdings.remove(asset_id);
+        }
+    }
+
+    // Every leg of an execution: buys, then any shares sold back
+    pub fn record(&mut self, report: &ExecReport) {
+        for leg in &report.legs {
+            self.buy(&leg.asset_id, &leg.market, &report.title, &leg.outcome, leg.filled, leg.cost);
+            self.sell(&leg.asset_id, leg.unwound, leg.proceeds);
+        }
+    }
+
+    pub fn sync_usdc(&mut self, usdc: f64) {
+        self.usdc = usdc;
+        self.usdc_synced_at = Some(Utc::now().to_rfc3339());
+    }
+
+    // ── Stream ────────────────────────────────────────────────────────────────
+    // Marks held tokens and settles resolved markets. Returns one message per
+    // position settled; `changed` is set when anything worth saving moved.
+    pub fn on_update(&mut self, update: &MarketUpdate, changed: &mut bool) -> Vec<String> {
+        match update {
+            MarketUpdate::Book { asset_id, book } => {
+                let Some(h) = self.holdings.get_mut(asset_id) else { return vec![] };
+                let mid = match (book.best_bid(), book.best_ask()) {
+                    (Some((b, _)), Some((a, _))) => Some((b + a) / 2.0),
+                    (Some((b, _)), None) => Some(b),
+                    (None, Some((a, _))) => Some(a),
+                    (None, None) => None,
+                };
+                if mid.is_some() && mid != h.mark {
+                    h.mark = mid;
+                    *changed = true;
+                }
+                vec![]
+            }
+            MarketUpdate::PriceChange { market, changes, .. } => {
+                for c in changes {
+                    self.markets.insert(c.asset_id.clone(), market.clone());
+                    if let Some(h) = self.holdings.get_mut(&c.asset_id).filter(|h| h.market.is_empty()) {
+                        h.market = market.clone();
+                        *changed = true;
+                    }
+                }
+                vec![]
+            }
+            MarketUpdate::MarketResolved { market, winning_asset_id } => {
+                let msgs = self.settle(market, winning_asset_id);
+                *changed |= !msgs.is_empty();
+                msgs
+            }
+            _ => vec![],
+        }
+    }
+
+    fn settle(&mut self, market: &str, winning_asset_id: &str) -> Vec<String> {
+        let settled: Vec<String> = self.holdings.values()
+            .filter(|h| h.asset_id == winning_asset_id || (!h.market.is_empty() && h.market == market))
+            .map(|h| h.asset_id.clone())
+            .collect();
+
+        let mut msgs = Vec::new();
+        for asset_id in settled {
+            let Some(h) = self.holdings.remove(&asset_id) else { continue };
+            let won = asset_id == winning_asset_id;
+            let payout = if won { h.shares } else { 0.0 };
+            let pnl = payout - h.cost_basis();
+            self.usdc += payout;
+            self.realized_pnl += pnl;
+            msgs.push(format!(
+                "🏁 Position resolved — {} · {}\n{} {:.2} shares @ {:.3} → ${:.2} · P&L ${:.2}",
+                h.title, h.outcome, if won { "WON" } else { "lost" }, h.shares, h.avg_price, payout, pnl
+            ));
+            self.resolved.push(ResolvedPosition {
+                title: h.title,
+                outcome: h.outcome,
+                shares: h.shares,
+                avg_price: h.avg_price,
+                won,
+                payout,
+                pnl,
+                resolved_at: Utc::now().to_rfc3339(),
+            });
+        }
+        msgs
+    }
+
+    // ── Reporting ─────────────────────────────────────────────────────────────
+    pub fn unrealized_pnl(&self) -> f64 {
+        self.holdings.values().map(Holding::unrealized).sum()
+    }
+
+    // USDC plus every holding at its mark (at cost when never marked)
+    pub fn equity(&self) -> f64 {
+        self.usdc + self.holdings.values()
+            .map(|h| h.shares * h.mark.unwrap_or(h.avg_price))
+            .sum::<f64>()
+    }
+
+    // Telegram reply to /positions
+    pub fn positions_text(&self) -> String {
+        if self.holdings.is_empty() {
+            return "No open positions".to_string();
+        }
+        let mut lines = vec![format!("📂 {} open positions", self.holdings.len())];
+        for h in self.holdings.values() {
+            let mark = h.mark.map_or("—".to_string(), |m| format!("{:.3}", m));
+            lines.push(format!(
+                "{} · {}\n  {:.2} @ {:.3} · mark {} · uP&L ${:.2}",
+                h.title, h.outcome, h.shares, h.avg_price, mark, h.unrealized()
+            ));
+        }
+        lines.push(format!("Updated {}", self.updated_at));
+        lines.join("\n")
+    }
+
+    // Telegram reply to /pnl
+    pub fn pnl_text(&self) -> String {
+        let won = self.resolved.iter().filter(|r| r.won).count();
+        let final_pnl: f64 = self.resolved.iter().map(|r| r.pnl).sum();
+        let synced = self.usdc_synced_at.as_deref().unwrap_or("never");
+        format!(
+            "💰 P&L\nRealized: ${:.2}\nUnrealized: ${:.2} (marked to mid)\nResolved positions: {} ({} won), final P&L ${:.2}\nUSDC: ${:.2} (synced {})\nEquity: ${:.2}\nUpdated {}",
+            self.realized_pnl, self.unrealized_pnl(), self.resolved.len(), won, final_pnl,
+            self.usdc, synced, self.equity(), self.updated_at
+        )
+    }
+}
+
+// ── Background task ───────────────────────────────────────────────────────────
+// Marks and settles from the stream, re-syncs the balance, and saves. The
+// executor records fills into the same Portfolio (see executor::spawn).
+pub fn spawn(
+    mut updates: broadcast::Receiver<MarketUpdate>,
+    portfolio: Arc<Mutex<Portfolio>>,
+    clob: Option<Arc<ClobClient>>,
+    tg: Option<TgQueue>,
+) {
+    tokio::spawn(async move {
+        let mut save = tokio::time::interval(SAVE_INTERVAL);
+        let mut sync = tokio::time::interval(BALANCE_SYNC_INTERVAL);
+        let mut dirty = false;
+        loop {
+            tokio::select! {
+                msg = updates.recv() => match msg {
+                    Ok(update) => {
+                        let mut settled = false;
+                        let msgs = portfolio.lock().unwrap().on_update(&update, &mut dirty);
+                        for text in msgs {
+                            info!(message = %text, "position resolved");
+                            if let Some(tg) = &tg { tg.broadcast(text); }
+                            settled = true;
+                        }
+                        if settled {
+                            portfolio.lock().unwrap().save();
+                            dirty = false;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "fell behind the WS pool");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = save.tick() => {
+                    if dirty {
+                        portfolio.lock().unwrap().save();
+                        dirty = false;
+                    }
+                }
+                _ = sync.tick() => {
+                    let Some(clob) = &clob else { continue };
+                    match clob.usdc_balance().await {
+                        Ok(usdc) => {
+                            portfolio.lock().unwrap().sync_usdc(usdc);
+                            dirty = true;
+                        }
+                        Err(e) => warn!(error = %e, "balance sync failed"),
+                    }
+                }
+            }
+        }
+    });
+}
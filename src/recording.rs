@@ -0,0 +1,138 @@
+// recording.rs — WebSocket session recordings
+//
+// A recording is gzip-compressed NDJSON, one line per thing the stream took in,
+// stamped with the time we received it (ms since epoch):
+//
+//   {"t":…,"kind":"msg","shard":0,"text":"<raw frame>"}        market-channel frame
+//   {"t":…,"kind":"resync","shard":0,"book":{…}}               REST /book applied
+//   {"t":…,"kind":"subscribe","tokens":[["<id>","<name>"],…]}  names for the ids
+//   {"t":…,"kind":"unsubscribe","ids":["<id>",…]}
+//   {"t":…,"kind":"meta","data":{…}}                           caller context,
+//                                                              e.g. Gamma events
+//
+// That is everything the handling pipeline in ws.rs consumes, so replaying a
+// recording through it (ws::Replay) rebuilds the same books and publishes the
+// same updates.
+//
+// Lines go to a writer thread that flushes about once a second, each flush as
+// its own gzip member. Concatenated members are still one valid .gz file, and a
+// crash loses at most the last second instead of leaving the whole file
+// without a trailer. Read back with MultiGzDecoder (`zcat` works too).
+
+use chrono::Utc;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedEvent {
+    Msg { shard: usize, text: String },
+    Resync { shard: usize, book: Value },
+    Subscribe { tokens: Vec<(String, String)> },
+    Unsubscribe { ids: Vec<String> },
+    Meta { data: Value },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recorded {
+    pub t: i64,
+    #[serde(flatten)]
+    pub event: RecordedEvent,
+}
+
+// ================================================================================
+// WRITER
+// ================================================================================
+#[derive(Clone)]
+pub struct Recorder {
+    tx: mpsc::Sender<String>,
+    path: PathBuf,
+}
+
+impl Recorder {
+    // New file <dir>/ws_<UTC timestamp>.ndjson.gz
+    pub fn create(dir: &str) -> std::io::Result<Recorder> {
+        fs::create_dir_all(dir)?;
+        let path = Path::new(dir).join(format!("ws_{}.ndjson.gz", Utc::now().format("%Y%m%dT%H%M%SZ")));
+        let file = File::create(&path)?;
+        let (tx, rx) = mpsc::channel::<String>();
+        let shown = path.display().to_string();
+        std::thread::spawn(move || write_loop(file, rx, shown));
+        Ok(Recorder { tx, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, event: RecordedEvent) {
+        self.record_at(Utc::now().timestamp_millis(), event);
+    }
+
+    pub fn record_at(&self, t: i64, event: RecordedEvent) {
+        if let Ok(line) = serde_json::to_string(&Recorded { t, event }) {
+            let _ = self.tx.send(line); // writer gone = file error already reported
+        }
+    }
+}
+
+// Batches lines and writes one gzip member per flush; ends when every
+// Recorder clone is dropped
+fn write_loop(mut file: File, rx: mpsc::Receiver<String>, path: String) {
+    let mut pending: Vec<String> = Vec::new();
+    let mut last_flush = Instant::now();
+    loop {
+        let open = match rx.recv_timeout(FLUSH_INTERVAL) {
+            Ok(line) => { pending.push(line); true }
+            Err(mpsc::RecvTimeoutError::Timeout) => true,
+            Err(mpsc::RecvTimeoutError::Disconnected) => false,
+        };
+        if !pending.is_empty() && (!open || last_flush.elapsed() >= FLUSH_INTERVAL) {
+            let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+            let written = pending.iter().try_for_each(|l| writeln!(gz, "{}", l))
+                .and_then(|_| gz.finish())
+                .and_then(|bytes| file.write_all(&bytes));
+            if let Err(e) = written {
+                error!(path = %path, error = %e, "failed to write recording, recording stopped");
+                return;
+            }
+            pending.clear();
+            last_flush = Instant::now();
+        }
+        if !open { return; }
+    }
+}
+
+// ================================================================================
+// READER
+// ================================================================================
+// Streams the records of a recording in order; unreadable lines are skipped
+pub fn read_recording(path: &str) -> std::io::Result<impl Iterator<Item = Recorded> + use<>> {
+    let reader = BufReader::new(MultiGzDecoder::new(File::open(path)?));
+    Ok(reader.lines()
+        .map_while(Result::ok)
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| serde_json::from_str(&l).ok()))
+}
+
+// Every meta record's data, in order — read up front by a replay to rebuild
+// whatever context the recording session was started with
+pub fn read_meta(path: &str) -> std::io::Result<Vec<Value>> {
+    Ok(read_recording(path)?
+        .filter_map(|r| match r.event {
+            RecordedEvent::Meta { data } => Some(data),
+            _ => None,
+        })
+        .collect())
+}
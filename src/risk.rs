@@ -0,0 +1,277 @@
+// risk.rs — guardrails for unattended execution
+//
+// Every opportunity the executor wants to take goes through RiskManager first.
+// It sizes the trade to whatever headroom is left under each limit:
+//   - per trade   — USDC across all legs of one execution
+//   - per market  — USDC held in one arb group (binary market or N-way set)
+//   - per event   — USDC held across all groups of one event
+//   - per day     — USDC spent on buys since 00:00 UTC
+// and refuses it outright when too many positions are already open. Positions
+// are released when a market_resolved event pays out one of their tokens.
+//
+// Failed executions — shares left exposed, or a leg the CLOB errored on — are
+// counted; after too many in a row the kill switch trips on its own. A killed
+// FOK that filled nothing, or a clean unwind, is the market moving, not a
+// failure: it neither counts nor resets the streak. Complete sets reset it.
+//
+// Kill switch: a flag checked before every single order, so flipping it halts
+// submission immediately — including hedges and unwinds already under way. It
+// is engaged by:
+//   - the consecutive-failure limit above
+//   - the kill file (config.kill_switch_path) appearing; `/kill` in Telegram
+//     writes it, and so can anyone with shell access (`touch KILL`)
+// Once engaged, spawn() cancels every resting order and tells subscribers. It
+// stays engaged for the life of the process: delete the file and restart to
+// resume trading.
+
+use crate::arb::ArbOpportunity;
+use crate::exec::ClobClient;
+use crate::executor::{ExecOutcome, ExecReport};
+use crate::fetch::Config;
+use crate::telegram::TgQueue;
+use crate::ws::MarketUpdate;
+use chrono::{NaiveDate, Utc};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, Notify};
+use tracing::{error, info, warn};
+
+const KILL_POLL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone)]
+pub struct RiskLimits {
+    pub max_trade_notional: f64,
+    pub max_market_notional: f64,
+    pub max_event_notional: f64,
+    pub max_daily_notional: f64,
+    pub max_open_positions: usize,
+    pub max_consecutive_failures: u32,
+}
+
+impl RiskLimits {
+    pub fn from_config(config: &Config) -> RiskLimits {
+        RiskLimits {
+            max_trade_notional: config.exec_max_notional_usdc,
+            max_market_notional: config.risk_max_market_notional_usdc,
+            max_event_notional: config.risk_max_event_notional_usdc,
+            max_daily_notional: config.risk_max_daily_notional_usdc,
+            max_open_positions: config.risk_max_open_positions,
+            max_consecutive_failures: config.risk_max_consecutive_failures,
+        }
+    }
+}
+
+// ================================================================================
+// KILL SWITCH
+// ================================================================================
+pub struct KillSwitch {
+    engaged: AtomicBool,
+    reason: Mutex<String>,
+    path: String, // kill file; empty = no file switch
+    tripped: Notify, // wakes the risk task as soon as the switch flips
+}
+
+impl KillSwitch {
+    pub fn new(path: &str) -> KillSwitch {
+        KillSwitch {
+            engaged: AtomicBool::new(false),
+            reason: Mutex::new(String::new()),
+            path: path.to_string(),
+            tripped: Notify::new(),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> KillSwitch {
+        KillSwitch::new(&config.kill_switch_path)
+    }
+
+    // Engages the switch; true if this call flipped it
+    pub fn engage(&self, reason: &str) -> bool {
+        let mut r = self.reason.lock().unwrap();
+        if self.engaged.swap(true, Ordering::SeqCst) { return false; }
+        *r = reason.to_string();
+        error!(reason = %reason, "kill switch engaged");
+        self.tripped.notify_one();
+        true
+    }
+
+    // Resolves once the switch has been engaged in-process
+    pub async fn tripped(&self) {
+        self.tripped.notified().await
+    }
+
+    // Checked before every order: the flag, then the kill file
+    pub fn is_engaged(&self) -> bool {
+        if self.engaged.load(Ordering::SeqCst) { return true; }
+        if !self.path.is_empty() && Path::new(&self.path).exists() {
+            let note = std::fs::read_to_string(&self.path).unwrap_or_default();
+            let note = note.trim();
+            self.engage(&if note.is_empty() { format!("kill file {} present", self.path) } else { note.to_string() });
+            return true;
+        }
+        false
+    }
+
+    pub fn reason(&self) -> String {
+        self.reason.lock().unwrap().clone()
+    }
+}
+
+// Creates the kill file; any process watching it halts within KILL_POLL
+pub fn write_kill_file(path: &str, reason: &str) -> std::io::Result<()> {
+    std::fs::write(path, format!("{}\n", reason))
+}
+
+// ================================================================================
+// LIMITS
+// ================================================================================
+#[derive(Default)]
+struct RiskState {
+    market: HashMap<String, f64>,              // group → USDC held
+    event: HashMap<String, f64>,               // event → USDC held
+    open: HashMap<String, HashSet<String>>,    // group → its token ids
+    day: Option<NaiveDate>,
+    daily: f64,                                // USDC bought today
+    failures: u32,                             // consecutive failed executions
+}
+
+pub struct RiskManager {
+    limits: RiskLimits,
+    kill: Arc<KillSwitch>,
+    state: Mutex<RiskState>,
+}
+
+// Group ids are "<event id>:<rest>" (see arb.rs)
+fn event_of(group_id: &str) -> &str {
+    group_id.split(':').next().unwrap_or(group_id)
+}
+
+impl RiskManager {
+    pub fn new(limits: RiskLimits, kill: Arc<KillSwitch>) -> RiskManager {
+        RiskManager { limits, kill, state: Mutex::new(RiskState::default()) }
+    }
+
+    pub fn kill_switch(&self) -> &Arc<KillSwitch> {
+        &self.kill
+    }
+
+    // How many sets of this opportunity we may buy, or why none
+    pub fn allowed_sets(&self, opp: &ArbOpportunity) -> Result<f64, String> {
+        if self.kill.is_engaged() {
+            return Err(format!("kill switch engaged ({})", self.kill.reason()));
+        }
+        let mut st = self.state.lock().unwrap();
+        let today = Utc::now().date_naive();
+        if st.day != Some(today) {
+            st.day = Some(today);
+            st.daily = 0.0;
+        }
+
+        if !st.open.contains_key(&opp.group_id) && st.open.len() >= self.limits.max_open_positions {
+            return Err(format!("{} positions open (max {})", st.open.len(), self.limits.max_open_positions));
+        }
+
+        let market = st.market.get(&opp.group_id).copied().unwrap_or(0.0);
+        let event = st.event.get(event_of(&opp.group_id)).copied().unwrap_or(0.0);
+        let headroom = [
+            ("trade", self.limits.max_trade_notional),
+            ("market", self.limits.max_market_notional - market),
+            ("event", self.limits.max_event_notional - event),
+            ("daily", self.limits.max_daily_notional - st.daily),
+        ];
+        let (binding, usdc) = headroom.iter().copied()
+            .fold(("", f64::INFINITY), |a, b| if b.1 < a.1 { b } else { a });
+
+        let per_set = opp.notional / opp.fill_size;
+        let sets = opp.fill_size.min(usdc / per_set).floor();
+        if sets < 1.0 {
+            return Err(format!("{} notional limit reached (${:.2} left)", binding, usdc.max(0.0)));
+        }
+        Ok(sets)
+    }
+
+    // Books an execution's spend and counts failures; may trip the kill switch
+    pub fn record(&self, opp: &ArbOpportunity, report: &ExecReport) {
+        let bought: f64 = report.legs.iter().map(|l| l.cost).sum();
+        let held = report.net_cost();
+        let mut st = self.state.lock().unwrap();
+
+        st.daily += bought;
+        if held > 0.0 {
+            *st.market.entry(opp.group_id.clone()).or_default() += held;
+            *st.event.entry(event_of(&opp.group_id).to_string()).or_default() += held;
+            st.open.entry(opp.group_id.clone()).or_default()
+                .extend(opp.legs.iter().map(|l| l.asset_id.clone()));
+        }
+
+        let errored = report.legs.iter().any(|l| l.error.is_some());
+        if report.outcome == ExecOutcome::Complete && !errored {
+            st.failures = 0;
+            return;
+        }
+        if report.outcome != ExecOutcome::Exposed && !errored {
+            return;
+        }
+        st.failures += 1;
+        if st.failures >= self.limits.max_consecutive_failures {
+            let reason = format!("{} consecutive failed executions", st.failures);
+            drop(st);
+            self.kill.engage(&reason);
+        }
+    }
+
+    // A resolution pays out every group holding the winning token
+    pub fn on_update(&self, update: &MarketUpdate) {
+        let MarketUpdate::MarketResolved { winning_asset_id, .. } = update else { return };
+        let mut st = self.state.lock().unwrap();
+        let settled: Vec<String> = st.open.iter()
+            .filter(|(_, assets)| assets.contains(winning_asset_id))
+            .map(|(g, _)| g.clone())
+            .collect();
+        for group in settled {
+            st.open.remove(&group);
+            let usdc = st.market.remove(&group).unwrap_or(0.0);
+            if let Some(e) = st.event.get_mut(event_of(&group)) {
+                *e = (*e - usdc).max(0.0);
+            }
+            info!(group_id = %group, released_usdc = usdc, "position settled");
+        }
+    }
+}
+
+// ── Background task ───────────────────────────────────────────────────────────
+// Releases settled positions, polls the kill file, and when the switch trips
+// (file, failure streak or /kill) cancels all resting orders and tells
+// subscribers.
+pub fn spawn(mut updates: broadcast::Receiver<MarketUpdate>, risk: Arc<RiskManager>, clob: Arc<ClobClient>, tg: Option<TgQueue>) {
+    tokio::spawn(async move {
+        let mut poll = tokio::time::interval(KILL_POLL);
+        let mut handled = false;
+        loop {
+            tokio::select! {
+                msg = updates.recv() => match msg {
+                    Ok(update) => risk.on_update(&update),
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "fell behind the WS pool");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = poll.tick() => {}
+                _ = risk.kill.tripped() => {}
+            }
+            if handled || !risk.kill.is_engaged() { continue; }
+            handled = true;
+
+            let cancelled = match clob.cancel_all().await {
+                Ok(ids) => format!("{} resting orders cancelled", ids.len()),
+                Err(e) => format!("cancelling resting orders failed: {}", e),
+            };
+            let text = format!("🛑 Kill switch engaged: {} — order submission halted, {}", risk.kill.reason(), cancelled);
+            error!(reason = %risk.kill.reason(), cancelled = %cancelled, "order submission halted");
+            if let Some(tg) = &tg { tg.broadcast(text); }
+        }
+    });
+}
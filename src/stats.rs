@@ -0,0 +1,258 @@
+// stats.rs — persisted arb lifecycles and duration statistics
+//
+// Every ArbRecord the engine closes is appended as one JSON line to
+// config.arb_log_path, so the history survives restarts and can be read by
+// the bot's /stats command and the arb_report binary without coordination.
+//
+// The report answers "how long do windows stay open, and where": duration
+// distribution overall and broken down by league, strategy and hour of day
+// (HST, to match the rest of the bot's output).
+
+use crate::arb::{ArbEvent, ArbRecord};
+use crate::fetch::hst_hour;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use tokio::sync::broadcast;
+use tracing::warn;
+
+// ================================================================================
+// LOG FILE
+// ================================================================================
+pub fn append_record(path: &str, rec: &ArbRecord) -> std::io::Result<()> {
+    let line = serde_json::to_string(rec).map_err(std::io::Error::other)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+// Reads every record in the log; missing file → empty, bad lines are skipped
+pub fn load_records(path: &str) -> Vec<ArbRecord> {
+    let Ok(text) = fs::read_to_string(path) else { return vec![] };
+    text.lines()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+// Appends every Closed event from the engine to the log
+pub fn spawn_recorder(mut events: broadcast::Receiver<ArbEvent>, path: String) {
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(ArbEvent::Closed(rec)) => {
+                    if let Err(e) = append_record(&path, &rec) {
+                        warn!(path = %path, error = %e, "failed to write arb record");
+                    }
+                }
+                Ok(ArbEvent::Open(_)) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(skipped = n, "fell behind the arb engine, some closes may be missing");
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+}
+
+// ================================================================================
+// DISTRIBUTIONS
+// ================================================================================
+#[derive(Debug, Clone, Default)]
+pub struct Distribution {
+    pub count: usize,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub max: f64,
+}
+
+impl Distribution {
+    pub fn from_values(mut values: Vec<f64>) -> Distribution {
+        if values.is_empty() { return Distribution::default(); }
+        values.sort_by(f64::total_cmp);
+        let pct = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+        Distribution {
+            count: values.len(),
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p50: pct(0.5),
+            p90: pct(0.9),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+// Duration and profit stats for one slice of the history
+#[derive(Debug, Clone, Default)]
+pub struct Bucket {
+    pub duration: Distribution, // seconds
+    pub peak_profit: Distribution,
+}
+
+impl Bucket {
+    fn from_records(recs: &[&ArbRecord]) -> Bucket {
+        Bucket {
+            duration: Distribution::from_values(recs.iter().map(|r| r.duration_secs()).collect()),
+            peak_profit: Distribution::from_values(recs.iter().map(|r| r.peak_profit).collect()),
+        }
+    }
+
+    fn line(&self, label: &str) -> String {
+        format!(
+            "{:<24} n={:<5} dur mean={:.1}s p50={:.1}s p90={:.1}s max={:.1}s | profit mean=${:.2} max=${:.2}",
+            label, self.duration.count, self.duration.mean, self.duration.p50, self.duration.p90, self.duration.max,
+            self.peak_profit.mean, self.peak_profit.max
+        )
+    }
+}
+
+// ================================================================================
+// REPORT
+// ================================================================================
+pub struct StatsReport {
+    pub overall: Bucket,
+    pub by_league: BTreeMap<String, Bucket>,
+    pub by_kind: BTreeMap<String, Bucket>,
+    pub by_hour: BTreeMap<u32, Bucket>, // HST hour the opportunity opened
+}
+
+impl StatsReport {
+    pub fn build(records: &[ArbRecord]) -> StatsReport {
+        let all: Vec<&ArbRecord> = records.iter().collect();
+        let group = |key: &dyn Fn(&ArbRecord) -> String| {
+            let mut map: BTreeMap<String, Vec<&ArbRecord>> = BTreeMap::new();
+            for r in records { map.entry(key(r)).or_default().push(r); }
+            map.into_iter().map(|(k, v)| (k, Bucket::from_records(&v))).collect::<BTreeMap<_, _>>()
+        };
+
+        let mut hours: BTreeMap<u32, Vec<&ArbRecord>> = BTreeMap::new();
+        for r in records {
+            if let Some(opened) = DateTime::<Utc>::from_timestamp_millis(r.opened_at_ms) {
+                hours.entry(hst_hour(&opened)).or_default().push(r);
+            }
+        }
+
+        StatsReport {
+            overall: Bucket::from_records(&all),
+            by_league: group(&|r| r.league.clone()),
+            by_kind: group(&|r| r.kind.strategy().to_string()),
+            by_hour: hours.into_iter().map(|(h, v)| (h, Bucket::from_records(&v))).collect(),
+        }
+    }
+
+    // Compact version for Telegram: overall, then league and strategy
+    pub fn summary(&self) -> String {
+        if self.overall.duration.count == 0 {
+            return "No closed arb opportunities recorded yet.".to_string();
+        }
+        let d = &self.overall.duration;
+        let mut out = format!(
+            "📊 Arb windows: {}\nDuration mean {:.1}s · median {:.1}s · p90 {:.1}s · max {:.1}s\nPeak profit mean ${:.2} · max ${:.2}",
+            d.count, d.mean, d.p50, d.p90, d.max, self.overall.peak_profit.mean, self.overall.peak_profit.max
+        );
+        for (title, map) in [("By league", &self.by_league), ("By strategy", &self.by_kind)] {
+            out.push_str(&format!("\n\n{}:", title));
+            for (k, b) in map {
+                out.push_str(&format!(
+                    "\n• {} — {} · median {:.1}s · mean {:.1}s",
+                    k, b.duration.count, b.duration.p50, b.duration.mean
+                ));
+            }
+        }
+        out
+    }
+
+    // Full terminal report, including the time-of-day breakdown
+    pub fn render(&self) -> String {
+        let mut out = vec![self.overall.line("ALL")];
+        out.push(String::new());
+        out.push("── By league ──".to_string());
+        out.extend(self.by_league.iter().map(|(k, b)| b.line(k)));
+        out.push(String::new());
+        out.push("── By strategy ──".to_string());
+        out.extend(self.by_kind.iter().map(|(k, b)| b.line(k)));
+        out.push(String::new());
+        out.push("── By hour opened (HST) ──".to_string());
+        out.extend(self.by_hour.iter().map(|(h, b)| b.line(&format!("{:02}:00", h))));
+        out.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arb::GroupKind;
+
+    // 2023-11-14 22:13:20 UTC = 12:13 HST
+    const T0: i64 = 1_700_000_000_000;
+
+    fn record(league: &str, kind: GroupKind, opened_at_ms: i64, secs: f64, peak_profit: f64) -> ArbRecord {
+        ArbRecord {
+            group_id: format!("{}:{:?}", league, kind),
+            title: "A vs B".to_string(),
+            league: league.to_string(),
+            kind,
+            opened_at_ms,
+            closed_at_ms: opened_at_ms + (secs * 1000.0) as i64,
+            peak_edge: 0.05,
+            peak_size: 100.0,
+            peak_profit,
+            updates: 1,
+        }
+    }
+
+    #[test]
+    fn distribution_of_values() {
+        let d = Distribution::from_values(vec![7.0, 1.0, 10.0, 4.0, 2.0, 9.0, 3.0, 8.0, 6.0, 5.0]);
+        assert_eq!(d.count, 10);
+        assert_eq!(d.mean, 5.5);
+        // Nearest rank on the sorted values: index round(9 × 0.5) = 5, round(9 × 0.9) = 8
+        assert_eq!((d.p50, d.p90, d.max), (6.0, 9.0, 10.0));
+
+        let one = Distribution::from_values(vec![2.5]);
+        assert_eq!((one.count, one.mean, one.p50, one.p90, one.max), (1, 2.5, 2.5, 2.5, 2.5));
+
+        let empty = Distribution::from_values(vec![]);
+        assert_eq!((empty.count, empty.mean, empty.max), (0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn report_buckets() {
+        let hour = 3_600_000;
+        let records = vec![
+            record("nba", GroupKind::Binary, T0, 2.0, 5.0),
+            record("nba", GroupKind::Binary, T0 + 10 * hour, 4.0, 1.0),
+            record("epl", GroupKind::AllNo, T0, 30.0, 9.0),
+        ];
+        let report = StatsReport::build(&records);
+        assert_eq!(report.overall.duration.count, 3);
+        assert_eq!(report.overall.duration.max, 30.0);
+        assert_eq!(report.overall.peak_profit.max, 9.0);
+
+        assert_eq!(report.by_league.keys().collect::<Vec<_>>(), ["epl", "nba"]);
+        assert_eq!(report.by_league["nba"].duration.mean, 3.0);
+        assert_eq!(report.by_kind["No on all"].duration.count, 1);
+        assert_eq!(report.by_hour.keys().copied().collect::<Vec<_>>(), [12, 22]);
+        assert_eq!(report.by_hour[&12].duration.count, 2);
+
+        assert!(report.summary().starts_with("📊 Arb windows: 3"), "{}", report.summary());
+        assert_eq!(StatsReport::build(&[]).summary(), "No closed arb opportunities recorded yet.");
+    }
+
+    #[test]
+    fn log_round_trip_skips_bad_lines() {
+        let path = std::env::temp_dir().join(format!("arb_log_{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let _ = fs::remove_file(&path);
+        assert!(load_records(&path).is_empty());
+
+        append_record(&path, &record("nba", GroupKind::Binary, T0, 2.0, 5.0)).expect("append");
+        fs::OpenOptions::new().append(true).open(&path).and_then(|mut f| writeln!(f, "not json\n")).expect("append junk");
+        append_record(&path, &record("epl", GroupKind::AllYes, T0, 1.0, 2.0)).expect("append");
+
+        let loaded = load_records(&path);
+        assert_eq!(loaded.iter().map(|r| (r.league.as_str(), r.kind)).collect::<Vec<_>>(), [("nba", GroupKind::Binary), ("epl", GroupKind::AllYes)]);
+        assert_eq!(loaded[0].duration_secs(), 2.0);
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,60 @@
+// tape.rs — per-asset trade tape
+//
+// Every last_trade_price event is appended to the asset's tape. Only the most
+// recent TAPE_LEN trades are kept per asset — enough for "what traded just
+// now" questions without growing forever on a busy market.
+
+use crate::book::Side;
+use crate::messages::{parse_f64, parse_ts, LastTradePriceMsg};
+use std::collections::{HashMap, VecDeque};
+
+pub const TAPE_LEN: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub price: f64,
+    pub size: f64,
+    pub side: Option<Side>, // taker side, when reported
+    pub fee_rate_bps: Option<u32>,
+    pub timestamp: u64,     // ms
+}
+
+impl Trade {
+    pub fn from_msg(msg: &LastTradePriceMsg) -> Option<Trade> {
+        Some(Trade {
+            price: parse_f64(&msg.price)?,
+            size: parse_f64(&msg.size).unwrap_or(0.0),
+            side: Side::parse(&msg.side),
+            fee_rate_bps: msg.fee_rate_bps.as_deref().and_then(|f| f.parse().ok()),
+            timestamp: parse_ts(&msg.timestamp),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TradeTapes {
+    tapes: HashMap<String, VecDeque<Trade>>,
+}
+
+impl TradeTapes {
+    pub fn record(&mut self, asset_id: &str, trade: Trade) {
+        let tape = self.tapes.entry(asset_id.to_string()).or_default();
+        tape.push_back(trade);
+        if tape.len() > TAPE_LEN {
+            tape.pop_front();
+        }
+    }
+
+    // Oldest → newest
+    pub fn trades(&self, asset_id: &str) -> impl Iterator<Item = &Trade> {
+        self.tapes.get(asset_id).into_iter().flatten()
+    }
+
+    pub fn last(&self, asset_id: &str) -> Option<&Trade> {
+        self.tapes.get(asset_id).and_then(VecDeque::back)
+    }
+
+    pub fn remove(&mut self, asset_id: &str) {
+        self.tapes.remove(asset_id);
+    }
+}
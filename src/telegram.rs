@@ -0,0 +1,769 @@
+// telegram.rs — rate-limited outbound Telegram message queue
+//
+// Nothing in the bot talks to sendMessage directly any more. Callers push
+// text into a TgQueue handle (non-blocking) and a single background task
+// drains it while staying inside Telegram's limits:
+//   - global:   ~30 messages/second across all chats
+//   - per chat: ~1 message/second
+//
+// When Telegram still answers 429, the retry_after it returns is honored
+// before anything else is sent. Messages that pile up for the same chat are
+// coalesced into one send, and anything dropped or badly delayed is reported
+// back to that chat once the queue catches up.
+//
+// Every response is checked against Telegram's {"ok":false,...} envelope.
+// Transient failures are retried; permanent ones (chat not found, bot blocked)
+// drop the message and disable that chat in the subscriber list so the bot
+// stops sending to it. A group that was upgraded to a supergroup is moved to
+// its new chat id and the message is sent there instead.
+//
+// Only allowlisted chats (config.chat_id plus tg_allowed_chat_ids) can
+// subscribe — broadcasts carry trading and execution details.
+//
+// Telegram bot API limits:
+// https://core.telegram.org/bots/faq#my-bot-is-hitting-limits-how-do-i-avoid-this
+
+use crate::fetch::Config;
+use crate::metrics::metrics;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+// Telegram rejects message text longer than this
+const MAX_MESSAGE_LEN: usize = 4096;
+
+// Separator used when several queued messages are merged into one send
+const COALESCE_SEP: &str = "\n\n";
+
+// Transient failures are retried this many times before the message is dropped
+const MAX_ATTEMPTS: u32 = 3;
+
+// Pause after a transient failure when Telegram didn't say how long to wait
+const TRANSIENT_BACKOFF: Duration = Duration::from_secs(2);
+
+// ================================================================================
+// LIMITS
+// Pulled out of Config once so the queue task owns plain values.
+// ================================================================================
+#[derive(Debug, Clone)]
+pub struct TgLimits {
+    pub global_per_sec: usize,
+    pub per_chat_interval: Duration,
+    pub max_pending_per_chat: usize,
+    pub delay_warn: Duration,
+}
+
+impl TgLimits {
+    pub fn from_config(config: &Config) -> TgLimits {
+        TgLimits {
+            global_per_sec: config.tg_global_per_sec.max(1),
+            per_chat_interval: Duration::from_millis(config.tg_per_chat_interval_ms),
+            max_pending_per_chat: config.tg_max_pending_per_chat.max(1),
+            delay_warn: Duration::from_secs(config.tg_delay_warn_secs),
+        }
+    }
+}
+
+// ================================================================================
+// STATS
+// Shared counters — readable from anywhere through TgQueue::stats().
+// ================================================================================
+#[derive(Debug, Default)]
+pub struct TgStats {
+    pub sent: AtomicU64,         // API calls that succeeded
+    pub coalesced: AtomicU64,    // messages merged into another send
+    pub dropped: AtomicU64,      // messages discarded because a chat's queue overflowed
+    pub delayed: AtomicU64,      // messages that waited longer than delay_warn
+    pub rate_limited: AtomicU64, // 429 responses received
+    pub retried: AtomicU64,      // transient failures that were queued for another attempt
+    pub failed: AtomicU64,       // messages given up on (permanent error or out of attempts)
+}
+
+impl TgStats {
+    pub fn summary(&self) -> String {
+        format!(
+            "sent={} coalesced={} dropped={} delayed={} rate_limited={} retried={} failed={}",
+            self.sent.load(Ordering::Relaxed),
+            self.coalesced.load(Ordering::Relaxed),
+            self.dropped.load(Ordering::Relaxed),
+            self.delayed.load(Ordering::Relaxed),
+            self.rate_limited.load(Ordering::Relaxed),
+            self.retried.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed),
+        )
+    }
+}
+
+// ================================================================================
+// ERRORS
+// Typed view of Telegram's {"ok":false,"error_code":...,"description":...}
+// envelope. is_permanent() decides whether a retry could ever succeed.
+// ================================================================================
+#[derive(Debug, Clone)]
+pub enum TgError {
+    Network(String),            // request never got a response (timeout, DNS, TLS...)
+    RateLimited(Duration),      // 429 — wait retry_after and try again
+    Server(i64, String),        // 5xx — Telegram's side, try again later
+    Malformed(String),          // response body wasn't a Telegram envelope
+    Unauthorized(String),       // 401 — bot token wrong or revoked
+    ChatNotFound(String),       // 400 — chat_id doesn't exist or bot was never added
+    BotBlocked(String),         // 403 — user blocked the bot / bot kicked from the group
+    Forbidden(String),          // any other 403 — e.g. no rights to post, chat still exists
+    ChatMigrated(i64),          // 400 — group became a supergroup with a new id
+    BadRequest(i64, String),    // any other 4xx — this message will never be accepted
+}
+
+impl TgError {
+    // Permanent errors are never retried
+    pub fn is_permanent(&self) -> bool {
+        !matches!(self, TgError::Network(_) | TgError::RateLimited(_) | TgError::Server(..) | TgError::Malformed(_))
+    }
+
+    // Errors that mean this chat will never receive anything again
+    // (a migrated chat lives on under its new id instead)
+    pub fn disables_chat(&self) -> bool {
+        matches!(self, TgError::ChatNotFound(_) | TgError::BotBlocked(_))
+    }
+
+    // Maps a failed envelope onto the matching variant
+    fn from_envelope(env: Envelope) -> TgError {
+        let code = env.error_code.unwrap_or(0);
+        let desc = env.description.unwrap_or_default();
+        let params = env.parameters.unwrap_or_default();
+        let lower = desc.to_lowercase();
+
+        match code {
+            429 => TgError::RateLimited(Duration::from_secs(params.retry_after.unwrap_or(1))),
+            401 => TgError::Unauthorized(desc),
+            403 if ["blocked", "kicked", "deactivated", "not a member"].iter().any(|k| lower.contains(k)) => {
+                TgError::BotBlocked(desc)
+            }
+            403 => TgError::Forbidden(desc),
+            400 if params.migrate_to_chat_id.is_some() => {
+                TgError::ChatMigrated(params.migrate_to_chat_id.unwrap_or_default())
+            }
+            400 if lower.contains("chat not found") => TgError::ChatNotFound(desc),
+            c if c >= 500 => TgError::Server(c, desc),
+            c => TgError::BadRequest(c, desc),
+        }
+    }
+}
+
+impl fmt::Display for TgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TgError::Network(e)        => write!(f, "network error: {e}"),
+            TgError::RateLimited(d)    => write!(f, "rate limited, retry after {}s", d.as_secs()),
+            TgError::Server(c, d)      => write!(f, "server error {c}: {d}"),
+            TgError::Malformed(e)      => write!(f, "malformed response: {e}"),
+            TgError::Unauthorized(d)   => write!(f, "bot token rejected: {d}"),
+            TgError::ChatNotFound(d)   => write!(f, "chat not found: {d}"),
+            TgError::BotBlocked(d)     => write!(f, "bot blocked: {d}"),
+            TgError::Forbidden(d)      => write!(f, "forbidden: {d}"),
+            TgError::ChatMigrated(id)  => write!(f, "chat migrated to {id}"),
+            TgError::BadRequest(c, d)  => write!(f, "bad request {c}: {d}"),
+        }
+    }
+}
+
+impl std::error::Error for TgError {}
+
+#[derive(Deserialize)]
+struct Envelope {
+    ok: bool,
+    error_code: Option<i64>,
+    description: Option<String>,
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Deserialize, Default)]
+struct ResponseParameters {
+    retry_after: Option<u64>,
+    migrate_to_chat_id: Option<i64>,
+}
+
+// ================================================================================
+// SUBSCRIBERS
+// Chats that receive broadcasts. Seeded with config.chat_id, persisted to
+// config.subscribers_path so disabled chats stay disabled across restarts.
+// ================================================================================
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subscriber {
+    pub chat_id: String,
+    pub active: bool,
+    #[serde(default)]
+    pub disabled_reason: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Subscribers {
+    path: String,
+    list: Vec<Subscriber>,
+}
+
+impl Subscribers {
+    // Loads the file if it exists; makes sure the configured chat is present
+    pub fn load(path: &str, default_chat: &str) -> Subscribers {
+        let list: Vec<Subscriber> = fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        let mut subs = Subscribers { path: path.to_string(), list };
+        if !subs.list.iter().any(|s| s.chat_id == default_chat) {
+            subs.add(default_chat);
+        }
+        subs
+    }
+
+    // Adds or re-activates a chat. Returns true if anything changed.
+    pub fn add(&mut self, chat_id: &str) -> bool {
+        match self.list.iter_mut().find(|s| s.chat_id == chat_id) {
+            Some(s) if s.active => return false,
+            Some(s) => { s.active = true; s.disabled_reason = None; }
+            None => self.list.push(Subscriber { chat_id: chat_id.to_string(), active: true, disabled_reason: None }),
+        }
+        self.save();
+        true
+    }
+
+    // Moves a chat to the id Telegram migrated it to, keeping its state.
+    // Returns true if the old id was known.
+    pub fn migrate(&mut self, from: &str, to: &str) -> bool {
+        let Some(i) = self.list.iter().position(|s| s.chat_id == from) else { return false };
+        let old = self.list.remove(i);
+        match self.list.iter_mut().find(|s| s.chat_id == to) {
+            Some(s) => { s.active |= old.active; if s.active { s.disabled_reason = None; } }
+            None => self.list.push(Subscriber { chat_id: to.to_string(), ..old }),
+        }
+        self.save();
+        true
+    }
+
+    // Disables every active chat that isn't allowlisted — a subscribers file
+    // from before the allowlist may still hold some. Returns how many.
+    pub fn retain_allowed(&mut self, allowed: &ChatAllowlist) -> usize {
+        let mut n = 0;
+        for s in self.list.iter_mut().filter(|s| s.active && !allowed.contains(&s.chat_id)) {
+            s.active = false;
+            s.disabled_reason = Some("not in allowlist".to_string());
+            n += 1;
+        }
+        if n > 0 { self.save(); }
+        n
+    }
+
+    // Marks a chat inactive. Returns true if it was active before.
+    pub fn disable(&mut self, chat_id: &str, reason: &str) -> bool {
+        match self.list.iter_mut().find(|s| s.chat_id == chat_id && s.active) {
+            Some(s) => { s.active = false; s.disabled_reason = Some(reason.to_string()); }
+            None => return false,
+        }
+        self.save();
+        true
+    }
+
+    pub fn active(&self) -> Vec<String> {
+        self.list.iter().filter(|s| s.active).map(|s| s.chat_id.clone()).collect()
+    }
+
+    pub fn all(&self) -> &[Subscriber] {
+        &self.list
+    }
+
+    fn save(&self) {
+        let json = serde_json::to_string_pretty(&self.list).unwrap();
+        if let Err(e) = fs::write(&self.path, json) {
+            warn!(path = %self.path, error = %e, "failed to save subscribers");
+        }
+    }
+}
+
+// ── Allowlist ─────────────────────────────────────────────────────────────────
+// Chats allowed to /start and to query positions: the owner chat plus
+// config.tg_allowed_chat_ids.
+#[derive(Debug, Clone)]
+pub struct ChatAllowlist {
+    ids: Vec<String>,
+}
+
+impl ChatAllowlist {
+    pub fn from_config(config: &Config) -> ChatAllowlist {
+        let mut ids = vec![config.chat_id.clone()];
+        ids.extend(config.tg_allowed_chat_ids.iter().cloned());
+        ChatAllowlist { ids }
+    }
+
+    pub fn contains(&self, chat_id: &str) -> bool {
+        !chat_id.is_empty() && self.ids.iter().any(|id| id == chat_id)
+    }
+}
+
+// ================================================================================
+// QUEUE HANDLE
+// Cheap to clone — every clone feeds the same background task.
+// ================================================================================
+struct Outgoing {
+    chat_id: String,
+    text: String,
+    queued_at: Instant,
+    attempts: u32,
+}
+
+impl Outgoing {
+    fn new(chat_id: &str, text: String) -> Outgoing {
+        Outgoing { chat_id: chat_id.to_string(), text, queued_at: Instant::now(), attempts: 0 }
+    }
+}
+
+#[derive(Clone)]
+pub struct TgQueue {
+    tx: mpsc::UnboundedSender<Outgoing>,
+    stats: Arc<TgStats>,
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+impl TgQueue {
+    // Spawns the queue task on the current tokio runtime and returns a handle.
+    // The task exits once every handle is dropped and the queue has drained.
+    pub fn spawn(client: Client, bot_token: String, limits: TgLimits, subscribers: Subscribers) -> TgQueue {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let stats = Arc::new(TgStats::default());
+        let subscribers = Arc::new(Mutex::new(subscribers));
+        let send = move |chat_id: String, text: String| {
+            let (client, bot_token) = (client.clone(), bot_token.clone());
+            async move { send_message(&client, &bot_token, &chat_id, &text).await }
+        };
+        tokio::spawn(run_queue(rx, send, limits, stats.clone(), subscribers.clone()));
+        TgQueue { tx, stats, subscribers }
+    }
+
+    // Queues a message and returns immediately
+    pub fn send(&self, chat_id: &str, text: impl Into<String>) {
+        if self.tx.send(Outgoing::new(chat_id, truncate(text.into()))).is_err() {
+            warn!("queue task has stopped, message discarded");
+        }
+    }
+
+    // Queues the same message for every active subscriber
+    pub fn broadcast(&self, text: impl Into<String>) {
+        let text = truncate(text.into());
+        for chat_id in self.subscribers.lock().unwrap().active() {
+            self.send(&chat_id, text.clone());
+        }
+    }
+
+    pub fn subscribers(&self) -> &Mutex<Subscribers> {
+        &self.subscribers
+    }
+
+    pub fn stats(&self) -> &TgStats {
+        &self.stats
+    }
+}
+
+// ================================================================================
+// QUEUE TASK
+// ================================================================================
+
+// Per-chat bookkeeping: what's waiting, when the chat may next be sent to,
+// and any drops/delays not yet reported back to it.
+#[derive(Default)]
+struct ChatQueue {
+    pending: VecDeque<Outgoing>,
+    next_slot: Option<Instant>,
+    unreported_dropped: u64,
+    unreported_delayed: u64,
+}
+
+// `send` makes one sendMessage call — send_message() in the bot, a scripted
+// stand-in in the tests.
+async fn run_queue<S, F>(
+    mut rx: mpsc::UnboundedReceiver<Outgoing>,
+    mut send: S,
+    limits: TgLimits,
+    stats: Arc<TgStats>,
+    subscribers: Arc<Mutex<Subscribers>>,
+) where
+    S: FnMut(String, String) -> F,
+    F: Future<Output = Result<(), TgError>>,
+{
+    let mut chats: HashMap<String, ChatQueue> = HashMap::new();
+    let mut recent_sends: VecDeque<Instant> = VecDeque::new(); // sends within the last second
+    let mut paused_until: Option<Instant> = None;               // set by 429 retry_after
+    let mut closed = false;
+
+    loop {
+        // ── 1. Idle: block until something arrives ───────────────────────────
+        if chats.values().all(|c| c.pending.is_empty()) {
+            if closed { return; }
+            match rx.recv().await {
+                Some(msg) => enqueue(&mut chats, msg, &limits, &stats),
+                None => return,
+            }
+        }
+
+        // ── 2. Pull in everything else already waiting ───────────────────────
+        while let Ok(msg) = rx.try_recv() {
+            enqueue(&mut chats, msg, &limits, &stats);
+        }
+
+        // ── 3. Work out when the next send is allowed ────────────────────────
+        let now = Instant::now();
+        while recent_sends.front().is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(1)) {
+            recent_sends.pop_front();
+        }
+
+        // Chat that has been allowed to send for the longest goes first
+        let Some((chat_id, chat_slot)) = chats
+            .iter()
+            .filter(|(_, c)| !c.pending.is_empty())
+            .map(|(id, c)| (id.clone(), c.next_slot.unwrap_or(now)))
+            .min_by_key(|(_, slot)| *slot)
+        else {
+            continue;
+        };
+
+        let mut ready_at = chat_slot;
+        if let Some(p) = paused_until {
+            ready_at = ready_at.max(p);
+        }
+        if recent_sends.len() >= limits.global_per_sec {
+            ready_at = ready_at.max(recent_sends[0] + Duration::from_secs(1));
+        }
+
+        if ready_at > now {
+            // Sleep until the slot opens, but keep accepting new messages so
+            // they get a chance to be coalesced into the next send.
+            tokio::select! {
+                _ = tokio::time::sleep_until(ready_at) => {}
+                msg = rx.recv(), if !closed => match msg {
+                    Some(msg) => enqueue(&mut chats, msg, &limits, &stats),
+                    None => closed = true,
+                },
+            }
+            continue;
+        }
+
+        // ── 4. Coalesce and send ─────────────────────────────────────────────
+        let chat = chats.get_mut(&chat_id).expect("chat selected above");
+        let batch = take_batch(&mut chat.pending);
+        let text = batch.iter().map(|m| m.text.as_str()).collect::<Vec<_>>().join(COALESCE_SEP);
+
+        recent_sends.push_back(now);
+        chat.next_slot = Some(now + limits.per_chat_interval);
+
+        match send(chat_id.clone(), text).await {
+            Ok(()) => {
+                stats.sent.fetch_add(1, Ordering::Relaxed);
+                stats.coalesced.fetch_add(batch.len() as u64 - 1, Ordering::Relaxed);
+
+                let delayed = batch.iter().filter(|m| m.queued_at.elapsed() > limits.delay_warn).count() as u64;
+                if delayed > 0 {
+                    stats.delayed.fetch_add(delayed, Ordering::Relaxed);
+                    chat.unreported_delayed += delayed;
+                }
+
+                // Once the chat has caught up, tell it what it missed
+                if chat.pending.is_empty() && (chat.unreported_dropped > 0 || chat.unreported_delayed > 0) {
+                    let notice = format!(
+                        "⚠️ Telegram rate limiting: {} message(s) dropped, {} delayed by more than {}s",
+                        chat.unreported_dropped, chat.unreported_delayed, limits.delay_warn.as_secs()
+                    );
+                    warn!(chat_id = %chat_id, dropped = chat.unreported_dropped, delayed = chat.unreported_delayed, "queue backed up");
+                    chat.unreported_dropped = 0;
+                    chat.unreported_delayed = 0;
+                    chat.pending.push_back(Outgoing::new(&chat_id, notice));
+                }
+            }
+            Err(TgError::RateLimited(wait)) => {
+                // Put the batch back untouched and stop everything until Telegram is ready
+                stats.rate_limited.fetch_add(1, Ordering::Relaxed);
+                metrics().tg_send_failures.with_label_values(&["rate_limited"]).inc();
+                warn!(retry_after_secs = wait.as_secs(), "429 from Telegram, pausing sends");
+                paused_until = Some(Instant::now() + wait);
+                for msg in batch.into_iter().rev() {
+                    chat.pending.push_front(msg);
+                }
+            }
+            Err(TgError::ChatMigrated(new_id)) => {
+                // Same chat under a new id — move it and everything queued for it
+                let new_id = new_id.to_string();
+                subscribers.lock().unwrap().migrate(&chat_id, &new_id);
+                info!(chat_id = %chat_id, new_id = %new_id, moved = batch.len() + chat.pending.len(), "chat migrated");
+                let moved: Vec<Outgoing> = batch.into_iter().chain(chat.pending.drain(..)).collect();
+                chats.remove(&chat_id);
+                for mut msg in moved {
+                    msg.chat_id = new_id.clone();
+                    enqueue(&mut chats, msg, &limits, &stats);
+                }
+            }
+            Err(e) if !e.is_permanent() => {
+                metrics().tg_send_failures.with_label_values(&["transient"]).inc();
+                // Retry each message individually; give up on ones out of attempts
+                let mut requeued = 0;
+                for mut msg in batch.into_iter().rev() {
+                    msg.attempts += 1;
+                    if msg.attempts < MAX_ATTEMPTS {
+                        chat.pending.push_front(msg);
+                        requeued += 1;
+                    } else {
+                        stats.failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                stats.retried.fetch_add(requeued, Ordering::Relaxed);
+                warn!(chat_id = %chat_id, error = %e, requeued, "send failed, requeued");
+                chat.next_slot = Some(Instant::now() + TRANSIENT_BACKOFF);
+            }
+            Err(e) => {
+                metrics().tg_send_failures.with_label_values(&["permanent"]).inc();
+                stats.failed.fetch_add(batch.len() as u64, Ordering::Relaxed);
+                error!(chat_id = %chat_id, error = %e, "send failed permanently");
+
+                // Nothing queued for this chat can be delivered either
+                if e.disables_chat() {
+                    let discarded = chat.pending.len() as u64;
+                    chat.pending.clear();
+                    stats.failed.fetch_add(discarded, Ordering::Relaxed);
+                    if subscribers.lock().unwrap().disable(&chat_id, &e.to_string()) {
+                        warn!(chat_id = %chat_id, discarded, "disabled subscriber");
+                    }
+                }
+                if let TgError::Unauthorized(_) = e {
+                    error!("bot token rejected, check bot_token in config.json");
+                }
+            }
+        }
+    }
+}
+
+// Adds a message to its chat's queue, dropping the oldest if it overflows
+fn enqueue(chats: &mut HashMap<String, ChatQueue>, msg: Outgoing, limits: &TgLimits, stats: &TgStats) {
+    let chat = chats.entry(msg.chat_id.clone()).or_default();
+    chat.pending.push_back(msg);
+    while chat.pending.len() > limits.max_pending_per_chat {
+        chat.pending.pop_front();
+        chat.unreported_dropped += 1;
+        stats.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Takes as many messages off the front as fit into one Telegram message.
+// Always takes at least one — every queued text is already within the limit.
+fn take_batch(pending: &mut VecDeque<Outgoing>) -> Vec<Outgoing> {
+    let mut batch = Vec::new();
+    let mut len = 0;
+    while let Some(next) = pending.front() {
+        let extra = if batch.is_empty() { 0 } else { COALESCE_SEP.len() };
+        if !batch.is_empty() && len + extra + next.text.len() > MAX_MESSAGE_LEN { break; }
+        len += extra + next.text.len();
+        batch.push(pending.pop_front().unwrap());
+    }
+    batch
+}
+
+// Cuts text down to MAX_MESSAGE_LEN bytes on a char boundary
+fn truncate(mut text: String) -> String {
+    if text.len() > MAX_MESSAGE_LEN {
+        let mut end = MAX_MESSAGE_LEN;
+        while !text.is_char_boundary(end) { end -= 1; }
+        text.truncate(end);
+    }
+    text
+}
+
+// ================================================================================
+// SEND MESSAGE
+// One sendMessage call, with the response checked. The queue task is the only
+// caller in the bot, but it's public so one-off tools can report errors.
+// ================================================================================
+pub async fn send_message(client: &Client, bot_token: &str, chat_id: &str, text: &str) -> Result<(), TgError> {
+    let url = format!(
+        "https://api.telegram.org/bot{}/sendMessage?chat_id={}&text={}",
+        bot_token,
+        chat_id,
+        urlencoding::encode(text)
+    );
+    // without_url() keeps the bot token out of error messages
+    let resp = client.get(&url).send().await.map_err(|e| TgError::Network(e.without_url().to_string()))?;
+    let status = resp.status();
+    let body = resp.text().await.map_err(|e| TgError::Network(e.without_url().to_string()))?;
+
+    let env: Envelope = match serde_json::from_str(&body) {
+        Ok(env) => env,
+        // Proxies and outages can return HTML — judge those by status alone
+        Err(_) if status.is_server_error() => return Err(TgError::Server(status.as_u16() as i64, body)),
+        Err(e) => return Err(TgError::Malformed(format!("HTTP {}: {}", status.as_u16(), e))),
+    };
+
+    if env.ok { Ok(()) } else { Err(TgError::from_envelope(env)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (time since start, chat id, text) for every sendMessage call
+    type SendLog = Arc<Mutex<Vec<(Duration, String, String)>>>;
+
+    fn limits(global_per_sec: usize, per_chat_interval_ms: u64) -> TgLimits {
+        TgLimits {
+            global_per_sec,
+            per_chat_interval: Duration::from_millis(per_chat_interval_ms),
+            max_pending_per_chat: 10,
+            delay_warn: Duration::from_secs(60),
+        }
+    }
+
+    fn subscribers(name: &str, chats: &[&str]) -> Arc<Mutex<Subscribers>> {
+        let path = std::env::temp_dir().join(format!("tg_subscribers_{}_{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut subs = Subscribers::load(&path.to_string_lossy(), chats[0]);
+        for chat in &chats[1..] {
+            subs.add(chat);
+        }
+        Arc::new(Mutex::new(subs))
+    }
+
+    // Runs the queue task against a fake sendMessage that logs every call and
+    // answers with respond(chat id, call number)
+    fn start(
+        limits: TgLimits,
+        subscribers: Arc<Mutex<Subscribers>>,
+        respond: impl Fn(&str, usize) -> Result<(), TgError> + Send + 'static,
+    ) -> (mpsc::UnboundedSender<Outgoing>, SendLog, Arc<TgStats>, tokio::task::JoinHandle<()>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let log = SendLog::default();
+        let stats = Arc::new(TgStats::default());
+        let started = Instant::now();
+        let calls = log.clone();
+        let send = move |chat_id: String, text: String| {
+            let mut calls = calls.lock().unwrap();
+            let result = respond(&chat_id, calls.len());
+            calls.push((started.elapsed(), chat_id, text));
+            std::future::ready(result)
+        };
+        let task = tokio::spawn(run_queue(rx, send, limits, stats.clone(), subscribers));
+        (tx, log, stats, task)
+    }
+
+    fn queue(tx: &mpsc::UnboundedSender<Outgoing>, chat_id: &str, text: &str) {
+        tx.send(Outgoing::new(chat_id, text.to_string())).expect("queue task running");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn per_chat_interval_and_coalescing() {
+        let (tx, log, stats, task) = start(limits(30, 1000), subscribers("per_chat", &["1", "2"]), |_, _| Ok(()));
+        queue(&tx, "1", "a");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        // Both wait for chat 1's next slot and go out as one message;
+        // chat 2 isn't held back by chat 1's interval
+        queue(&tx, "1", "b");
+        queue(&tx, "1", "c");
+        queue(&tx, "2", "d");
+        drop(tx);
+        task.await.unwrap();
+
+        let log = log.lock().unwrap();
+        let sends: Vec<(&str, &str)> = log.iter().map(|(_, chat, text)| (chat.as_str(), text.as_str())).collect();
+        assert_eq!(sends, [("1", "a"), ("2", "d"), ("1", "b\n\nc")]);
+        assert!(log[1].0 < Duration::from_secs(1), "chat 2 sent at {:?}", log[1].0);
+        assert!(log[2].0 >= Duration::from_secs(1), "chat 1 resent at {:?}", log[2].0);
+        assert_eq!(stats.sent.load(Ordering::Relaxed), 3);
+        assert_eq!(stats.coalesced.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn global_limit_across_chats() {
+        let chats = ["1", "2", "3", "4", "5"];
+        let (tx, log, _, task) = start(limits(2, 0), subscribers("global", &chats), |_, _| Ok(()));
+        for chat in chats {
+            queue(&tx, chat, "hello");
+        }
+        drop(tx);
+        task.await.unwrap();
+
+        let at: Vec<Duration> = log.lock().unwrap().iter().map(|(t, ..)| *t).collect();
+        assert_eq!(at.len(), 5);
+        for i in 2..at.len() {
+            assert!(at[i] >= at[i - 2] + Duration::from_secs(1), "more than 2 sends within a second: {:?}", at);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_after_pauses_every_chat() {
+        let respond = |_: &str, call: usize| match call {
+            0 => Err(TgError::RateLimited(Duration::from_secs(5))),
+            _ => Ok(()),
+        };
+        let (tx, log, stats, task) = start(limits(30, 0), subscribers("retry_after", &["1", "2"]), respond);
+        queue(&tx, "1", "a");
+        queue(&tx, "2", "b");
+        drop(tx);
+        task.await.unwrap();
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 3, "{:?}", log);
+        assert!(log[1..].iter().all(|(t, ..)| *t >= Duration::from_secs(5)), "sent before retry_after: {:?}", log);
+        // The rejected message is sent again, untouched
+        assert!(log[1..].iter().any(|(_, chat, text)| *chat == log[0].1 && *text == log[0].2), "{:?}", log);
+        assert_eq!(stats.rate_limited.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.sent.load(Ordering::Relaxed), 2);
+    }
+
+    fn envelope_error(body: &str) -> TgError {
+        TgError::from_envelope(serde_json::from_str(body).expect("valid envelope"))
+    }
+
+    #[test]
+    fn envelope_errors() {
+        let e = envelope_error(r#"{"ok":false,"error_code":429,"description":"Too Many Requests: retry after 7","parameters":{"retry_after":7}}"#);
+        assert!(matches!(e, TgError::RateLimited(d) if d == Duration::from_secs(7)), "{e}");
+        assert!(!e.is_permanent());
+
+        let e = envelope_error(r#"{"ok":false,"error_code":403,"description":"Forbidden: bot was blocked by the user"}"#);
+        assert!(matches!(e, TgError::BotBlocked(_)) && e.disables_chat(), "{e}");
+        let e = envelope_error(r#"{"ok":false,"error_code":403,"description":"Forbidden: not enough rights to send text messages"}"#);
+        assert!(matches!(e, TgError::Forbidden(_)) && e.is_permanent() && !e.disables_chat(), "{e}");
+        let e = envelope_error(r#"{"ok":false,"error_code":400,"description":"Bad Request: chat not found"}"#);
+        assert!(matches!(e, TgError::ChatNotFound(_)) && e.disables_chat(), "{e}");
+        let e = envelope_error(r#"{"ok":false,"error_code":400,"description":"Bad Request: group chat was upgraded to a supergroup chat","parameters":{"migrate_to_chat_id":-1001234}}"#);
+        assert!(matches!(e, TgError::ChatMigrated(-1001234)) && !e.disables_chat(), "{e}");
+        let e = envelope_error(r#"{"ok":false,"error_code":400,"description":"Bad Request: message text is empty"}"#);
+        assert!(matches!(e, TgError::BadRequest(400, _)) && e.is_permanent(), "{e}");
+        let e = envelope_error(r#"{"ok":false,"error_code":401,"description":"Unauthorized"}"#);
+        assert!(matches!(e, TgError::Unauthorized(_)) && e.is_permanent(), "{e}");
+        let e = envelope_error(r#"{"ok":false,"error_code":502,"description":"Bad Gateway"}"#);
+        assert!(matches!(e, TgError::Server(502, _)) && !e.is_permanent(), "{e}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn blocked_chat_is_disabled() {
+        let subs = subscribers("blocked", &["1", "2"]);
+        let respond = |chat: &str, _: usize| match chat {
+            "2" => Err(TgError::BotBlocked("Forbidden: bot was blocked by the user".to_string())),
+            _ => Ok(()),
+        };
+        let (tx, log, stats, task) = start(limits(30, 0), subs.clone(), respond);
+        queue(&tx, "1", "a");
+        queue(&tx, "2", "b");
+        drop(tx);
+        task.await.unwrap();
+
+        let subs = subs.lock().unwrap();
+        assert_eq!(subs.active(), ["1"]);
+        let blocked = subs.all().iter().find(|s| s.chat_id == "2").expect("chat 2 kept");
+        assert!(blocked.disabled_reason.as_deref().is_some_and(|r| r.contains("blocked")), "{:?}", blocked);
+        // Blocked is permanent: one attempt, no retry
+        assert_eq!(log.lock().unwrap().iter().filter(|(_, chat, _)| chat == "2").count(), 1);
+        assert_eq!(stats.failed.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.sent.load(Ordering::Relaxed), 1);
+    }
+}
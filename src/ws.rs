@@ -1,162 +1,1110 @@
 // ws.rs — Polymarket CLOB WebSocket price monitor
 //
-// Connects to Polymarket's real-time order book stream and prints live
-// price updates to the terminal. Runs as a background tokio task.
+// Connects to Polymarket's real-time order book stream and publishes typed
+// MarketUpdate events on a broadcast channel. Runs as a background tokio task.
+// Nothing in here prints prices — the terminal printer (spawn_printer) is just
+// one subscriber, alongside anything else that wants to react to the feed.
+//
+// The subscription set is live: a WsHandle sends Subscribe/Unsubscribe
+// commands into the running task, which forwards them on the open socket.
+// After a reconnect the full current set is subscribed again automatically.
+//
+// Subscriptions are spread over several connections ("shards") so no single
+// socket carries hundreds of assets. A pool task places each subscribe batch on
+// the least-loaded shard with room (opening a new connection when allowed),
+// folds shards back together when unsubscribes leave them underused, and all
+// shards publish into the same update stream.
+//
+// Reconnects back off exponentially (with jitter) up to a cap. While
+// connected, a PING goes out every ping interval and the connection is torn
+// down if no market data has arrived within the stale timeout (PONGs don't
+// count) — an open but silent feed is treated the same as a dropped one, and
+// three missed PONGs mark the socket itself dead. Every state change is
+// published as a WsLifecycle event.
+//
+// With ws_record_dir set, every raw frame (and every REST resync and
+// subscription change) is written to a recording (see recording.rs). Replay
+// feeds a recording back through the same message handling, so arb logic can
+// be run deterministically against a captured session.
+//
+// Diagnostics go through tracing (see logging.rs): each shard task runs in a
+// "ws_shard" span, resyncs carry the token_id. Only spawn_printer prints.
 //
 // Polymarket WebSocket docs:
 // wss://ws-subscriptions-clob.polymarket.com/ws/market
 
+use crate::book::{Books, OrderBook, Side};
+use crate::fetch::{fetch_book, Config};
+use crate::messages::{parse_f64, parse_ts, BookMsg, MarketMessage, PriceChangeMsg};
+use crate::metrics::metrics;
+use crate::recording::{read_recording, Recorded, RecordedEvent, Recorder};
+use crate::tape::{Trade, TradeTapes};
+use crate::telegram::TgQueue;
 use futures_util::{SinkExt, StreamExt};
+use reqwest::Client;
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::Instant;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, info, info_span, warn, Instrument};
 
 const WS_URL: &str = "wss://ws-subscriptions-clob.polymarket.com/ws/market";
 
-// ── Entry point ───────────────────────────────────────────────────────────────
+// Updates buffered per subscriber before a slow one starts missing them
+const UPDATE_CHANNEL_CAPACITY: usize = 4096;
+
+// Failed REST resyncs are retried after this, doubling up to the max
+const RESYNC_RETRY_INITIAL: Duration = Duration::from_millis(500);
+const RESYNC_RETRY_MAX: Duration = Duration::from_secs(30);
+
+// ── Market updates ────────────────────────────────────────────────────────────
+// What the stream publishes. Book carries a full copy of the book after every
+// change so subscribers never need access to the stream's own state.
+#[derive(Debug, Clone)]
+pub enum MarketUpdate {
+    // Book after a snapshot, delta or REST resync
+    Book { asset_id: String, book: OrderBook },
+    // One price_change message, with top of book after it was applied
+    PriceChange { market: String, timestamp: u64, changes: Vec<LevelChange> },
+    Trade { asset_id: String, trade: Trade },
+    TickSize { asset_id: String, tick_size: f64 },
+    MarketResolved { market: String, winning_asset_id: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelChange {
+    pub asset_id: String,
+    pub outcome: String,        // display name, "" if unknown
+    pub side: Option<Side>,
+    pub price: f64,
+    pub size: f64,              // new total at this level, 0 = removed
+    pub best_bid: Option<f64>,  // from the local book after the change
+    pub best_ask: Option<f64>,
+}
+
+// ── Shared state ──────────────────────────────────────────────────────────────
+// Owned jointly by the pool, every shard and every WsHandle.
+struct Shared {
+    subscribed: RwLock<HashMap<String, String>>, // token_id → outcome_name, all shards
+    shard_sizes: RwLock<HashMap<usize, usize>>,  // shard id → assets on it
+    lifecycle: broadcast::Sender<WsLifecycle>,
+    updates: broadcast::Sender<MarketUpdate>,
+    stats: WsStats,
+    recorder: Option<Recorder>,
+}
+
+impl Shared {
+    fn new(recorder: Option<Recorder>) -> Arc<Shared> {
+        let (lifecycle, _) = broadcast::channel(64);
+        let (updates, _) = broadcast::channel(UPDATE_CHANNEL_CAPACITY);
+        Arc::new(Shared {
+            subscribed: RwLock::new(HashMap::new()),
+            shard_sizes: RwLock::new(HashMap::new()),
+            lifecycle,
+            updates,
+            stats: WsStats::default(),
+            recorder,
+        })
+    }
+
+    fn emit(&self, event: WsLifecycle) {
+        let _ = self.lifecycle.send(event); // no listeners is fine
+    }
+
+    fn publish(&self, update: MarketUpdate) {
+        let _ = self.updates.send(update);
+    }
+
+    fn record(&self, event: RecordedEvent) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(event);
+        }
+    }
+}
+
+// ── Stats ─────────────────────────────────────────────────────────────────────
+// Message counts by event_type. Unknown types are counted separately and only
+// logged the first time each one shows up.
+#[derive(Debug, Default)]
+pub struct WsStats {
+    pub by_type: Mutex<HashMap<String, u64>>,
+    pub unknown: Mutex<HashMap<String, u64>>,
+}
+
+impl WsStats {
+    fn count(&self, msg: &MarketMessage) {
+        *self.by_type.lock().unwrap().entry(msg.kind().to_string()).or_default() += 1;
+        // Unknown types share one label so the series count stays bounded
+        let label = if let MarketMessage::Unknown(_) = msg { "unknown" } else { msg.kind() };
+        metrics().ws_messages.with_label_values(&[label]).inc();
+        if let MarketMessage::Unknown(event_type) = msg {
+            let mut unknown = self.unknown.lock().unwrap();
+            let n = unknown.entry(event_type.clone()).or_default();
+            if *n == 0 {
+                warn!(event_type = %event_type, "unhandled event_type (further ones counted silently)");
+            }
+            *n += 1;
+        }
+    }
+}
+
+// ── Settings ──────────────────────────────────────────────────────────────────
+// Pulled out of Config once so the stream tasks own plain values.
+#[derive(Debug, Clone)]
+pub struct WsSettings {
+    pub backoff_initial: Duration, // first reconnect delay
+    pub backoff_max: Duration,     // reconnect delay never grows past this
+    pub ping_interval: Duration,   // how often PING is sent while connected
+    pub stale_timeout: Duration,   // no market data for this long forces a reconnect
+    pub max_shards: usize,         // most connections the pool will open
+    pub max_assets_per_shard: usize,
+    pub record_dir: String,        // "" = don't record
+}
+
+impl WsSettings {
+    pub fn from_config(config: &Config) -> WsSettings {
+        WsSettings {
+            backoff_initial: Duration::from_millis(config.ws_backoff_initial_ms.max(1)),
+            backoff_max: Duration::from_secs(config.ws_backoff_max_secs.max(1)),
+            ping_interval: Duration::from_secs(config.ws_ping_interval_secs.max(1)),
+            stale_timeout: Duration::from_secs(config.ws_stale_timeout_secs.max(1)),
+            max_shards: config.ws_max_shards.max(1),
+            max_assets_per_shard: config.ws_max_assets_per_shard.max(1),
+            record_dir: config.ws_record_dir.clone(),
+        }
+    }
+}
+
+// ── Lifecycle events ──────────────────────────────────────────────────────────
+// Published on every connection state change, tagged with the shard it came
+// from. Subscribe with WsHandle::lifecycle(); nobody listening is fine.
+#[derive(Debug, Clone)]
+pub enum WsLifecycle {
+    Connecting { shard: usize, attempt: u32 },
+    Connected { shard: usize },
+    Subscribed { shard: usize, tokens: usize },
+    Stale { shard: usize, silent_for: Duration },
+    Disconnected { shard: usize, reason: String },
+    Reconnecting { shard: usize, attempt: u32, delay: Duration },
+    ShardClosed { shard: usize },
+}
+
+// ── Commands ──────────────────────────────────────────────────────────────────
+// Sent from a WsHandle to the pool, and from the pool to individual shards.
+// A Subscribe batch is kept on one shard when it fits, so pass all tokens of
+// a market/event together.
+#[derive(Debug, Clone)]
+pub enum WsCommand {
+    Subscribe(Vec<(String, String)>), // (token_id, outcome_name) pairs to add
+    Unsubscribe(Vec<String>),         // token_ids to drop
+}
+
+// ── Handle ────────────────────────────────────────────────────────────────────
+// Cheap to clone. Dropping every handle shuts the pool and all shards down.
+#[derive(Clone)]
+pub struct WsHandle {
+    cmd_tx: mpsc::UnboundedSender<WsCommand>,
+    shared: Arc<Shared>,
+}
+
+impl WsHandle {
+    // Adds tokens to the live connection (already-subscribed ids are ignored)
+    pub fn subscribe(&self, tokens: Vec<(String, String)>) {
+        let _ = self.cmd_tx.send(WsCommand::Subscribe(tokens));
+    }
+
+    // Removes tokens from the live connection
+    pub fn unsubscribe(&self, token_ids: Vec<String>) {
+        let _ = self.cmd_tx.send(WsCommand::Unsubscribe(token_ids));
+    }
+
+    // Snapshot of the current (token_id, outcome_name) set
+    pub fn subscribed(&self) -> Vec<(String, String)> {
+        self.shared
+            .subscribed
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(id, name)| (id.clone(), name.clone()))
+            .collect()
+    }
+
+    // New receiver for connection lifecycle events
+    pub fn lifecycle(&self) -> broadcast::Receiver<WsLifecycle> {
+        self.shared.lifecycle.subscribe()
+    }
+
+    // New receiver for market updates. Only sees updates sent after this call.
+    pub fn updates(&self) -> broadcast::Receiver<MarketUpdate> {
+        self.shared.updates.subscribe()
+    }
+
+    // shard id → number of assets it carries
+    pub fn shard_sizes(&self) -> HashMap<usize, usize> {
+        self.shared.shard_sizes.read().unwrap().clone()
+    }
+
+    pub fn stats(&self) -> &WsStats {
+        &self.shared.stats
+    }
+
+    // Stores caller context in the recording (e.g. the Gamma events being
+    // watched, so a replay can rebuild arb groups); no-op when not recording
+    pub fn record_meta(&self, data: Value) {
+        self.shared.record(RecordedEvent::Meta { data });
+    }
+}
+
+// ── Entry points ──────────────────────────────────────────────────────────────
 // Takes a list of (token_id, outcome_name) pairs so we can display
 // readable names like "Fuego" and "AB3" instead of raw token IDs.
-pub async fn run(tokens: Vec<(String, String)>) {
+
+// Spawns the pool on the current runtime and returns a handle to steer it.
+// The client is only used for REST /book resyncs.
+pub fn spawn(client: Client, tokens: Vec<(String, String)>, settings: WsSettings) -> WsHandle {
+    let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+    let recorder = (!settings.record_dir.is_empty()).then(|| Recorder::create(&settings.record_dir)).and_then(|r| match r {
+        Ok(r) => {
+            info!(path = %r.path().display(), "recording WebSocket session");
+            Some(r)
+        }
+        Err(e) => {
+            warn!(dir = %settings.record_dir, error = %e, "could not start recording, continuing without");
+            None
+        }
+    });
+    let shared = Shared::new(recorder);
+    tokio::spawn(run_pool(client, shared.clone(), cmd_rx, settings));
+    let handle = WsHandle { cmd_tx, shared };
+    if !tokens.is_empty() {
+        handle.subscribe(tokens);
+    }
+    handle
+}
+
+// Runs in the foreground with a fixed token set, printing to the terminal —
+// never returns
+pub async fn run(client: Client, tokens: Vec<(String, String)>, settings: WsSettings) {
+    let handle = spawn(client, tokens, settings);
+    spawn_printer(&handle);
+    std::future::pending::<()>().await;
+}
+
+// ================================================================================
+// SHARD POOL
+// Owns the shard list and decides which connection each asset lives on.
+// Shards only ever hear about their own assets.
+// ================================================================================
+struct ShardSlot {
+    id: usize,
+    cmd_tx: mpsc::UnboundedSender<WsCommand>,
+    assets: HashMap<String, String>,
+}
+
+async fn run_pool(
+    client: Client,
+    shared: Arc<Shared>,
+    mut cmd_rx: mpsc::UnboundedReceiver<WsCommand>,
+    settings: WsSettings,
+) {
+    let mut shards: Vec<ShardSlot> = Vec::new();
+    let mut next_id = 0;
+
+    // Ends once every handle is gone; dropping `shards` closes each shard
+    while let Some(cmd) = cmd_rx.recv().await {
+        match cmd {
+            WsCommand::Subscribe(tokens) => {
+                let fresh: Vec<(String, String)> = {
+                    let known = shared.subscribed.read().unwrap();
+                    let mut seen = HashSet::new();
+                    tokens.into_iter().filter(|(id, _)| !known.contains_key(id) && seen.insert(id.clone())).collect()
+                };
+                if !fresh.is_empty() {
+                    shared.record(RecordedEvent::Subscribe { tokens: fresh.clone() });
+                }
+                place(fresh, &mut shards, &mut next_id, &client, &shared, &settings);
+            }
+            WsCommand::Unsubscribe(ids) => {
+                shared.record(RecordedEvent::Unsubscribe { ids: ids.clone() });
+                for shard in shards.iter_mut() {
+                    let mine: Vec<String> = ids.iter().filter(|id| shard.assets.remove(*id).is_some()).cloned().collect();
+                    if !mine.is_empty() {
+                        let _ = shard.cmd_tx.send(WsCommand::Unsubscribe(mine));
+                    }
+                }
+                {
+                    let mut known = shared.subscribed.write().unwrap();
+                    for id in &ids { known.remove(id); }
+                }
+                rebalance(&mut shards, &shared, &settings);
+            }
+        }
+        *shared.shard_sizes.write().unwrap() = shards.iter().map(|s| (s.id, s.assets.len())).collect();
+    }
+}
+
+// ── Placement ─────────────────────────────────────────────────────────────────
+// Whole batch onto the least-loaded shard with room; failing that a new shard;
+// failing that, token by token into whatever room is left. Tokens that don't
+// fit anywhere are dropped with a warning.
+fn place(
+    tokens: Vec<(String, String)>,
+    shards: &mut Vec<ShardSlot>,
+    next_id: &mut usize,
+    client: &Client,
+    shared: &Arc<Shared>,
+    settings: &WsSettings,
+) {
+    if tokens.is_empty() { return; }
+    let cap = settings.max_assets_per_shard;
+
+    let mut assignments: HashMap<usize, Vec<(String, String)>> = HashMap::new(); // shard index → tokens
+    let mut remaining = tokens;
+
+    while !remaining.is_empty() {
+        let target = shards
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.assets.len() + remaining.len() <= cap)
+            .min_by_key(|(_, s)| s.assets.len())
+            .map(|(i, _)| i);
+
+        let (idx, take) = match target {
+            Some(i) => (i, remaining.len()),
+            None if shards.len() < settings.max_shards => {
+                shards.push(new_shard(next_id, client, shared, settings));
+                (shards.len() - 1, remaining.len().min(cap))
+            }
+            None => {
+                // Split into whatever room is left, least-loaded first
+                match shards.iter().enumerate().filter(|(_, s)| s.assets.len() < cap).min_by_key(|(_, s)| s.assets.len()) {
+                    Some((i, s)) => (i, (cap - s.assets.len()).min(remaining.len())),
+                    None => {
+                        warn!(shards = shards.len(), dropped = remaining.len(), "all shards full, tokens not subscribed");
+                        break;
+                    }
+                }
+            }
+        };
+
+        let batch: Vec<(String, String)> = remaining.drain(..take).collect();
+        shards[idx].assets.extend(batch.iter().cloned());
+        assignments.entry(idx).or_default().extend(batch);
+    }
+
+    let mut known = shared.subscribed.write().unwrap();
+    for (idx, batch) in assignments {
+        known.extend(batch.iter().cloned());
+        let _ = shards[idx].cmd_tx.send(WsCommand::Subscribe(batch));
+    }
+}
+
+// ── Rebalancing ───────────────────────────────────────────────────────────────
+// After removals: close empty shards, then while the assets would fit on one
+// fewer connection, move the smallest shard's assets onto the others.
+fn rebalance(shards: &mut Vec<ShardSlot>, shared: &Shared, settings: &WsSettings) {
+    let cap = settings.max_assets_per_shard;
+
     loop {
-        println!("[WS] Connecting to Polymarket...");
+        let total: usize = shards.iter().map(|s| s.assets.len()).sum();
+        let needed = total.div_ceil(cap).max(1);
+        let has_empty = shards.iter().any(|s| s.assets.is_empty());
+        if shards.len() <= needed && !has_empty { return; }
+        if shards.is_empty() { return; }
+
+        // Smallest shard goes; its assets are spread over the rest
+        let (idx, _) = shards.iter().enumerate().min_by_key(|(_, s)| s.assets.len()).unwrap();
+        let victim = shards.remove(idx);
+        info!(shard = victim.id, moved = victim.assets.len(), "closing shard");
+        shared.emit(WsLifecycle::ShardClosed { shard: victim.id });
+
+        let mut moving: Vec<(String, String)> = victim.assets.into_iter().collect();
+        while !moving.is_empty() {
+            let Some(target) = shards.iter_mut().filter(|s| s.assets.len() < cap).min_by_key(|s| s.assets.len()) else {
+                // Shouldn't happen (needed < shards.len()), but never lose assets
+                warn!(dropped = moving.len(), "no room to move assets, dropping them");
+                let mut known = shared.subscribed.write().unwrap();
+                for (id, _) in &moving { known.remove(id); }
+                break;
+            };
+            let take = (cap - target.assets.len()).min(moving.len());
+            let batch: Vec<(String, String)> = moving.drain(..take).collect();
+            target.assets.extend(batch.iter().cloned());
+            let _ = target.cmd_tx.send(WsCommand::Subscribe(batch));
+        }
+        // victim.cmd_tx dropped here → that shard's task exits
+    }
+}
+
+fn new_shard(next_id: &mut usize, client: &Client, shared: &Arc<Shared>, settings: &WsSettings) -> ShardSlot {
+    let id = *next_id;
+    *next_id += 1;
+    let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+    info!(shard = id, "opening shard");
+    let span = info_span!("ws_shard", shard = id);
+    tokio::spawn(run_shard(id, client.clone(), shared.clone(), cmd_rx, settings.clone()).instrument(span));
+    ShardSlot { id, cmd_tx, assets: HashMap::new() }
+}
+
+// ================================================================================
+// SHARD
+// One WebSocket connection and its reconnect loop. Exits when the pool drops
+// its command sender.
+// ================================================================================
+async fn run_shard(
+    shard: usize,
+    client: Client,
+    shared: Arc<Shared>,
+    mut cmd_rx: mpsc::UnboundedReceiver<WsCommand>,
+    settings: WsSettings,
+) {
+    let mut backoff = Backoff::new(&settings);
+    let mut assets: HashMap<String, String> = HashMap::new();
+
+    // Take whatever the pool already queued so the first connect subscribes it
+    while let Ok(cmd) = cmd_rx.try_recv() {
+        apply_command(cmd, &mut assets, shard);
+    }
+
+    loop {
+        info!(attempt = backoff.attempt + 1, "connecting to Polymarket");
+        shared.emit(WsLifecycle::Connecting { shard, attempt: backoff.attempt + 1 });
+
+        let reason = match connect_and_stream(shard, &client, &shared, &mut assets, &mut cmd_rx, &settings, &mut backoff).await {
+            Ok(StreamEnd::Shutdown) => return,
+            Ok(StreamEnd::Closed) => "stream ended".to_string(),
+            Err(e) => e.to_string(),
+        };
+        let delay = backoff.next_delay();
+        warn!(reason = %reason, delay_ms = delay.as_millis() as u64, "disconnected, reconnecting");
+        shared.emit(WsLifecycle::Disconnected { shard, reason });
+        shared.emit(WsLifecycle::Reconnecting { shard, attempt: backoff.attempt, delay });
+        metrics().ws_reconnects.inc();
+
+        // Commands that arrive while disconnected still update the set —
+        // the next connect subscribes whatever it holds at that point.
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => break,
+                cmd = cmd_rx.recv() => match cmd {
+                    Some(cmd) => { apply_command(cmd, &mut assets, shard); }
+                    None => return,
+                },
+            }
+        }
+    }
+}
 
-        match connect_and_stream(&tokens).await {
-            Ok(_) => println!("[WS] Stream ended, reconnecting..."),
-            Err(e) => println!("[WS] Connection error: {e}, reconnecting in 5s..."),
+enum StreamEnd {
+    Closed,   // server or network ended the stream — reconnect
+    Shutdown, // pool closed this shard — stop
+}
+
+// ── Reconnect backoff ─────────────────────────────────────────────────────────
+// Doubles per consecutive failure up to backoff_max, then picks a random point
+// in the upper half of that window so many clients don't reconnect in lockstep.
+// Reset as soon as a connection delivers data.
+struct Backoff {
+    initial: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    fn new(settings: &WsSettings) -> Backoff {
+        Backoff { initial: settings.backoff_initial, max: settings.backoff_max, attempt: 0 }
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let exp = self.initial.saturating_mul(1u32 << self.attempt.min(16));
+        self.attempt += 1;
+        let ceiling = exp.min(self.max);
+        ceiling / 2 + ceiling.mul_f64(jitter() / 2.0)
+    }
+
+    fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+// Cheap 0.0..1.0 jitter from the clock's sub-second nanos — good enough to
+// spread reconnects, no RNG dependency needed.
+fn jitter() -> f64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .subsec_nanos();
+    nanos as f64 / 1_000_000_000.0
+}
+
+// ── Lifecycle → Telegram ──────────────────────────────────────────────────────
+// Broadcasts the events worth waking someone up for: the feed going stale,
+// and the connection coming back after a disconnect. Routine connect noise
+// (Connecting, the first Subscribed) stays in the terminal.
+pub fn forward_lifecycle_to_telegram(handle: &WsHandle, tg: TgQueue) {
+    let mut rx = handle.lifecycle();
+    tokio::spawn(async move {
+        let mut down: HashSet<usize> = HashSet::new();
+        loop {
+            let event = match rx.recv().await {
+                Ok(e) => e,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            match event {
+                WsLifecycle::Stale { shard, silent_for } => {
+                    tg.broadcast(format!("⚠️ WS shard {} stale — no data for {}s, reconnecting", shard, silent_for.as_secs()));
+                }
+                WsLifecycle::Reconnecting { shard, attempt, delay } if attempt >= 3 => {
+                    tg.broadcast(format!("⚠️ WS shard {} reconnect attempt {} failed, retrying in {}s", shard, attempt, delay.as_secs()));
+                    down.insert(shard);
+                }
+                WsLifecycle::Disconnected { shard, .. } => { down.insert(shard); }
+                WsLifecycle::Subscribed { shard, tokens } if down.remove(&shard) => {
+                    tg.broadcast(format!("✅ WS shard {} reconnected — {} tokens subscribed", shard, tokens));
+                }
+                WsLifecycle::ShardClosed { shard } => { down.remove(&shard); }
+                _ => {}
+            }
         }
+    });
+}
 
-        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+// ── Apply a command to a shard's asset set ────────────────────────────────────
+// Returns the subscribe/unsubscribe message to forward on the socket, or None
+// if nothing actually changed.
+fn apply_command(cmd: WsCommand, assets: &mut HashMap<String, String>, shard: usize) -> Option<Value> {
+    match cmd {
+        WsCommand::Subscribe(tokens) => {
+            let added: Vec<String> = tokens
+                .into_iter()
+                .filter_map(|(id, name)| assets.insert(id.clone(), name).is_none().then_some(id))
+                .collect();
+            if added.is_empty() { return None; }
+            info!(shard, added = added.len(), total = assets.len(), "subscribing to more tokens");
+            Some(json!({ "assets_ids": added, "operation": "subscribe" }))
+        }
+        WsCommand::Unsubscribe(ids) => {
+            let removed: Vec<String> = ids.into_iter().filter(|id| assets.remove(id).is_some()).collect();
+            if removed.is_empty() { return None; }
+            info!(shard, removed = removed.len(), total = assets.len(), "unsubscribing tokens");
+            Some(json!({ "assets_ids": removed, "operation": "unsubscribe" }))
+        }
     }
 }
 
 // ── Connect, subscribe, and stream messages ───────────────────────────────────
-async fn connect_and_stream(tokens: &[(String, String)]) -> Result<(), Box<dyn std::error::Error>> {
+async fn connect_and_stream(
+    shard: usize,
+    client: &Client,
+    shared: &Shared,
+    assets: &mut HashMap<String, String>,
+    cmd_rx: &mut mpsc::UnboundedReceiver<WsCommand>,
+    settings: &WsSettings,
+    backoff: &mut Backoff,
+) -> Result<StreamEnd, Box<dyn std::error::Error>> {
+    let started = std::time::Instant::now();
     let (mut ws, _) = connect_async(WS_URL).await?;
+    shared.emit(WsLifecycle::Connected { shard });
 
-    // Build lookup map: token_id → outcome_name for display
-    // Build token_id list for the subscription message
-    let names: HashMap<String, String> = tokens
-        .iter()
-        .map(|(id, name)| (id.clone(), name.clone()))
-        .collect();
-    let token_ids: Vec<&String> = tokens.iter().map(|(id, _)| id).collect();
+    // Subscribe the full current set — on a reconnect this restores
+    // everything added or removed since the first connect.
+    let token_ids: Vec<String> = assets.keys().cloned().collect();
 
-    println!("[WS] Connected — subscribing to {} tokens", token_ids.len());
+    info!(tokens = token_ids.len(), latency_ms = started.elapsed().as_millis() as u64, "connected, subscribing");
 
     let sub_msg = json!({
         "assets_ids": token_ids,
         "type": "market"
     });
     ws.send(Message::Text(sub_msg.to_string())).await?;
-    println!("[WS] Subscribed. Streaming live prices...\n");
+    info!(tokens = token_ids.len(), "subscribed, streaming live prices");
+    shared.emit(WsLifecycle::Subscribed { shard, tokens: token_ids.len() });
+
+    // ── Heartbeat / stale detection ───────────────────────────────────────────
+    // Polymarket expects a text "PING" roughly every 10s and answers "PONG".
+    // Two clocks: any inbound frame (PONG included) proves the socket is alive,
+    // but only market data proves the feed is — a socket that still answers
+    // PING after the data stopped is stale all the same.
+    let mut heartbeat = tokio::time::interval(settings.ping_interval);
+    heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut last_seen = Instant::now();
+    let mut last_data = Instant::now();
 
-    // ── State map ─────────────────────────────────────────────────────────────
-    // Tracks the latest known market_ask per token so we can always calculate
-    // the current sum across both tokens, even when only one side updates.
-    let mut ask_state: HashMap<String, f64> = HashMap::new();
+    // ── Per-connection market state ───────────────────────────────────────────
+    // Books are seeded from the `book` snapshots sent on subscribe and kept
+    // current from price_change deltas. Books that drift are resynced from
+    // REST /book in the background; every request reports back on resync_rx
+    // (None = it failed) and failures are retried with backoff.
+    let mut state = MarketState::default();
+    let mut resyncing: HashSet<String> = HashSet::new();
+    let mut resync_failures: HashMap<String, u32> = HashMap::new();
+    let (resync_tx, mut resync_rx) = mpsc::unbounded_channel::<(String, Option<Value>)>();
 
-    while let Some(msg) = ws.next().await {
-        match msg? {
-            Message::Text(text) => handle_message(&text, &names, &mut ask_state),
-            Message::Ping(data) => { ws.send(Message::Pong(data)).await?; }
-            Message::Close(_)   => { println!("[WS] Server closed connection"); break; }
-            _ => {}
+    loop {
+        tokio::select! {
+            msg = ws.next() => {
+                let Some(msg) = msg else { break };
+                let msg = msg?;
+                last_seen = Instant::now();
+                match msg {
+                    Message::Text(text) if text == "PONG" => {}
+                    Message::Text(text) => {
+                        last_data = Instant::now();
+                        metrics().ws_message_seen();
+                        backoff.reset();
+                        shared.record(RecordedEvent::Msg { shard, text: text.clone() });
+                        let stale = handle_message(&text, shared, &mut state);
+                        for id in stale {
+                            request_resync(client, id, Duration::ZERO, &mut resyncing, &resync_tx);
+                        }
+                    }
+                    Message::Ping(data) => { ws.send(Message::Pong(data)).await?; }
+                    Message::Close(_)   => { info!("server closed connection"); break; }
+                    _ => {}
+                }
+            }
+            _ = heartbeat.tick() => {
+                let silent_for = last_data.elapsed();
+                if silent_for >= settings.stale_timeout {
+                    warn!(silent_ms = silent_for.as_millis() as u64, "no data, treating connection as stale");
+                    shared.emit(WsLifecycle::Stale { shard, silent_for });
+                    return Err("stale feed".into());
+                }
+                // Three missed PONGs in a row — the socket itself is dead
+                let dead_for = last_seen.elapsed();
+                if dead_for >= settings.ping_interval * 3 {
+                    warn!(silent_ms = dead_for.as_millis() as u64, "no PONG, treating connection as dead");
+                    return Err("no PONG".into());
+                }
+                ws.send(Message::Text("PING".to_string())).await?;
+            }
+            Some((id, book)) = resync_rx.recv() => {
+                resyncing.remove(&id);
+                if !assets.contains_key(&id) {
+                    resync_failures.remove(&id);
+                    continue;
+                }
+                let applied = book.is_some_and(|book| {
+                    shared.record(RecordedEvent::Resync { shard, book: book.clone() });
+                    apply_resync(book, shared, &mut state)
+                });
+                if applied {
+                    resync_failures.remove(&id);
+                } else {
+                    let failures = resync_failures.entry(id.clone()).or_default();
+                    *failures += 1;
+                    let delay = resync_retry_delay(*failures);
+                    warn!(token_id = %id, failures = *failures, retry_ms = delay.as_millis() as u64, "resync failed, retrying");
+                    request_resync(client, id, delay, &mut resyncing, &resync_tx);
+                }
+            }
+            cmd = cmd_rx.recv() => {
+                let Some(cmd) = cmd else {
+                    let _ = ws.close(None).await;
+                    return Ok(StreamEnd::Shutdown);
+                };
+                if let WsCommand::Unsubscribe(ids) = &cmd {
+                    for id in ids { state.remove(id); }
+                }
+                if let Some(op) = apply_command(cmd, assets, shard) {
+                    ws.send(Message::Text(op.to_string())).await?;
+                }
+            }
         }
     }
 
-    Ok(())
+    Ok(StreamEnd::Closed)
+}
+
+// ── Market state ──────────────────────────────────────────────────────────────
+// Everything the stream has learned about its assets on this connection.
+// Tick sizes live on the books (see book.rs) since they describe the book.
+#[derive(Default)]
+struct MarketState {
+    books: Books,
+    tapes: TradeTapes,
+}
+
+impl MarketState {
+    fn remove(&mut self, asset_id: &str) {
+        self.books.remove(asset_id);
+        self.tapes.remove(asset_id);
+    }
+}
+
+// ── Background REST resync ────────────────────────────────────────────────────
+// At most one request in flight (or waiting to retry) per token. The outcome
+// always comes back to the stream loop — the raw snapshot on success, None on
+// failure — so books are only ever touched from one place, the snapshot can
+// be recorded exactly as it was applied, and a failed token is never stuck.
+fn request_resync(
+    client: &Client,
+    token_id: String,
+    delay: Duration,
+    resyncing: &mut HashSet<String>,
+    resync_tx: &mpsc::UnboundedSender<(String, Option<Value>)>,
+) {
+    if !resyncing.insert(token_id.clone()) { return; }
+    let client = client.clone();
+    let tx = resync_tx.clone();
+    let span = info_span!("resync", token_id = %token_id);
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let book = fetch_book(&client, &token_id).await;
+        let _ = tx.send((token_id, book));
+    }.instrument(span));
+}
+
+// Doubles per consecutive failure of the same token, capped
+fn resync_retry_delay(failures: u32) -> Duration {
+    RESYNC_RETRY_INITIAL.saturating_mul(1u32 << failures.saturating_sub(1).min(16)).min(RESYNC_RETRY_MAX)
+}
+
+// Applies a REST /book body. False if it wasn't a usable snapshot — an error
+// body, or a crossed book — and the token still needs one.
+fn apply_resync(book: Value, shared: &Shared, state: &mut MarketState) -> bool {
+    let Ok(snap) = serde_json::from_value::<BookMsg>(book) else { return false };
+    let id = snap.asset_id.clone();
+    if state.books.apply_snapshot(&snap).is_some() { return false; }
+    info!(token_id = %id, "book resynced from REST");
+    publish_book(shared, &state.books, &id);
+    true
 }
 
 // ── Route each message by event_type ─────────────────────────────────────────
-fn handle_message(text: &str, names: &HashMap<String, String>, ask_state: &mut HashMap<String, f64>) {
+// Updates local state, publishes the matching MarketUpdates, and returns the
+// token_ids whose books need a fresh snapshot.
+fn handle_message(text: &str, shared: &Shared, state: &mut MarketState) -> Vec<String> {
     let msg: Value = match serde_json::from_str(text) {
         Ok(v) => v,
-        Err(_) => { println!("[WS] Unparseable: {text}"); return; }
+        Err(e) => { debug!(error = %e, text, "unparseable message"); return vec![]; }
     };
 
-    match msg.get("event_type").and_then(Value::as_str) {
-        Some("price_change") => print_price_change(&msg, names, ask_state),
-        Some("book")         => seed_state_from_book(&msg, ask_state), // seeds initial ask state, no display
-        Some("last_trade_price") => {} // on-chain receipt only, ask state unchanged
-        Some(other)          => println!("[WS] Unhandled event_type: {other}"),
-        None                 => {} // subscription ack, safe to ignore
+    // The initial snapshot arrives as an array of `book` messages
+    let msgs = match msg {
+        Value::Array(arr) => arr,
+        other => vec![other],
+    };
+
+    let mut stale = Vec::new();
+    for msg in msgs {
+        // No event_type = subscription ack, safe to ignore
+        let Some(msg) = MarketMessage::parse(msg) else { continue };
+        shared.stats.count(&msg);
+
+        match msg {
+            MarketMessage::PriceChange(pc) => {
+                stale.extend(state.books.apply_price_change(&pc));
+                let mut touched: Vec<&str> = pc.price_changes.iter().map(|c| c.asset_id.as_str()).collect();
+                touched.sort_unstable();
+                touched.dedup();
+                for id in touched {
+                    publish_book(shared, &state.books, id);
+                }
+                shared.publish(price_change_update(&pc, shared, &state.books));
+            }
+            MarketMessage::Book(book) => {
+                stale.extend(state.books.apply_snapshot(&book));
+                publish_book(shared, &state.books, &book.asset_id);
+            }
+            MarketMessage::TickSizeChange(tsc) => {
+                if let Some(tick) = parse_f64(&tsc.new_tick_size) {
+                    state.books.set_tick_size(&tsc.asset_id, tick);
+                    shared.publish(MarketUpdate::TickSize { asset_id: tsc.asset_id, tick_size: tick });
+                }
+            }
+            MarketMessage::LastTradePrice(ltp) => {
+                // Book snapshot that follows a trade carries the level changes
+                if let Some(trade) = Trade::from_msg(&ltp) {
+                    state.tapes.record(&ltp.asset_id, trade.clone());
+                    shared.publish(MarketUpdate::Trade { asset_id: ltp.asset_id, trade });
+                }
+            }
+            MarketMessage::MarketResolved(mr) => {
+                shared.publish(MarketUpdate::MarketResolved { market: mr.market, winning_asset_id: mr.winning_asset_id });
+            }
+            MarketMessage::BestBidAsk(_)
+            | MarketMessage::NewMarket(_)
+            | MarketMessage::Unknown(_) => {} // counted above
+        }
+    }
+    stale
+}
+
+fn publish_book(shared: &Shared, books: &Books, asset_id: &str) {
+    if let Some(book) = books.get(asset_id) {
+        shared.publish(MarketUpdate::Book { asset_id: asset_id.to_string(), book: book.clone() });
     }
 }
 
+// Builds the PriceChange update from a message that has already been applied
+fn price_change_update(msg: &PriceChangeMsg, shared: &Shared, books: &Books) -> MarketUpdate {
+    let names = shared.subscribed.read().unwrap();
+    let changes = msg.price_changes
+        .iter()
+        .map(|c| {
+            let book = books.get(&c.asset_id);
+            LevelChange {
+                asset_id: c.asset_id.clone(),
+                outcome: names.get(&c.asset_id).cloned().unwrap_or_default(),
+                side: Side::parse(&c.side),
+                price: parse_f64(&c.price).unwrap_or(0.0),
+                size: parse_f64(&c.size).unwrap_or(0.0),
+                best_bid: book.and_then(OrderBook::best_bid).map(|(p, _)| p),
+                best_ask: book.and_then(OrderBook::best_ask).map(|(p, _)| p),
+            }
+        })
+        .collect();
+    MarketUpdate::PriceChange { market: msg.market.clone(), timestamp: parse_ts(&msg.timestamp), changes }
+}
+
+// ================================================================================
+// REPLAY
+// Feeds a recording (see recording.rs) back through handle_message and
+// apply_resync in the order it was received, with one MarketState per recorded
+// shard, publishing on the same update channel a live stream would. Nothing
+// touches the network: the REST resyncs that followed a stale book are in the
+// recording already.
+//
+// Player applies one record at a time and is fully synchronous — a caller
+// that drains its receivers with try_recv() after every feed() (the backtest
+// does) knows exactly which record each update came from. Replay runs a
+// Player as a task, paced by the recorded receive times.
+// ================================================================================
+#[derive(Debug, Clone, Default)]
+pub struct ReplayStats {
+    pub records: u64,
+    pub messages: u64,
+    pub resyncs: u64,
+    pub first_ms: i64, // receive time of the first record
+    pub last_ms: i64,
+}
+
+impl ReplayStats {
+    // Wall-clock span the recording covers
+    pub fn recorded_secs(&self) -> f64 {
+        (self.last_ms - self.first_ms).max(0) as f64 / 1000.0
+    }
+}
+
+pub struct Player {
+    shared: Arc<Shared>,
+    states: HashMap<usize, MarketState>,
+    stats: ReplayStats,
+}
+
+impl Default for Player {
+    fn default() -> Player {
+        Player::new()
+    }
+}
+
+impl Player {
+    pub fn new() -> Player {
+        Player { shared: Shared::new(None), states: HashMap::new(), stats: ReplayStats::default() }
+    }
+
+    // Same handle type as a live stream; subscribe/unsubscribe on it are
+    // ignored, the recording decides what is subscribed
+    pub fn handle(&self) -> WsHandle {
+        WsHandle { cmd_tx: mpsc::unbounded_channel().0, shared: self.shared.clone() }
+    }
+
+    pub fn stats(&self) -> &ReplayStats {
+        &self.stats
+    }
+
+    // Applies one record, publishing whatever updates it produces
+    pub fn feed(&mut self, record: Recorded) {
+        if self.stats.records == 0 { self.stats.first_ms = record.t; }
+        self.stats.records += 1;
+        self.stats.last_ms = record.t;
+
+        match record.event {
+            RecordedEvent::Msg { shard, text } => {
+                self.stats.messages += 1;
+                handle_message(&text, &self.shared, self.states.entry(shard).or_default());
+            }
+            RecordedEvent::Resync { shard, book } => {
+                self.stats.resyncs += 1;
+                apply_resync(book, &self.shared, self.states.entry(shard).or_default());
+            }
+            RecordedEvent::Subscribe { tokens } => {
+                self.shared.subscribed.write().unwrap().extend(tokens);
+            }
+            RecordedEvent::Unsubscribe { ids } => {
+                let mut known = self.shared.subscribed.write().unwrap();
+                for id in &ids {
+                    known.remove(id);
+                    for state in self.states.values_mut() { state.remove(id); }
+                }
+            }
+            RecordedEvent::Meta { .. } => {}
+        }
+    }
+}
+
+// Before each record the replay waits until every update receiver has taken
+// everything published so far, so subscribers never lag and a run over the
+// same file always sees the same updates in the same order. A receiver that
+// is created and then never read therefore stalls the replay.
+pub struct Replay {
+    records: Box<dyn Iterator<Item = Recorded> + Send>,
+    speed: f64,
+    player: Player,
+}
+
+impl Replay {
+    // speed: 1.0 = as recorded, 10.0 = ten times faster, 0 = as fast as the
+    // subscribers keep up
+    pub fn open(path: &str, speed: f64) -> std::io::Result<Replay> {
+        let records = Box::new(read_recording(path)?);
+        Ok(Replay { records, speed: speed.max(0.0), player: Player::new() })
+    }
+
+    // Take receivers from this before start()
+    pub fn handle(&self) -> WsHandle {
+        self.player.handle()
+    }
+
+    // Runs the replay on the current runtime; resolves once the file is done
+    pub fn start(self) -> tokio::task::JoinHandle<ReplayStats> {
+        let Replay { records, speed, mut player } = self;
+
+        // The file is read on its own thread so decompression never blocks the runtime
+        let (tx, mut rx) = mpsc::channel(1024);
+        std::thread::spawn(move || {
+            for record in records {
+                if tx.blocking_send(record).is_err() { return; }
+            }
+        });
+
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let mut first_ms = None;
+            while let Some(record) = rx.recv().await {
+                let first_ms = *first_ms.get_or_insert(record.t);
+                if speed > 0.0 {
+                    let offset = (record.t - first_ms).max(0) as f64 / 1000.0 / speed;
+                    tokio::time::sleep_until(started + Duration::from_secs_f64(offset)).await;
+                }
+                player.feed(record);
+                while !player.shared.updates.is_empty() {
+                    tokio::task::yield_now().await;
+                }
+            }
+            player.stats
+        })
+    }
+}
+
+// ================================================================================
+// TERMINAL PRINTER
+// One subscriber among many — prints each price_change as a paired line and
+// logs tick size changes as they happen.
+// ================================================================================
+pub fn spawn_printer(handle: &WsHandle) {
+    let mut rx = handle.updates();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(MarketUpdate::PriceChange { changes, .. }) => print_price_change(&changes),
+                Ok(MarketUpdate::TickSize { asset_id, tick_size }) => info!(asset_id = %asset_id, tick_size, "tick size changed"),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => warn!(skipped = n, "printer fell behind"),
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+}
+
 // ── price_change ──────────────────────────────────────────────────────────────
 // Fires when the CLOB matches or cancels an order (off-chain).
 // Each message contains two entries — one per token — since every event on a
 // binary market affects both sides simultaneously.
 //
-// We update ask_state on every message so the sum always reflects the
-// latest known ask for both tokens even when only one side changes.
-fn print_price_change(msg: &Value, names: &HashMap<String, String>, ask_state: &mut HashMap<String, f64>) {
-    let changes = match msg.get("price_changes").and_then(Value::as_array) {
-        Some(c) => c,
-        None    => return,
-    };
+// best_ask on each change comes from the local book after the whole message
+// was applied, so the sum always reflects the latest known ask for both tokens
+// even when only one side changes.
+fn print_price_change(changes: &[LevelChange]) {
+    // Need exactly two entries to display a paired line
+    let [a, b, ..] = changes else { return };
 
-    // Update state for every token in this message
-    for change in changes {
-        let id  = change.get("asset_id").and_then(Value::as_str).unwrap_or("");
-        let ask = change.get("best_ask").and_then(Value::as_str)
-            .and_then(|v| v.parse::<f64>().ok());
-        if let (id, Some(ask)) = (id, ask) {
-            ask_state.insert(id.to_string(), ask);
-        }
-    }
+    let name_a = if a.outcome.is_empty() { "Token A" } else { a.outcome.as_str() };
+    let name_b = if b.outcome.is_empty() { "Token B" } else { b.outcome.as_str() };
+    let side_a = side_label(a.side);
+    let side_b = side_label(b.side);
 
-    // Need exactly two entries to display a paired line
-    if changes.len() < 2 { return; }
-
-    let a = &changes[0];
-    let b = &changes[1];
-
-    let id_a   = a.get("asset_id").and_then(Value::as_str).unwrap_or("");
-    let id_b   = b.get("asset_id").and_then(Value::as_str).unwrap_or("");
-    let name_a = names.get(id_a).map(|s| s.as_str()).unwrap_or("Token A");
-    let name_b = names.get(id_b).map(|s| s.as_str()).unwrap_or("Token B");
-    let side_a = a.get("side").and_then(Value::as_str).unwrap_or("?");
-    let side_b = b.get("side").and_then(Value::as_str).unwrap_or("?");
-    let size_a = a.get("size").and_then(Value::as_str).unwrap_or("?");
-
-    // Use state map for the sum — guaranteed to use latest known ask for both tokens
-    let ask_a = ask_state.get(id_a).copied();
-    let ask_b = ask_state.get(id_b).copied();
+    let ask_a = a.best_ask;
+    let ask_b = b.best_ask;
     let sum   = ask_a.zip(ask_b).map(|(a, b)| a + b);
 
-    let size_label = if size_a == "0" { "CANCEL".to_string() } else { size_a.to_string() };
+    let size_label = if a.size == 0.0 { "CANCEL".to_string() } else { a.size.to_string() };
     let ask_a_str  = ask_a.map(|v| format!("{v:.2}")).unwrap_or("—".to_string());
     let ask_b_str  = ask_b.map(|v| format!("{v:.2}")).unwrap_or("—".to_string());
     let sum_str    = sum.map(|s| format!("{s:.2}")).unwrap_or("—".to_string());
-    let arb_flag   = sum.map(|s| if s < 0.98 { " ← ARB" } else { "" }).unwrap_or("");
 
+    // Arb flags come from arb.rs now — this line is display only
     println!(
-        "  {side_a:<4} {name_a:<20} ask={ask_a_str:<5}  |  {side_b:<4} {name_b:<20} ask={ask_b_str:<5}  |\nsum={sum_str}  size={size_label}{arb_flag}",
+        "  {side_a:<4} {name_a:<20} ask={ask_a_str:<5}  |  {side_b:<4} {name_b:<20} ask={ask_b_str:<5}  |\nsum={sum_str}  size={size_label}",
     );
 }
 
-// ── seed_state_from_book ──────────────────────────────────────────────────────
-// Silently populates ask_state from the initial book snapshot Polymarket sends
-// on subscribe. This ensures the very first price_change line shows real ask
-// values instead of blanks.
-//
-// Book asks are sorted highest→lowest so best ask (lowest) is the LAST entry.
-fn seed_state_from_book(msg: &Value, ask_state: &mut HashMap<String, f64>) {
-    let id = match msg.get("asset_id").and_then(Value::as_str) {
-        Some(id) => id,
-        None     => return,
-    };
+fn side_label(side: Option<Side>) -> &'static str {
+    match side {
+        Some(Side::Bid) => "BUY",
+        Some(Side::Ask) => "SELL",
+        None            => "?",
+    }
+}
 
-    let best_ask = msg
-        .get("asks")
-        .and_then(Value::as_array)
-        .and_then(|arr| arr.last())       // last entry = lowest ask = best ask
-        .and_then(|l| l.get("price"))
-        .and_then(Value::as_str)
-        .and_then(|v| v.parse::<f64>().ok());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if let Some(ask) = best_ask {
-        ask_state.insert(id.to_string(), ask);
+    fn backoff() -> Backoff {
+        Backoff { initial: Duration::from_millis(500), max: Duration::from_secs(60), attempt: 0 }
+    }
+
+    // Every delay falls in the upper half of its window
+    fn assert_in_window(delay: Duration, ceiling: Duration) {
+        assert!(delay >= ceiling / 2 && delay <= ceiling, "{:?} outside {:?}..={:?}", delay, ceiling / 2, ceiling);
+    }
+
+    #[test]
+    fn backoff_doubles_per_failure() {
+        let mut b = backoff();
+        for ceiling_ms in [500, 1000, 2000, 4000, 8000] {
+            assert_in_window(b.next_delay(), Duration::from_millis(ceiling_ms));
+        }
+        assert_eq!(b.attempt, 5);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let mut b = backoff();
+        for _ in 0..7 {
+            b.next_delay();
+        }
+        // 500ms × 2^7 = 64s is already past the 60s cap; far past 2^16 it must not overflow
+        for _ in 0..40 {
+            assert_in_window(b.next_delay(), Duration::from_secs(60));
+        }
+    }
+
+    #[test]
+    fn backoff_reset_starts_over() {
+        let mut b = backoff();
+        for _ in 0..6 {
+            b.next_delay();
+        }
+        b.reset();
+        assert_in_window(b.next_delay(), Duration::from_millis(500));
     }
 }
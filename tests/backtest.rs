@@ -0,0 +1,217 @@
+// tests/backtest.rs — strategy grids over recorded sessions (backtest.rs)
+//
+// Run with: cargo test --test backtest
+//
+// Each test writes a synthetic recording of one binary market into its own
+// temp dir: a 1s arb window at cost 0.95, then the market resolves for A.
+
+use latex_telegram_bot::backtest::{Backtest, BacktestReport, OpportunityResult, Strategy, StrategySummary};
+use latex_telegram_bot::fees::{FeeConfig, TakerSchedule};
+use latex_telegram_bot::fetch::Config;
+use latex_telegram_bot::recording::{read_recording, RecordedEvent, Recorder};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+const A: &str = "111";
+const B: &str = "222";
+
+fn near(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+fn book(asset: &str, asks: Value) -> Value {
+    json!({ "event_type": "book", "asset_id": asset, "market": "0xm", "bids": [{ "price": "0.30", "size": "50" }], "asks": asks })
+}
+
+fn price_change(asset: &str, price: &str, size: &str) -> String {
+    json!({
+        "event_type": "price_change",
+        "market": "0xm",
+        "price_changes": [{ "asset_id": asset, "side": "SELL", "price": price, "size": size }]
+    }).to_string()
+}
+
+// Opens at t0+500 (B's ask drops to 0.50), closes at t0+1500 (A's 0.45 ask
+// is taken), resolves for A at t0+2000
+fn write_session(dir: &str) -> String {
+    let rec = Recorder::create(dir).expect("create recording");
+    let path = rec.path().display().to_string();
+    let t0 = 1_700_000_000_000;
+    let event = json!({
+        "id": "1001",
+        "title": "A vs B",
+        "markets": [{
+            "question": "Who wins?",
+            "sportsMarketType": "moneyline",
+            "clobTokenIds": format!("[\"{}\", \"{}\"]", A, B),
+            "outcomes": "[\"A\", \"B\"]"
+        }]
+    });
+    rec.record_at(t0, RecordedEvent::Meta { data: event });
+    rec.record_at(t0, RecordedEvent::Subscribe { tokens: vec![(A.into(), "A".into()), (B.into(), "B".into())] });
+    let snapshot = json!([
+        book(A, json!([{ "price": "0.55", "size": "100" }, { "price": "0.45", "size": "100" }])),
+        book(B, json!([{ "price": "0.60", "size": "100" }])),
+    ]);
+    rec.record_at(t0 + 100, RecordedEvent::Msg { shard: 0, text: snapshot.to_string() });
+    rec.record_at(t0 + 500, RecordedEvent::Msg { shard: 0, text: price_change(B, "0.50", "100") });
+    rec.record_at(t0 + 1500, RecordedEvent::Msg { shard: 0, text: price_change(A, "0.45", "0") });
+    let resolved = json!({ "event_type": "market_resolved", "market": "0xm", "winning_asset_id": A });
+    rec.record_at(t0 + 2000, RecordedEvent::Msg { shard: 0, text: resolved.to_string() });
+    drop(rec);
+
+    // The writer thread flushes once every recorder clone is gone
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut records = 0;
+    while records < 6 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+        records = read_recording(&path).map(|r| r.count()).unwrap_or(0);
+    }
+    assert_eq!(records, 6, "recording flushed");
+    path
+}
+
+// Writes the session into a fresh dir; returns the dir and the recording path
+fn recorded_session(name: &str) -> (String, String) {
+    let dir = std::env::temp_dir().join(format!("test_backtest_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let dir = dir.to_string_lossy().to_string();
+    let path = write_session(&dir);
+    (dir, path)
+}
+
+fn strategy(name: &str, threshold: f64, latency_ms: u64, fees: FeeConfig) -> Strategy {
+    Strategy {
+        name: name.to_string(),
+        threshold,
+        min_profit: 0.0,
+        fee_model: name.to_string(),
+        fees,
+        latency_ms,
+        max_notional: 1000.0,
+    }
+}
+
+fn run(files: &[&str]) -> BacktestReport {
+    let fees = FeeConfig { taker_fee_bps: 200.0, taker_schedule: TakerSchedule::Flat, ..FeeConfig::default() };
+    let mut backtest = Backtest::new(vec![
+        strategy("base", 0.98, 0, FeeConfig::default()),
+        strategy("strict", 0.94, 0, FeeConfig::default()),
+        strategy("fees", 0.98, 0, fees),
+        strategy("slow", 0.98, 1200, FeeConfig::default()),
+    ]);
+    for file in files {
+        backtest.run_file(file).expect("run recording");
+    }
+    backtest.finish()
+}
+
+fn summary(report: &BacktestReport, name: &str) -> StrategySummary {
+    report.summaries.iter().find(|s| s.strategy == name).cloned().expect("summary for every strategy")
+}
+
+fn result(report: &BacktestReport, name: &str) -> Option<OpportunityResult> {
+    report.results.iter().find(|r| r.strategy == name).cloned()
+}
+
+fn grid_config() -> Config {
+    serde_json::from_value(json!({
+        "bot_token": "", "chat_id": "", "hours_window": 24, "pool_max_idle_per_host": 1,
+        "request_timeout_secs": 1, "tag_ids": [],
+        "backtest_thresholds": [0.97, 0.98],
+        "backtest_latencies_ms": [0, 250, 1000],
+        "backtest_fee_models": { "none": {}, "flat": { "taker_fee_bps": 100 } }
+    })).expect("config")
+}
+
+#[test]
+fn grid_covers_every_threshold_fee_model_and_latency() {
+    let config = grid_config();
+    let grid = Strategy::grid(&config, &config.backtest_thresholds, &config.backtest_latencies_ms);
+    assert_eq!(grid.len(), 2 * 2 * 3);
+}
+
+#[test]
+fn empty_grid_lists_fall_back_to_live_settings() {
+    let config = grid_config();
+    let grid = Strategy::grid(&config, &[], &[]);
+    assert_eq!(grid.len(), 2, "one per fee model");
+    assert_eq!(grid[0].threshold, config.arb_threshold);
+}
+
+#[test]
+fn window_without_latency_is_traded_in_full() {
+    let (dir, path) = recorded_session("base");
+    let report = run(&[&path]);
+    let base = summary(&report, "base");
+    assert_eq!((base.windows, base.filled), (1, 1));
+
+    // Timing in recorded time; locked profit 100 × (1 − 0.95), realized at resolution
+    let r = result(&report, "base").expect("base result");
+    assert_eq!((r.opened_ms, r.closed_ms), (1_700_000_000_500, Some(1_700_000_001_500)));
+    assert!(near(r.window_secs, 1.0), "{}", r.window_secs);
+    assert!(r.locked_profit.is_some_and(|p| near(p, 5.0)), "{:?}", r.locked_profit);
+    assert!(near(base.realized_pnl, 5.0) && near(base.open_cost, 0.0), "{:?}", base);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn threshold_above_the_edge_sees_nothing() {
+    let (dir, path) = recorded_session("strict");
+    let report = run(&[&path]);
+    assert_eq!(summary(&report, "strict").windows, 0);
+    assert!(result(&report, "strict").is_none());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn taker_fees_come_off_the_locked_profit() {
+    // 200 bps flat on 45 + 50 USDC of shares
+    let (dir, path) = recorded_session("fees");
+    let report = run(&[&path]);
+    let r = result(&report, "fees").expect("fees result");
+    assert!(r.locked_profit.is_some_and(|p| near(p, 5.0 - 1.9)), "{:?}", r.locked_profit);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn latency_past_the_window_leaves_a_losing_leg() {
+    let (dir, path) = recorded_session("slow");
+    let report = run(&[&path]);
+    let r = result(&report, "slow").expect("slow result");
+    assert_eq!((r.status, r.legs_killed), ("partial", 1));
+    let slow = summary(&report, "slow");
+    assert!(near(slow.realized_pnl, -50.0) && near(slow.locked_profit, 0.0), "{:?}", slow);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn reruns_produce_identical_csvs() {
+    let (dir, path) = recorded_session("determinism");
+    let first = run(&[&path]);
+    let second = run(&[&path]);
+    assert_eq!(second.results_csv().expect("results csv"), first.results_csv().expect("results csv"));
+    assert_eq!(second.summary_csv().expect("summary csv"), first.summary_csv().expect("summary csv"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn results_accumulate_across_files() {
+    let (dir, path) = recorded_session("accumulate");
+    let once = run(&[&path]);
+    let twice = run(&[&path, &path]);
+    let base = summary(&twice, "base");
+    assert_eq!(base.windows, 2);
+    assert!(near(base.realized_pnl, 10.0), "{}", base.realized_pnl);
+    assert_eq!(twice.records, 2 * once.records);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn results_csv_has_a_row_per_window() {
+    // base, fees and slow each trade the one window
+    let (dir, path) = recorded_session("csv");
+    let csv = String::from_utf8(run(&[&path]).results_csv().expect("results csv")).expect("utf-8 csv");
+    assert_eq!(csv.lines().count(), 1 + 3);
+    let _ = std::fs::remove_dir_all(&dir);
+}
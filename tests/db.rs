@@ -0,0 +1,202 @@
+// tests/db.rs — the SQLite history store (db.rs)
+//
+// Run with: cargo test --test db
+//
+// Every test opens its own database in the temp dir.
+
+use latex_telegram_bot::arb::{ArbLeg, ArbOpportunity, ArbRecord, GroupKind};
+use latex_telegram_bot::db::{BookRow, DbWrite, Retention, Store, WsRow};
+use rusqlite::params;
+use serde_json::{json, Value};
+
+const NOW: i64 = 1_700_000_000_000;
+
+fn temp_db(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("test_db_{}_{}.db", name, std::process::id()));
+    let path = path.to_string_lossy().to_string();
+    remove_db(&path);
+    path
+}
+
+fn remove_db(path: &str) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+    }
+}
+
+fn count(store: &Store, table: &str) -> i64 {
+    store.connection()
+        .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get(0))
+        .expect("count rows")
+}
+
+fn event() -> Value {
+    json!({
+        "id": "1001",
+        "title": "A vs B",
+        "slug": "a-vs-b",
+        "endDate": "2026-01-01T00:00:00Z",
+        "seriesSlug": "nba",
+        "markets": [{
+            "conditionId": "0xabc",
+            "question": "Will A win?",
+            "sportsMarketType": "moneyline",
+            "clobTokenIds": "[\"111\", \"222\"]",
+            "outcomes": "[\"A\", \"B\"]"
+        }]
+    })
+}
+
+// REST /book body with unsorted levels
+fn fetch_run(at_ms: i64) -> DbWrite {
+    let book = json!({
+        "bids": [{ "price": "0.40", "size": "10" }, { "price": "0.44", "size": "5" }],
+        "asks": [{ "price": "0.50", "size": "7" }, { "price": "0.46", "size": "3" }]
+    });
+    DbWrite::FetchRun { started_ms: at_ms, events: vec![event()], books: vec![BookRow::from_rest("111", &book, at_ms)] }
+}
+
+fn opportunity(at_ms: i64) -> ArbOpportunity {
+    ArbOpportunity {
+        group_id: "1001:Will A win?".to_string(),
+        title: "A vs B".to_string(),
+        kind: GroupKind::Binary,
+        legs: vec![ArbLeg {
+            asset_id: "111".to_string(),
+            outcome: "Yes".to_string(),
+            market: "0xm".to_string(),
+            price: 0.45,
+            size: 100.0,
+            avg_price: 0.45,
+            worst_price: 0.45,
+        }],
+        cost: 0.95,
+        payout: 1.0,
+        edge: 0.05,
+        fill_size: 100.0,
+        notional: 95.0,
+        fees: 0.0,
+        gas: 0.0,
+        expected_profit: 5.0,
+        detected_at: chrono::DateTime::from_timestamp_millis(at_ms).expect("timestamp"),
+    }
+}
+
+fn closed(opened_at_ms: i64, closed_at_ms: i64) -> ArbRecord {
+    ArbRecord {
+        group_id: "1001:Will A win?".to_string(),
+        title: "A vs B".to_string(),
+        league: "nba".to_string(),
+        kind: GroupKind::Binary,
+        opened_at_ms,
+        closed_at_ms,
+        peak_edge: 0.07,
+        peak_size: 100.0,
+        peak_profit: 7.0,
+        updates: 3,
+    }
+}
+
+fn ws(at_ms: i64, asset_id: &str) -> DbWrite {
+    DbWrite::Ws(WsRow { received_ms: at_ms, kind: "trade", asset_id: asset_id.to_string(), market: String::new(), payload: json!({}) })
+}
+
+#[test]
+fn migrations_run_once() {
+    let path = temp_db("migrations");
+    let version = Store::open(&path).expect("open store").schema_version().expect("version");
+    assert!(version >= 1, "{}", version);
+
+    let reopened = Store::open(&path).expect("reopen store");
+    assert_eq!(reopened.schema_version().expect("version"), version);
+    drop(reopened);
+    remove_db(&path);
+}
+
+#[test]
+fn repeat_fetches_update_the_event() {
+    let path = temp_db("fetch_runs");
+    let mut store = Store::open(&path).expect("open store");
+    store.write(&[fetch_run(NOW - 1000)]).expect("first run");
+    store.write(&[fetch_run(NOW)]).expect("second run");
+
+    assert_eq!(count(&store, "fetch_runs"), 2);
+    assert_eq!((count(&store, "events"), count(&store, "markets"), count(&store, "tokens")), (1, 1, 2));
+    let seen: (i64, i64, String) = store.connection()
+        .query_row("SELECT first_seen_ms, last_seen_ms, league FROM events WHERE id = '1001'", [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+        .expect("event row");
+    assert_eq!(seen, (NOW - 1000, NOW, "nba".to_string()));
+    drop(store);
+    remove_db(&path);
+}
+
+#[test]
+fn rest_books_store_top_of_book() {
+    let path = temp_db("books");
+    let mut store = Store::open(&path).expect("open store");
+    store.write(&[fetch_run(NOW)]).expect("run");
+
+    let top: (f64, f64) = store.connection()
+        .query_row("SELECT best_bid, best_ask FROM book_snapshots", [], |r| Ok((r.get(0)?, r.get(1)?)))
+        .expect("snapshot row");
+    assert_eq!(top, (0.44, 0.46));
+    drop(store);
+    remove_db(&path);
+}
+
+#[test]
+fn one_arb_row_per_window_completed_on_close() {
+    let path = temp_db("arbs");
+    let mut store = Store::open(&path).expect("open store");
+    store.write(&[
+        DbWrite::ArbOpen(opportunity(NOW - 5000)),
+        DbWrite::ArbOpen(opportunity(NOW - 4000)),
+        DbWrite::ArbClosed(closed(NOW - 5000, NOW)),
+        DbWrite::ArbOpen(opportunity(NOW + 1000)),
+    ]).expect("arb writes");
+
+    let rows: Vec<(i64, Option<i64>, Option<f64>)> = store.connection()
+        .prepare("SELECT detected_ms, closed_ms, peak_edge FROM arb_opportunities ORDER BY id")
+        .and_then(|mut st| st.query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?.collect())
+        .expect("arb rows");
+    assert_eq!(rows, [(NOW - 5000, Some(NOW), Some(0.07)), (NOW + 1000, None, None)]);
+    drop(store);
+    remove_db(&path);
+}
+
+#[test]
+fn retention_prunes_only_old_ws_rows() {
+    let path = temp_db("retention");
+    let mut store = Store::open(&path).expect("open store");
+    let now = chrono::Utc::now().timestamp_millis();
+    store.write(&[fetch_run(now - 30 * 86_400_000), DbWrite::ArbOpen(opportunity(now - 30 * 86_400_000))]).expect("old rows");
+    store.write(&[ws(now - 3 * 3_600_000, "111"), ws(now, "111")]).expect("ws writes");
+
+    let removed = store.prune(&Retention { ws_hours: 2, books_days: 0, arbs_days: 0 }).expect("prune");
+    assert_eq!(removed, 1);
+    let kept: i64 = store.connection()
+        .query_row("SELECT COUNT(*) FROM ws_updates WHERE received_ms = ?1", params![now], |r| r.get(0))
+        .expect("count recent");
+    assert_eq!((count(&store, "ws_updates"), kept), (1, 1));
+
+    // 0 days keeps books and arbs forever
+    assert_eq!((count(&store, "book_snapshots"), count(&store, "arb_opportunities")), (1, 1));
+    drop(store);
+    remove_db(&path);
+}
+
+#[test]
+fn failed_write_keeps_the_rest_of_the_batch() {
+    let path = temp_db("failures");
+    let mut store = Store::open(&path).expect("open store");
+    store.connection().execute_batch(
+        "CREATE TRIGGER reject_bad BEFORE INSERT ON ws_updates WHEN NEW.asset_id = 'bad'
+         BEGIN SELECT RAISE(ABORT, 'bad row'); END;",
+    ).expect("trigger");
+
+    let failed = store.write(&[ws(NOW, "111"), ws(NOW, "bad"), ws(NOW, "111")]).expect("batch with a bad row");
+    assert_eq!(failed, 1);
+    assert_eq!(count(&store, "ws_updates"), 2);
+    drop(store);
+    remove_db(&path);
+}
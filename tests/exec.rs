@@ -0,0 +1,531 @@
+// tests/exec.rs — order signing, the CLOB client, execution, risk and portfolio
+//
+// Run with: cargo test --test exec
+//
+// Signing is checked against known-answer vectors, including the EIP-712
+// "Ether Mail" example from the EIP itself. Everything that talks to the CLOB
+// runs against mock_clob.rs on a local port, one mock per test.
+
+use latex_telegram_bot::exec::{
+    hash_struct, keccak256, parse_address, recover_address, signature_hex, typed_data_digest, word_address,
+    word_string, ClobClient, Eip712Domain, ExecError, OrderArgs, OrderSide, OrderType, Wallet,
+};
+use latex_telegram_bot::arb::{ArbEvent, ArbLeg, ArbOpportunity, GroupKind};
+use latex_telegram_bot::executor::{self, ExecOutcome, ExecSettings, Executor, PartialPolicy};
+use latex_telegram_bot::book::{price_to_units, OrderBook};
+use latex_telegram_bot::mock_clob::MockClob;
+use latex_telegram_bot::portfolio::Portfolio;
+use latex_telegram_bot::risk::{write_kill_file, KillSwitch, RiskLimits, RiskManager};
+use latex_telegram_bot::ws::MarketUpdate;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+const TOKEN: &str = "71321045679252212594626385532706912750332728571942532289631379312455583992563";
+
+fn near(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+// ================================================================================
+// SIGNING
+// ================================================================================
+fn ether_mail_domain() -> Eip712Domain {
+    Eip712Domain {
+        name: "Ether Mail".to_string(),
+        version: "1".to_string(),
+        chain_id: 1,
+        verifying_contract: Some(parse_address("0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC").expect("contract address")),
+    }
+}
+
+// EIP-712 spec example: https://eips.ethereum.org/EIPS/eip-712
+fn ether_mail_digest() -> [u8; 32] {
+    const PERSON: &str = "Person(string name,address wallet)";
+    const MAIL: &str = "Mail(Person from,Person to,string contents)Person(string name,address wallet)";
+    let person = |name: &str, wallet: &str| {
+        hash_struct(PERSON, &[word_string(name), word_address(&parse_address(wallet).expect("wallet address"))])
+    };
+    let mail = hash_struct(MAIL, &[
+        person("Cow", "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"),
+        person("Bob", "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"),
+        word_string("Hello, Bob!"),
+    ]);
+    typed_data_digest(&ether_mail_domain(), &mail)
+}
+
+fn cow() -> Wallet {
+    Wallet::from_hex(&hex::encode(keccak256(b"cow"))).expect("cow key")
+}
+
+#[test]
+fn keccak256_of_empty_input() {
+    assert_eq!(hex::encode(keccak256(b"")), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+}
+
+#[test]
+fn address_derived_from_private_key() {
+    let one = Wallet::from_hex("0x0000000000000000000000000000000000000000000000000000000000000001").expect("key 1");
+    assert_eq!(one.address_hex(), "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+    assert_eq!(cow().address_hex(), "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826");
+}
+
+#[test]
+fn eip712_domain_separator_and_digest() {
+    assert_eq!(hex::encode(ether_mail_domain().separator()), "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f");
+    assert_eq!(hex::encode(ether_mail_digest()), "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2");
+}
+
+#[test]
+fn eip712_signature_matches_and_recovers() {
+    let digest = ether_mail_digest();
+    let sig = cow().sign_digest(&digest).expect("sign");
+    assert_eq!(
+        signature_hex(&sig),
+        "0x4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+         07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b91562\
+         1c"
+    );
+    assert_eq!(recover_address(&digest, &sig), Some(cow().address()));
+}
+
+// ================================================================================
+// CLOB CLIENT
+// ================================================================================
+async fn mock() -> MockClob {
+    MockClob::start(137).await.expect("start mock CLOB")
+}
+
+fn client(mock: &MockClob, seed: &str) -> ClobClient {
+    let wallet = Wallet::from_hex(&hex::encode(keccak256(seed.as_bytes()))).expect("wallet");
+    ClobClient::new(reqwest::Client::new(), &mock.url(), wallet, 137, 0, None)
+}
+
+// Client with API creds already derived
+async fn authed(mock: &MockClob, seed: &str) -> ClobClient {
+    let mut clob = client(mock, seed);
+    clob.derive_api_key().await.expect("derive API key");
+    clob
+}
+
+fn args(side: OrderSide, price: f64, size: f64) -> OrderArgs {
+    OrderArgs { token_id: TOKEN.to_string(), side, price, size, fee_rate_bps: 0, neg_risk: false }
+}
+
+#[tokio::test]
+async fn orders_refused_before_api_creds() {
+    let mock = mock().await;
+    let clob = client(&mock, "test_exec wallet");
+    let placed = clob.place(&args(OrderSide::Buy, 0.5, 10.0), OrderType::Fok).await;
+    assert!(matches!(placed, Err(ExecError::Auth(_))), "{:?}", placed);
+    assert!(mock.orders().is_empty());
+}
+
+#[tokio::test]
+async fn api_key_derived_with_an_l1_signature() {
+    let mock = mock().await;
+    let mut clob = client(&mock, "test_exec wallet");
+    let creds = clob.derive_api_key().await.expect("derive API key");
+    assert_eq!(mock.creds().map(|m| m.api_key), Some(creds.api_key));
+}
+
+#[tokio::test]
+async fn buy_order_amounts_and_signer() {
+    let mock = mock().await;
+    let clob = authed(&mock, "test_exec wallet").await;
+    let order = clob.build_order(&args(OrderSide::Buy, 0.47, 12.5)).expect("build order");
+    assert_eq!((order.maker_amount, order.taker_amount), (5_875_000, 12_500_000));
+    assert_eq!(order.signer, clob.address());
+}
+
+#[tokio::test]
+async fn fok_fills_across_levels_or_is_killed() {
+    let mock = mock().await;
+    let clob = authed(&mock, "test_exec wallet").await;
+    mock.set_asks(TOKEN, &[(0.48, 10.0), (0.50, 20.0), (0.55, 100.0)]);
+
+    // 30 available at ≤ 0.50
+    let fok = clob.place(&args(OrderSide::Buy, 0.50, 25.0), OrderType::Fok).await.expect("FOK");
+    assert_eq!(fok.status, "matched");
+    assert!(near(fok.filled_shares(OrderSide::Buy), 25.0), "{:?}", fok);
+
+    // 5 left at 0.50
+    let killed = clob.place(&args(OrderSide::Buy, 0.50, 10.0), OrderType::Fok).await;
+    assert!(matches!(killed, Err(ExecError::Killed(_))), "{:?}", killed);
+}
+
+#[tokio::test]
+async fn ioc_takes_what_is_there() {
+    let mock = mock().await;
+    let clob = authed(&mock, "test_exec wallet").await;
+    mock.set_asks(TOKEN, &[(0.50, 5.0), (0.55, 100.0)]);
+    let ioc = clob.place(&args(OrderSide::Buy, 0.50, 10.0), OrderType::Ioc).await.expect("IOC");
+    assert!(near(ioc.filled_shares(OrderSide::Buy), 5.0), "{:?}", ioc);
+}
+
+#[tokio::test]
+async fn gtc_rests_when_not_marketable() {
+    let mock = mock().await;
+    let clob = authed(&mock, "test_exec wallet").await;
+    mock.set_asks(TOKEN, &[(0.50, 5.0)]);
+    let gtc = clob.place(&args(OrderSide::Buy, 0.40, 10.0), OrderType::Gtc).await.expect("GTC");
+    assert_eq!(gtc.status, "live");
+    assert_eq!(gtc.filled_shares(OrderSide::Buy), 0.0);
+}
+
+#[tokio::test]
+async fn sell_fok_fills_into_bids() {
+    let mock = mock().await;
+    let clob = authed(&mock, "test_exec wallet").await;
+    mock.set_bids(TOKEN, &[(0.52, 50.0)]);
+    let sell = clob.place(&args(OrderSide::Sell, 0.50, 20.0), OrderType::Fok).await.expect("SELL FOK");
+    assert!(near(sell.filled_shares(OrderSide::Sell), 20.0), "{:?}", sell);
+}
+
+#[tokio::test]
+async fn neg_risk_orders_accepted() {
+    let mock = mock().await;
+    let clob = authed(&mock, "test_exec wallet").await;
+    mock.set_bids(TOKEN, &[(0.52, 50.0)]);
+    let neg = OrderArgs { neg_risk: true, ..args(OrderSide::Sell, 0.50, 1.0) };
+    clob.place(&neg, OrderType::Fok).await.expect("neg-risk order");
+    assert_eq!(mock.orders().len(), 1);
+}
+
+#[tokio::test]
+async fn tampered_signature_rejected() {
+    let mock = mock().await;
+    let clob = authed(&mock, "test_exec wallet").await;
+    let mut forged = clob.build_order(&args(OrderSide::Sell, 0.50, 1.0)).expect("build order");
+    forged.taker_amount += 1;
+    let posted = clob.post_order(&forged, OrderType::Fok).await;
+    assert!(matches!(posted, Err(ExecError::Rejected(ref m)) if m.contains("signature")), "{:?}", posted);
+    assert!(mock.orders().is_empty());
+}
+
+// ================================================================================
+// EXECUTION
+// ================================================================================
+// Two-leg binary arb: YES @ 0.45 + NO @ 0.50
+fn two_leg_opp() -> ArbOpportunity {
+    let leg = |asset: &str, outcome: &str, price: f64| ArbLeg {
+        asset_id: asset.to_string(),
+        outcome: outcome.to_string(),
+        market: "0xm".to_string(),
+        price,
+        size: 100.0,
+        avg_price: price,
+        worst_price: price,
+    };
+    ArbOpportunity {
+        group_id: "test:binary".to_string(),
+        title: "Test market".to_string(),
+        kind: GroupKind::Binary,
+        legs: vec![leg("111", "Yes", 0.45), leg("222", "No", 0.50)],
+        cost: 0.95,
+        payout: 1.0,
+        edge: 0.05,
+        fill_size: 100.0,
+        notional: 95.0,
+        fees: 0.0,
+        gas: 0.0,
+        expected_profit: 5.0,
+        detected_at: chrono::Utc::now(),
+    }
+}
+
+fn settings(policy: PartialPolicy) -> ExecSettings {
+    ExecSettings { policy, unwind_slippage: 0.05, hedge_slippage: 0.02, max_signal_age: Duration::from_secs(1) }
+}
+
+fn no_kill() -> Arc<KillSwitch> {
+    Arc::new(KillSwitch::new(""))
+}
+
+// Mock with both legs of two_leg_opp on offer, and an executor on it
+async fn executor_on_mock(seed: &str, policy: PartialPolicy, kill: Arc<KillSwitch>) -> (MockClob, Arc<ClobClient>, Executor) {
+    let mock = mock().await;
+    let clob = Arc::new(authed(&mock, seed).await);
+    mock.set_asks("111", &[(0.45, 100.0)]);
+    mock.set_asks("222", &[(0.50, 100.0)]);
+    let executor = Executor::new(clob.clone(), settings(policy), kill, None);
+    (mock, clob, executor)
+}
+
+#[tokio::test]
+async fn both_legs_fill_complete_sets() {
+    let (_mock, _, executor) = executor_on_mock("test_exec wallet", PartialPolicy::Unwind, no_kill()).await;
+    let r = executor.execute(&two_leg_opp(), 10.0).await;
+    assert_eq!(r.outcome, ExecOutcome::Complete);
+    assert!(near(r.sets, 10.0), "{}", r.sets);
+}
+
+#[tokio::test]
+async fn missing_leg_unwound_into_the_bid() {
+    let (mock, _, executor) = executor_on_mock("test_exec wallet", PartialPolicy::Unwind, no_kill()).await;
+    mock.set_asks("222", &[]);
+    mock.set_bids("111", &[(0.44, 100.0)]);
+    let r = executor.execute(&two_leg_opp(), 10.0).await;
+    assert_eq!((r.outcome, r.sets), (ExecOutcome::Unwound, 0.0));
+    assert!(r.legs[0].unwound > 9.99, "{:?}", r.legs);
+    // Bought at 0.45, sold at 0.44
+    assert!(near(r.net_cost(), 0.1), "{}", r.net_cost());
+}
+
+#[tokio::test]
+async fn hedge_buys_the_missing_leg_within_slippage() {
+    let (mock, _, executor) = executor_on_mock("test_exec wallet", PartialPolicy::Hedge, no_kill()).await;
+    mock.set_asks("222", &[(0.51, 100.0)]);
+    let r = executor.execute(&two_leg_opp(), 10.0).await;
+    assert_eq!(r.outcome, ExecOutcome::Complete, "{:?}", r.legs);
+    assert!(near(r.sets, 10.0), "{}", r.sets);
+}
+
+#[tokio::test]
+async fn hold_keeps_the_exposure() {
+    let (mock, _, executor) = executor_on_mock("test_exec wallet", PartialPolicy::Hold, no_kill()).await;
+    mock.set_asks("222", &[]);
+    let r = executor.execute(&two_leg_opp(), 10.0).await;
+    assert_eq!(r.outcome, ExecOutcome::Exposed);
+    assert!(r.legs[0].held() > 9.99, "{:?}", r.legs);
+}
+
+#[tokio::test]
+async fn nothing_filled_leaves_no_position() {
+    let (mock, _, executor) = executor_on_mock("test_exec wallet", PartialPolicy::Unwind, no_kill()).await;
+    mock.set_asks("111", &[]);
+    mock.set_asks("222", &[]);
+    let r = executor.execute(&two_leg_opp(), 10.0).await;
+    assert_eq!((r.outcome, r.net_cost()), (ExecOutcome::NoFill, 0.0));
+}
+
+// ================================================================================
+// RISK
+// ================================================================================
+fn limits() -> RiskLimits {
+    RiskLimits {
+        max_trade_notional: 50.0,
+        max_market_notional: 30.0,
+        max_event_notional: 1000.0,
+        max_daily_notional: 1000.0,
+        max_open_positions: 1,
+        max_consecutive_failures: 2,
+    }
+}
+
+#[test]
+fn sized_to_the_tightest_limit() {
+    // $0.95 per set: the $30 market limit binds before the $50 trade limit
+    let risk = RiskManager::new(limits(), no_kill());
+    assert_eq!(risk.allowed_sets(&two_leg_opp()), Ok(31.0));
+}
+
+#[tokio::test]
+async fn held_positions_count_against_the_limits_until_resolved() {
+    let kill = no_kill();
+    let (_mock, _, executor) = executor_on_mock("test_exec risk wallet", PartialPolicy::Unwind, kill.clone()).await;
+    let risk = RiskManager::new(limits(), kill);
+    let opp = two_leg_opp();
+    let other = ArbOpportunity { group_id: "other:binary".to_string(), ..two_leg_opp() };
+    risk.record(&opp, &executor.execute(&opp, 10.0).await);
+
+    assert_eq!(risk.allowed_sets(&opp), Ok(21.0), "$9.50 held in the market");
+    let blocked = risk.allowed_sets(&other);
+    assert!(blocked.as_ref().is_err_and(|e| e.contains("positions open")), "{:?}", blocked);
+
+    risk.on_update(&MarketUpdate::MarketResolved { market: "m".to_string(), winning_asset_id: "111".to_string() });
+    assert_eq!(risk.allowed_sets(&other), Ok(31.0));
+}
+
+#[tokio::test]
+async fn empty_books_are_not_failures() {
+    let kill = no_kill();
+    let (mock, _, executor) = executor_on_mock("test_exec risk wallet", PartialPolicy::Unwind, kill.clone()).await;
+    let risk = RiskManager::new(limits(), kill.clone());
+    let opp = two_leg_opp();
+    mock.set_asks("111", &[]);
+    mock.set_asks("222", &[]);
+    for _ in 0..2 {
+        risk.record(&opp, &executor.execute(&opp, 5.0).await);
+    }
+    assert!(!kill.is_engaged(), "{}", kill.reason());
+}
+
+#[tokio::test]
+async fn rejected_legs_trip_the_kill_switch() {
+    let kill = no_kill();
+    let (mock, _, executor) = executor_on_mock("test_exec risk wallet", PartialPolicy::Unwind, kill.clone()).await;
+    let risk = RiskManager::new(limits(), kill.clone());
+    let opp = two_leg_opp();
+    mock.reject_all(Some("not enough balance / allowance"));
+    for _ in 0..2 {
+        risk.record(&opp, &executor.execute(&opp, 5.0).await);
+    }
+    mock.reject_all(None);
+    assert!(kill.is_engaged());
+    tokio::time::timeout(Duration::from_millis(100), kill.tripped()).await.expect("tripped() wakes the risk task");
+    assert!(risk.allowed_sets(&opp).is_err());
+
+    // Nothing is sent once killed, even with the books back
+    let before = mock.orders().len();
+    let r = executor.execute(&opp, 5.0).await;
+    assert_eq!((mock.orders().len(), r.outcome), (before, ExecOutcome::NoFill));
+}
+
+#[test]
+fn kill_file_engages_the_switch() {
+    let path = std::env::temp_dir().join(format!("test_exec_kill_{}", std::process::id()));
+    let path = path.to_string_lossy().to_string();
+    let _ = std::fs::remove_file(&path);
+    let kill = KillSwitch::new(&path);
+    assert!(!kill.is_engaged(), "{}", kill.reason());
+
+    write_kill_file(&path, "maintenance").expect("write kill file");
+    assert!(kill.is_engaged());
+    assert_eq!(kill.reason(), "maintenance");
+    std::fs::remove_file(&path).expect("remove kill file");
+}
+
+#[tokio::test]
+async fn cancel_all_cancels_resting_orders() {
+    let mock = mock().await;
+    let clob = authed(&mock, "test_exec risk wallet").await;
+    mock.set_asks(TOKEN, &[(0.40, 5.0)]);
+    let resting = clob.place(&args(OrderSide::Buy, 0.40, 10.0), OrderType::Gtc).await.expect("GTC");
+    let cancelled = clob.cancel_all().await.expect("cancel all");
+    assert_eq!(cancelled, [resting.order_id.as_str()]);
+    let status = mock.orders().into_iter().find(|o| o.order_id == resting.order_id).map(|o| o.status);
+    assert_eq!(status.as_deref(), Some("canceled"));
+}
+
+// ================================================================================
+// PORTFOLIO
+// ================================================================================
+fn temp_portfolio(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("test_exec_portfolio_{}_{}.json", name, std::process::id()));
+    let path = path.to_string_lossy().to_string();
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+// $250 synced from the CLOB, then 10 complete sets bought for $9.50
+async fn portfolio_with_sets(name: &str) -> (MockClob, Executor, Portfolio, String) {
+    let (mock, clob, executor) = executor_on_mock("test_exec portfolio wallet", PartialPolicy::Unwind, no_kill()).await;
+    let path = temp_portfolio(name);
+    let mut p = Portfolio::load(&path);
+    mock.set_balance(250.0);
+    p.sync_usdc(clob.usdc_balance().await.expect("balance"));
+    p.record(&executor.execute(&two_leg_opp(), 10.0).await);
+    (mock, executor, p, path)
+}
+
+#[tokio::test]
+async fn fills_held_against_the_synced_balance() {
+    let (_mock, _, p, path) = portfolio_with_sets("fills").await;
+    assert_eq!(p.holdings.len(), 2);
+    assert!(near(p.usdc, 240.5), "{}", p.usdc);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn unwind_realizes_the_spread() {
+    let (mock, executor, mut p, path) = portfolio_with_sets("unwind").await;
+    // Second attempt: NO is gone, YES sold back at 0.44 after buying at 0.45
+    mock.set_asks("222", &[]);
+    mock.set_bids("111", &[(0.44, 100.0)]);
+    p.record(&executor.execute(&two_leg_opp(), 10.0).await);
+    assert!(near(p.realized_pnl, -0.1), "{}", p.realized_pnl);
+    assert_eq!(p.holdings.get("111").map(|h| (h.shares, h.avg_price)), Some((10.0, 0.45)));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn marked_to_the_book_mid() {
+    let (_mock, _, mut p, path) = portfolio_with_sets("mark").await;
+    let mut book = OrderBook::default();
+    book.bids.insert(price_to_units("0.50").expect("price"), 10.0);
+    book.asks.insert(price_to_units("0.54").expect("price"), 10.0);
+    let mut changed = false;
+    p.on_update(&MarketUpdate::Book { asset_id: "111".to_string(), book }, &mut changed);
+    assert!(changed);
+    // 10 YES bought at 0.45, mid 0.52
+    assert!(near(p.unrealized_pnl(), 0.7), "{}", p.unrealized_pnl());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn survives_a_restart() {
+    let (_mock, _, mut p, path) = portfolio_with_sets("restart").await;
+    p.save();
+    let loaded = Portfolio::load(&path);
+    assert_eq!(loaded.holdings.keys().collect::<Vec<_>>(), p.holdings.keys().collect::<Vec<_>>());
+    assert_eq!((loaded.usdc, loaded.realized_pnl), (p.usdc, p.realized_pnl));
+    std::fs::remove_file(&path).expect("remove portfolio");
+}
+
+#[test]
+fn corrupt_file_moved_aside() {
+    let path = temp_portfolio("corrupt");
+    std::fs::write(&path, "{ not json").expect("write corrupt file");
+    let fresh = Portfolio::load(&path);
+    assert!(fresh.holdings.is_empty());
+    assert!(!Path::new(&path).exists());
+
+    let backups: Vec<_> = std::fs::read_dir(std::env::temp_dir()).expect("read temp dir")
+        .map(|e| e.expect("dir entry").path())
+        .filter(|p| p.to_string_lossy().starts_with(&format!("{}.corrupt-", path)))
+        .collect();
+    assert_eq!(backups.len(), 1, "{:?}", backups);
+    for b in backups {
+        std::fs::remove_file(b).expect("remove backup");
+    }
+}
+
+#[tokio::test]
+async fn resolution_settles_by_condition_id() {
+    // Settled by the condition id the arb group carried, with no price_change
+    // ever seen for the market: YES +$5.50, NO −$5.00
+    let (_mock, _, mut p, path) = portfolio_with_sets("resolution").await;
+    let mut changed = false;
+    let msgs = p.on_update(&MarketUpdate::MarketResolved { market: "0xm".to_string(), winning_asset_id: "111".to_string() }, &mut changed);
+    assert_eq!(msgs.len(), 2);
+    assert!(p.holdings.is_empty());
+
+    let mut pnl: Vec<(bool, f64)> = p.resolved.iter().map(|r| (r.won, (r.pnl * 100.0).round() / 100.0)).collect();
+    pnl.sort_by(|a, b| a.1.total_cmp(&b.1));
+    assert_eq!(pnl, [(false, -5.0), (true, 5.5)]);
+    assert!(p.positions_text().contains("No"), "{}", p.positions_text());
+    assert!(p.pnl_text().contains("Realized"), "{}", p.pnl_text());
+    let _ = std::fs::remove_file(&path);
+}
+
+// A stale first Open must not use up the episode: the fresh re-price that
+// follows is traded, and only once
+#[tokio::test(start_paused = true)]
+async fn stale_open_does_not_block_the_episode() {
+    let limits = RiskLimits { max_trade_notional: 1000.0, max_market_notional: 1000.0, max_open_positions: 10, max_consecutive_failures: 5, ..limits() };
+    let kill = no_kill();
+    let (mock, _, executor) = executor_on_mock("test_exec episode wallet", PartialPolicy::Unwind, kill.clone()).await;
+    let risk = Arc::new(RiskManager::new(limits, kill));
+    let path = temp_portfolio("episode");
+    let portfolio = Arc::new(Mutex::new(Portfolio::load(&path)));
+
+    let (arbs, _) = broadcast::channel(16);
+    executor::spawn(arbs.subscribe(), executor, risk, portfolio);
+
+    let stale = ArbOpportunity { detected_at: chrono::Utc::now() - chrono::Duration::seconds(5), ..two_leg_opp() };
+    arbs.send(ArbEvent::Open(stale)).expect("executor subscribed");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(mock.orders().len(), 0, "stale Open is skipped");
+
+    arbs.send(ArbEvent::Open(two_leg_opp())).expect("executor subscribed");
+    arbs.send(ArbEvent::Open(two_leg_opp())).expect("executor subscribed");
+    for _ in 0..50 {
+        if mock.orders().len() >= 2 { break; }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(mock.orders().len(), 2, "one FOK per leg, executed once");
+    let _ = std::fs::remove_file(&path);
+}
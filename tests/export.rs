@@ -0,0 +1,114 @@
+// tests/export.rs — CSV and Parquet export of fetch reports (export.rs)
+//
+// Run with: cargo test --test export
+//
+// The report has one event with a two-way and a three-way market, and one
+// empty bid side. Files are written into a per-test temp dir and read back.
+
+use arrow_array::{Array, Float64Array, RecordBatch, StringArray};
+use latex_telegram_bot::export::{export_report, rows_from_report, ExportFormat};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+fn report() -> Vec<Value> {
+    let side = |outcome: &str, token: &str, bid: Option<&str>, ask: &str| json!({
+        "outcome": outcome, "token_id": token,
+        "best_bid": bid, "best_bid_size": bid.map(|_| "120"),
+        "best_ask": ask, "best_ask_size": "80.5",
+    });
+    vec![json!({
+        "id": "1001",
+        "title": "Lakers vs. Celtics, \"rivalry\" night",
+        "slug": "nba-lal-bos",
+        "endDateHST": "2026-01-17 17:00 HST",
+        "endDateUTC": "2026-01-18T03:00:00Z",
+        "market_entries": [
+            { "question": "Lakers vs. Celtics", "sides": [
+                side("Lakers", "111", Some("0.47"), "0.49"),
+                side("Celtics", "222", None, "0.53"),
+            ]},
+            { "question": "Draw?", "sides": [
+                side("Yes", "333", Some("0.10"), "0.12"),
+                side("No", "444", Some("0.88"), "0.90"),
+                side("Maybe", "555", Some("0.01"), "0.02"),
+            ]},
+        ],
+    })]
+}
+
+// Exports in `format` into a fresh dir; returns the dir and the written file
+fn export(name: &str, format: ExportFormat) -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("test_export_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create test dir");
+    let stem = dir.join("sports_events_20260118T043000Z.json");
+    let written = export_report(&report(), &[format], &stem).expect("export");
+    assert_eq!(written.len(), 1, "{:?}", written);
+    (dir, PathBuf::from(&written[0]))
+}
+
+fn read_parquet(path: &Path) -> RecordBatch {
+    let file = std::fs::File::open(path).expect("open parquet");
+    let mut reader = ParquetRecordBatchReaderBuilder::try_new(file).expect("parquet metadata").build().expect("parquet reader");
+    reader.next().expect("one batch").expect("read batch")
+}
+
+#[test]
+fn one_row_per_outcome() {
+    let rows = rows_from_report(&report());
+    assert_eq!(rows.len(), 5);
+    assert!(rows.iter().all(|r| r.event_id == "1001" && r.end_time_utc == "2026-01-18T03:00:00Z"), "{:?}", rows);
+    assert_eq!(rows.iter().map(|r| r.token_id.as_str()).collect::<Vec<_>>(), ["111", "222", "333", "444", "555"]);
+}
+
+#[test]
+fn prices_parsed_and_empty_sides_none() {
+    let rows = rows_from_report(&report());
+    assert_eq!((rows[0].best_bid, rows[0].best_bid_size), (Some(0.47), Some(120.0)));
+    assert_eq!((rows[1].best_bid, rows[1].best_bid_size), (None, None));
+    assert_eq!((rows[1].best_ask, rows[1].best_ask_size), (Some(0.53), Some(80.5)));
+}
+
+#[test]
+fn csv_header_quoting_and_empty_cells() {
+    let (dir, path) = export("csv", ExportFormat::Csv);
+    assert_eq!(path.extension().and_then(|e| e.to_str()), Some("csv"));
+    let csv = std::fs::read_to_string(&path).expect("read csv");
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], "event_id,title,slug,end_time_utc,question,outcome,token_id,best_bid,best_bid_size,best_ask,best_ask_size");
+    assert!(lines[1].starts_with("1001,\"Lakers vs. Celtics, \"\"rivalry\"\" night\","), "{}", lines[1]);
+    assert!(lines[2].ends_with(",222,,,0.53,80.5"), "{}", lines[2]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn parquet_round_trips_strings_floats_and_nulls() {
+    let (dir, path) = export("parquet", ExportFormat::Parquet);
+    assert_eq!(path.extension().and_then(|e| e.to_str()), Some("parquet"));
+    let batch = read_parquet(&path);
+    assert_eq!((batch.num_rows(), batch.num_columns()), (5, 11));
+
+    let tokens = batch.column_by_name("token_id").expect("token_id column");
+    let tokens = tokens.as_any().downcast_ref::<StringArray>().expect("token_id is utf8");
+    assert_eq!(tokens.value(4), "555");
+
+    let bids = batch.column_by_name("best_bid").expect("best_bid column");
+    let bids = bids.as_any().downcast_ref::<Float64Array>().expect("best_bid is f64");
+    assert_eq!(bids.value(0), 0.47);
+    assert!(bids.is_null(1));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn writes_every_requested_format() {
+    let dir = std::env::temp_dir().join(format!("test_export_both_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("create test dir");
+    let stem = dir.join("report.json");
+    let written = export_report(&report(), &[ExportFormat::Csv, ExportFormat::Parquet], &stem).expect("export");
+    assert_eq!(written.len(), 2, "{:?}", written);
+    assert!(stem.with_extension("csv").exists() && stem.with_extension("parquet").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
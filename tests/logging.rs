@@ -0,0 +1,53 @@
+// tests/logging.rs — secret redaction and log settings (logging.rs)
+//
+// Run with: cargo test --test logging
+
+use latex_telegram_bot::logging::{redact, redact_url, run_id, LogFormat};
+
+const TOKEN: &str = "123456789:AAH-secret_Token";
+
+#[test]
+fn configured_token_redacted_in_urls() {
+    let line = format!("error sending request for url (https://api.telegram.org/bot{}/getUpdates?offset=3)", TOKEN);
+    assert_eq!(
+        redact(&line, &[TOKEN.to_string()]),
+        "error sending request for url (https://api.telegram.org/bot<redacted>/getUpdates?offset=3)"
+    );
+}
+
+#[test]
+fn every_occurrence_redacted() {
+    let line = format!("token={} again {}", TOKEN, TOKEN);
+    assert_eq!(redact(&line, &[TOKEN.to_string()]), "token=<redacted> again <redacted>");
+}
+
+#[test]
+fn empty_secret_ignored() {
+    assert_eq!(redact("plain text", &[String::new()]), "plain text");
+}
+
+#[test]
+fn unknown_bot_tokens_redacted() {
+    assert_eq!(redact_url("https://api.telegram.org/bot987:xyz_ABC-1/sendMessage"), "https://api.telegram.org/bot<redacted>/sendMessage");
+    assert_eq!(redact_url("see /bot987:xyz and /bot55:abc"), "see /bot<redacted> and /bot<redacted>");
+}
+
+#[test]
+fn look_alikes_left_alone() {
+    for text in ["https://example.com/bots/list", "/bot:abc", "/bot123/", "/bot123:", "robot 12:30"] {
+        assert_eq!(redact_url(text), text);
+    }
+}
+
+#[test]
+fn log_format_parses_case_insensitively() {
+    assert_eq!(LogFormat::parse("TEXT"), Some(LogFormat::Text));
+    assert_eq!(LogFormat::parse("json"), Some(LogFormat::Json));
+    assert_eq!(LogFormat::parse("xml"), None);
+}
+
+#[test]
+fn run_ids_are_unique() {
+    let (a, b) = (run_id(), run_id());
+    assert_ne!(a, b);
+}
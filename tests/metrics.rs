@@ -0,0 +1,183 @@
+// tests/metrics.rs — Prometheus counters and the /metrics endpoint (metrics.rs)
+//
+// Run with: cargo test --test metrics
+//
+// Counters are driven through the same code paths the bot uses and read
+// back by scraping the endpoint over HTTP. The registry is process-wide, so
+// each test owns the series it checks.
+
+use latex_telegram_bot::arb::{self, ArbEngine, ArbEvent};
+use latex_telegram_bot::fees::FeeConfig;
+use latex_telegram_bot::metrics::{metrics, serve};
+use latex_telegram_bot::recording::{Recorded, RecordedEvent};
+use latex_telegram_bot::ws::Player;
+use serde_json::json;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+const A: &str = "111";
+const B: &str = "222";
+
+// Value of one series, e.g. `ws_messages_total{event_type="book"}`
+fn value(text: &str, series: &str) -> Option<f64> {
+    text.lines()
+        .find_map(|l| l.strip_prefix(series).and_then(|rest| rest.strip_prefix(' ')))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+fn msg(t: i64, text: String) -> Recorded {
+    Recorded { t, event: RecordedEvent::Msg { shard: 0, text } }
+}
+
+fn book(asset: &str, ask: &str) -> serde_json::Value {
+    json!({ "event_type": "book", "asset_id": asset, "market": "0xm", "bids": [], "asks": [{ "price": ask, "size": "100" }] })
+}
+
+fn price_change(asset: &str, price: &str, size: &str) -> String {
+    json!({
+        "event_type": "price_change",
+        "market": "0xm",
+        "price_changes": [{ "asset_id": asset, "side": "SELL", "price": price, "size": size }]
+    }).to_string()
+}
+
+async fn get(url: &str) -> (u16, String) {
+    let resp = reqwest::get(url).await.expect("metrics endpoint reachable");
+    let status = resp.status().as_u16();
+    (status, resp.text().await.expect("response body"))
+}
+
+// Binds a fresh endpoint on a free port; every one serves the same registry
+async fn endpoint() -> String {
+    let addr = serve("127.0.0.1:0").await.expect("bind metrics endpoint");
+    format!("http://{}", addr)
+}
+
+async fn scrape() -> String {
+    let (status, text) = get(&format!("{}/metrics", endpoint().await)).await;
+    assert_eq!(status, 200, "{}", text);
+    text
+}
+
+struct Session {
+    opens: usize,
+    closes: usize,
+}
+
+// Feeds one WS session through the player and an arb engine, once per test
+// binary — the counters are process-wide, so tests share its effects.
+// Snapshot at 0.55 + 0.60, B drops to 0.40 (arb), B re-prices at 0.41
+// (still open), A's ask is pulled (closed), plus one unknown message.
+fn session() -> &'static Session {
+    static SESSION: OnceLock<Session> = OnceLock::new();
+    SESSION.get_or_init(|| {
+        let event = json!({
+            "id": "1001",
+            "title": "A vs B",
+            "markets": [{
+                "question": "Who wins?",
+                "sportsMarketType": "moneyline",
+                "clobTokenIds": format!("[\"{}\", \"{}\"]", A, B),
+                "outcomes": "[\"A\", \"B\"]"
+            }]
+        });
+        let mut engine = ArbEngine::new(0.98, 0.0, FeeConfig::default());
+        for group in arb::groups_from_event(&event) {
+            engine.add_group(group);
+        }
+        let mut player = Player::new();
+        let mut updates = player.handle().updates();
+        let t0 = 1_700_000_000_000;
+        let records = vec![
+            msg(t0, json!([book(A, "0.55"), book(B, "0.60")]).to_string()),
+            msg(t0 + 100, price_change(B, "0.40", "100")),
+            msg(t0 + 200, price_change(B, "0.41", "100")),
+            msg(t0 + 300, price_change(A, "0.55", "0")),
+            msg(t0 + 400, json!({ "event_type": "something_new", "market": "0xm" }).to_string()),
+        ];
+        let mut events = Vec::new();
+        for record in records {
+            player.feed(record);
+            while let Ok(update) = updates.try_recv() {
+                events.extend(engine.on_update(&update));
+            }
+        }
+        Session {
+            opens: events.iter().filter(|e| matches!(e, ArbEvent::Open(_))).count(),
+            closes: events.iter().filter(|e| matches!(e, ArbEvent::Closed(_))).count(),
+        }
+    })
+}
+
+#[tokio::test]
+async fn rest_latency_and_errors_by_api_and_endpoint() {
+    let started = Instant::now();
+    metrics().observe_request("gamma", "/events", started, true);
+    metrics().observe_request("gamma", "/events", started, true);
+    metrics().observe_request("clob", "/book", started, false);
+
+    let text = scrape().await;
+    assert_eq!(value(&text, r#"polymarket_request_duration_seconds_count{api="gamma",endpoint="/events"}"#), Some(2.0));
+    assert_eq!(value(&text, r#"polymarket_request_errors_total{api="gamma",endpoint="/events"}"#), None);
+    assert_eq!(value(&text, r#"polymarket_request_errors_total{api="clob",endpoint="/book"}"#), Some(1.0));
+}
+
+#[tokio::test]
+async fn events_fetched_per_tag() {
+    metrics().events_fetched.with_label_values(&["100639"]).inc_by(12);
+    let text = scrape().await;
+    assert_eq!(value(&text, r#"gamma_events_fetched_total{tag_id="100639"}"#), Some(12.0));
+}
+
+#[tokio::test]
+async fn ws_messages_counted_by_type() {
+    session();
+    let text = scrape().await;
+    assert_eq!(value(&text, r#"ws_messages_total{event_type="book"}"#), Some(2.0));
+    assert_eq!(value(&text, r#"ws_messages_total{event_type="price_change"}"#), Some(3.0));
+}
+
+#[tokio::test]
+async fn unknown_ws_types_share_one_label() {
+    session();
+    let text = scrape().await;
+    assert_eq!(value(&text, r#"ws_messages_total{event_type="unknown"}"#), Some(1.0));
+    assert!(!text.contains("something_new"), "{}", text);
+}
+
+#[tokio::test]
+async fn arb_window_counted_once_however_often_repriced() {
+    let session = session();
+    assert_eq!((session.opens, session.closes), (2, 1));
+    let text = scrape().await;
+    assert_eq!(value(&text, "arb_opportunities_total"), Some(1.0));
+}
+
+#[tokio::test]
+async fn reconnects_and_telegram_failures() {
+    metrics().ws_reconnects.inc();
+    metrics().tg_send_failures.with_label_values(&["transient"]).inc();
+    let text = scrape().await;
+    assert_eq!(value(&text, "ws_reconnects_total"), Some(1.0));
+    assert_eq!(value(&text, r#"telegram_send_failures_total{kind="transient"}"#), Some(1.0));
+}
+
+#[tokio::test]
+async fn seconds_since_last_message_computed_at_scrape_time() {
+    // After the shared session nothing else marks a message, so the gauge
+    // only grows until this test resets it
+    session();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let silent = value(&scrape().await, "ws_seconds_since_last_message").expect("gauge exported");
+    assert!(silent >= 1.0, "{}", silent);
+
+    metrics().ws_message_seen();
+    let fresh = value(&scrape().await, "ws_seconds_since_last_message").expect("gauge exported");
+    assert!(fresh < 0.5, "{}", fresh);
+}
+
+#[tokio::test]
+async fn only_get_metrics_is_served() {
+    let (status, _) = get(&format!("{}/", endpoint().await)).await;
+    assert_eq!(status, 404);
+}
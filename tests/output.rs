@@ -0,0 +1,103 @@
+// tests/output.rs — snapshot files and atomic writes (output.rs)
+//
+// Run with: cargo test --test output
+//
+// Every test writes into its own directory under the temp dir.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use latex_telegram_bot::output::{save_snapshot, write_atomic, OutputSettings};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("test_output_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("create test dir");
+    dir
+}
+
+fn settings(dir: &Path) -> OutputSettings {
+    OutputSettings {
+        dir: dir.to_string_lossy().to_string(),
+        template: "sports_events_{timestamp}.json".to_string(),
+        latest_name: "latest.json".to_string(),
+        keep_last: 3,
+    }
+}
+
+fn t0() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 1, 18, 4, 30, 0).single().expect("valid time")
+}
+
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir).expect("read test dir")
+        .map(|e| e.expect("dir entry").file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+fn read(path: PathBuf) -> String {
+    fs::read_to_string(path).expect("read file")
+}
+
+#[test]
+fn snapshot_named_from_the_template() {
+    let dir = temp_dir("name");
+    let path = save_snapshot(&settings(&dir), b"[1]", &t0()).expect("save snapshot");
+    assert_eq!(path.file_name().map(|n| n.to_string_lossy().to_string()).as_deref(), Some("sports_events_20260118T043000Z.json"));
+    assert_eq!(read(path), "[1]");
+    assert_eq!(read(dir.join("latest.json")), "[1]");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn keeps_the_newest_snapshots_and_latest_follows() {
+    let dir = temp_dir("retention");
+    for i in 0..5 {
+        save_snapshot(&settings(&dir), format!("[{}]", i + 1).as_bytes(), &(t0() + Duration::minutes(i))).expect("save snapshot");
+    }
+    assert_eq!(file_names(&dir), [
+        "latest.json",
+        "sports_events_20260118T043200Z.json",
+        "sports_events_20260118T043300Z.json",
+        "sports_events_20260118T043400Z.json",
+    ]);
+    assert_eq!(read(dir.join("latest.json")), "[5]");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn retention_leaves_unrelated_files() {
+    let dir = temp_dir("unrelated");
+    fs::write(dir.join("notes.txt"), "keep me").expect("write notes");
+    for i in 0..5 {
+        save_snapshot(&settings(&dir), b"[]", &(t0() + Duration::minutes(i))).expect("save snapshot");
+    }
+    assert_eq!(read(dir.join("notes.txt")), "keep me");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn atomic_write_replaces_contents() {
+    let dir = temp_dir("replace");
+    let target = dir.join("state.json");
+    write_atomic(&target, b"old").expect("first write");
+    write_atomic(&target, b"new").expect("second write");
+    assert_eq!(read(target), "new");
+    assert_eq!(file_names(&dir), ["state.json"], "no temp file left behind");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn failed_atomic_write_keeps_previous_contents() {
+    let dir = temp_dir("failed");
+    let target = dir.join("state.json");
+    write_atomic(&target, b"old").expect("first write");
+
+    // A directory squatting on the temp name makes the write fail part-way
+    fs::create_dir(dir.join("state.json.tmp")).expect("create squatter");
+    assert!(write_atomic(&target, b"new").is_err());
+    assert_eq!(read(target), "old");
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,236 @@
+// tests/replay.rs — session recordings (recording.rs) and ws::Replay
+//
+// Run with: cargo test --test replay
+//
+// Each test writes a synthetic recording of one binary market into its own
+// temp dir; see write_session for what it contains.
+
+use latex_telegram_bot::arb::{self, ArbEngine, ArbEvent};
+use latex_telegram_bot::fees::FeeConfig;
+use latex_telegram_bot::recording::{read_meta, read_recording, RecordedEvent, Recorder};
+use latex_telegram_bot::ws::{MarketUpdate, Replay, ReplayStats};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+const A: &str = "111";
+const B: &str = "222";
+
+fn event() -> Value {
+    json!({
+        "id": "1001",
+        "title": "A vs B",
+        "markets": [{
+            "question": "Who wins?",
+            "sportsMarketType": "moneyline",
+            "clobTokenIds": format!("[\"{}\", \"{}\"]", A, B),
+            "outcomes": "[\"A\", \"B\"]"
+        }]
+    })
+}
+
+fn level(price: &str, size: &str) -> Value {
+    json!({ "price": price, "size": size })
+}
+
+fn book(asset: &str, bids: Vec<Value>, asks: Vec<Value>) -> Value {
+    json!({ "event_type": "book", "asset_id": asset, "market": "0xm", "timestamp": "1700000000000", "bids": bids, "asks": asks })
+}
+
+fn price_change(asset: &str, side: &str, price: &str, size: &str) -> String {
+    json!({
+        "event_type": "price_change",
+        "market": "0xm",
+        "timestamp": "1700000000000",
+        "price_changes": [{ "asset_id": asset, "side": side, "price": price, "size": size }]
+    }).to_string()
+}
+
+// One session: subscribe, initial snapshots (no arb: 0.45 + 0.60), B's ask
+// drops to 0.50 (arb), a REST resync of A, A's 0.45 ask is taken (closed),
+// then the market resolves. Receive times are spread over two seconds.
+fn write_session(dir: &str) -> String {
+    let rec = Recorder::create(dir).expect("create recording");
+    let path = rec.path().display().to_string();
+    let t0 = 1_700_000_000_000;
+    rec.record_at(t0, RecordedEvent::Meta { data: event() });
+    rec.record_at(t0, RecordedEvent::Subscribe { tokens: vec![(A.into(), "A".into()), (B.into(), "B".into())] });
+    let snapshot = json!([
+        book(A, vec![level("0.40", "50")], vec![level("0.55", "100"), level("0.45", "100")]),
+        book(B, vec![level("0.35", "50")], vec![level("0.60", "100")]),
+    ]);
+    rec.record_at(t0 + 100, RecordedEvent::Msg { shard: 0, text: snapshot.to_string() });
+    rec.record_at(t0 + 500, RecordedEvent::Msg { shard: 0, text: price_change(B, "SELL", "0.50", "100") });
+    let rest = book(A, vec![level("0.40", "50")], vec![level("0.55", "100"), level("0.45", "80")]);
+    rec.record_at(t0 + 1000, RecordedEvent::Resync { shard: 0, book: rest });
+    rec.record_at(t0 + 1500, RecordedEvent::Msg { shard: 0, text: price_change(A, "SELL", "0.45", "0") });
+    let resolved = json!({ "event_type": "market_resolved", "market": "0xm", "winning_asset_id": A, "winning_outcome": "A" });
+    rec.record_at(t0 + 2000, RecordedEvent::Msg { shard: 0, text: resolved.to_string() });
+    rec.record_at(t0 + 2000, RecordedEvent::Msg { shard: 0, text: "not json".to_string() });
+    drop(rec);
+    path
+}
+
+// Waits for the writer thread to flush everything after the recorder is dropped
+fn wait_for_records(path: &str, expected: usize) -> usize {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let n = read_recording(path).map(|r| r.count()).unwrap_or(0);
+        if n >= expected || Instant::now() > deadline { return n; }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+struct Run {
+    stats: ReplayStats,
+    updates: Vec<String>,
+    arbs: Vec<String>,
+    elapsed: Duration,
+}
+
+// Replays `path` through an arb engine, collecting every update and arb event
+async fn replay(path: &str, speed: f64) -> Run {
+    let mut engine = ArbEngine::new(0.98, 0.0, FeeConfig::default());
+    for event in read_meta(path).expect("read meta") {
+        for group in arb::groups_from_event(&event) {
+            engine.add_group(group);
+        }
+    }
+    let replay = Replay::open(path, speed).expect("open replay");
+    let handle = replay.handle();
+    let mut updates = handle.updates();
+    let arb_events = arb::spawn(handle.updates(), engine);
+    let mut arb_rx = arb_events.subscribe();
+    drop(arb_events);
+
+    let update_log = tokio::spawn(async move {
+        let mut log = Vec::new();
+        while let Ok(update) = updates.recv().await {
+            log.push(match update {
+                MarketUpdate::Book { asset_id, book } => format!("book {} ask {:?}", asset_id, book.best_ask()),
+                MarketUpdate::PriceChange { changes, .. } => format!("price_change {}", changes.len()),
+                MarketUpdate::MarketResolved { winning_asset_id, .. } => format!("resolved {}", winning_asset_id),
+                other => format!("{:?}", other),
+            });
+        }
+        log
+    });
+    let arb_log = tokio::spawn(async move {
+        let mut log = Vec::new();
+        while let Ok(event) = arb_rx.recv().await {
+            log.push(match event {
+                ArbEvent::Open(opp) => format!("open {:.2}", opp.cost),
+                ArbEvent::Closed(rec) => format!("closed {:.2}", rec.peak_edge),
+            });
+        }
+        log
+    });
+
+    let started = Instant::now();
+    let stats = replay.start().await.expect("replay task");
+    let elapsed = started.elapsed();
+    drop(handle);
+    Run { stats, updates: update_log.await.expect("update log task"), arbs: arb_log.await.expect("arb log task"), elapsed }
+}
+
+fn temp_dir(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("test_replay_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.to_string_lossy().to_string()
+}
+
+// Writes the session into a fresh dir and waits until all of it is on disk.
+// Returns the dir and the recording path.
+fn recorded_session(name: &str) -> (String, String) {
+    let dir = temp_dir(name);
+    let path = write_session(&dir);
+    assert_eq!(wait_for_records(&path, 8), 8, "records flushed");
+    (dir, path)
+}
+
+const LIVE_UPDATES: [&str; 8] = [
+    "book 111 ask Some((0.45, 100.0))",
+    "book 222 ask Some((0.6, 100.0))",
+    "book 222 ask Some((0.5, 100.0))",
+    "price_change 1",
+    "book 111 ask Some((0.45, 80.0))",
+    "book 111 ask Some((0.55, 100.0))",
+    "price_change 1",
+    "resolved 111",
+];
+
+#[test]
+fn records_survive_the_gzip_round_trip() {
+    let (dir, path) = recorded_session("round_trip");
+    let meta = read_meta(&path).expect("read meta");
+    assert_eq!(meta.len(), 1);
+    assert_eq!(meta[0]["id"], "1001");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn records_span_several_flushes() {
+    // A pause between writes puts them in separate gzip members
+    let dir = temp_dir("flushes");
+    let rec = Recorder::create(&dir).expect("create recording");
+    let path = rec.path().display().to_string();
+    rec.record(RecordedEvent::Unsubscribe { ids: vec![A.into()] });
+    std::thread::sleep(Duration::from_millis(1300));
+    rec.record(RecordedEvent::Unsubscribe { ids: vec![B.into()] });
+    drop(rec);
+    assert_eq!(wait_for_records(&path, 2), 2);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replay_counts_records_and_span() {
+    let (dir, path) = recorded_session("stats");
+    let run = replay(&path, 0.0).await;
+    assert_eq!((run.stats.records, run.stats.messages, run.stats.resyncs), (8, 5, 1));
+    assert!((run.stats.recorded_secs() - 2.0).abs() < 1e-9, "{}", run.stats.recorded_secs());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replay_publishes_what_a_live_stream_would() {
+    let (dir, path) = recorded_session("updates");
+    let run = replay(&path, 0.0).await;
+    assert_eq!(run.updates, LIVE_UPDATES);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replay_drives_the_arb_engine() {
+    let (dir, path) = recorded_session("arbs");
+    let run = replay(&path, 0.0).await;
+    // The resync re-prices the open window, so Open fires twice before it closes
+    assert_eq!(run.arbs, ["open 0.95", "open 0.95", "closed 0.05"]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn replays_are_deterministic() {
+    let (dir, path) = recorded_session("determinism");
+    let first = replay(&path, 0.0).await;
+    let second = replay(&path, 0.0).await;
+    assert_eq!(second.updates, first.updates);
+    assert_eq!(second.arbs, first.arbs);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn speed_zero_runs_flat_out() {
+    let (dir, path) = recorded_session("flat_out");
+    let run = replay(&path, 0.0).await;
+    assert!(run.elapsed < Duration::from_millis(500), "{:?}", run.elapsed);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn speed_paces_by_receive_time() {
+    // Two recorded seconds at 10× take about 200ms, and see the same updates
+    let (dir, path) = recorded_session("paced");
+    let run = replay(&path, 10.0).await;
+    assert!(run.elapsed >= Duration::from_millis(190) && run.elapsed < Duration::from_millis(1500), "{:?}", run.elapsed);
+    assert_eq!(run.updates, LIVE_UPDATES);
+    let _ = std::fs::remove_dir_all(&dir);
+}